@@ -0,0 +1,576 @@
+use crate::{
+    tokenizer::{
+        ends_in_escape, unescape, Token, TokenKind, COMMENT, ESCAPE_CHARACTER, FALSE, NEW_LINE,
+        QUOTE, TRUE,
+    },
+    Ast, Error, Location, Node, ParseOptions, Symbol,
+};
+use std::{borrow::Cow, path::PathBuf};
+
+/// Nodes parsed without copying the input. Identifiers and strings borrow from the contents
+/// unless they had to be unescaped, and lists refer to their children by id.
+/// Comments are skipped, like [`crate::parse_str`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arena<'src> {
+    nodes: Vec<ArenaNode<'src>>,
+    children: Vec<NodeId>,
+    roots: Vec<NodeId>,
+}
+
+/// The index of a node in an [`Arena`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(u32);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArenaNode<'src> {
+    pub ast: ArenaAst<'src>,
+    pub location: Location,
+    /// Where the list was closed.
+    end: Option<Location>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArenaAst<'src> {
+    Bool(bool),
+    Identifier(Cow<'src, str>),
+    List(Children),
+    Number(f64),
+    String(Cow<'src, str>),
+}
+
+/// The children of a list, looked up with [`Arena::children`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Children {
+    /// The first node of the list's subtree.
+    first: u32,
+    start: u32,
+    len: u32,
+}
+
+impl<'src> Arena<'src> {
+    /// Parses the contents without copying identifiers or strings.
+    pub fn parse(contents: &'src str) -> Result<Self, Error> {
+        Self::parse_with_options(contents, None, &ParseOptions::default())
+    }
+
+    /// Parses the contents from a file, enforcing the limits in the options.
+    pub fn parse_with_options(
+        contents: &'src str,
+        path: Option<PathBuf>,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        options.check_input(contents, &path)?;
+        let mut location = Location::new(path);
+        location.line = 1;
+        Scanner {
+            contents,
+            options,
+            start: location.clone(),
+            location,
+            state: State::Idle,
+            arena: Arena {
+                nodes: vec![],
+                children: vec![],
+                roots: vec![],
+            },
+            pending: vec![],
+            open: vec![],
+            tokens: 0,
+            depth: 0,
+            parse_error: None,
+        }
+        .scan()
+    }
+
+    /// Returns the top level nodes.
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn get(&self, id: NodeId) -> &ArenaNode<'src> {
+        &self.nodes[id.0 as usize]
+    }
+
+    /// Returns the children of the node, or `None` if it isn't a list.
+    pub fn children(&self, id: NodeId) -> Option<&[NodeId]> {
+        match self.get(id).ast {
+            ArenaAst::List(children) => {
+                let start = children.start as usize;
+                Some(&self.children[start..start + children.len as usize])
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the number of nodes, including those nested in lists.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Copies the node and its children into an owned node.
+    pub fn to_node(&self, id: NodeId) -> Node {
+        let first = match self.get(id).ast {
+            ArenaAst::List(children) => children.first,
+            _ => id.0,
+        };
+        self.build(first..id.0 + 1).pop().flatten().unwrap()
+    }
+
+    /// Copies the top level nodes into owned nodes.
+    pub fn to_nodes(&self) -> Vec<Node> {
+        let mut built = self.build(0..self.nodes.len() as u32);
+        self.roots
+            .iter()
+            .map(|id| built[id.0 as usize].take().unwrap())
+            .collect()
+    }
+
+    /// Builds owned nodes for the ids in order. Children always come before their list,
+    /// so each list takes its children from the nodes already built.
+    fn build(&self, ids: std::ops::Range<u32>) -> Vec<Option<Node>> {
+        let offset = ids.start as usize;
+        let mut built: Vec<Option<Node>> = Vec::with_capacity(ids.len());
+        for id in ids {
+            let node = &self.nodes[id as usize];
+            let token = |kind| Token {
+                kind,
+                location: node.location.clone(),
+            };
+            let ast = match &node.ast {
+                ArenaAst::Bool(b) => Ast::Bool(*b),
+                ArenaAst::Identifier(id) => Ast::Identifier(Symbol::new(id)),
+                ArenaAst::Number(n) => Ast::Number(*n),
+                ArenaAst::String(s) => Ast::String(s.to_string()),
+                ArenaAst::List(_) => Ast::List(
+                    self.children(NodeId(id))
+                        .unwrap()
+                        .iter()
+                        .map(|child| built[child.0 as usize - offset].take().unwrap())
+                        .collect(),
+                ),
+            };
+            let tokens = match (&ast, &node.end) {
+                (Ast::List(_), Some(end)) => vec![
+                    token(TokenKind::Symbol('(')),
+                    Token {
+                        kind: TokenKind::Symbol(')'),
+                        location: end.clone(),
+                    },
+                ],
+                (Ast::Bool(b), _) => vec![token(TokenKind::Bool(*b))],
+                (Ast::Identifier(id), _) => vec![token(TokenKind::Identifier(*id))],
+                (Ast::Number(n), _) => vec![token(TokenKind::Number(*n))],
+                (Ast::String(s), _) => vec![token(TokenKind::String(s.clone()))],
+                _ => vec![],
+            };
+            built.push(Some(Node { ast, tokens }));
+        }
+        built
+    }
+}
+
+impl<'src> std::ops::Index<NodeId> for Arena<'src> {
+    type Output = ArenaNode<'src>;
+
+    fn index(&self, id: NodeId) -> &Self::Output {
+        self.get(id)
+    }
+}
+
+impl ArenaNode<'_> {
+    /// Returns the identifier, if the node is one.
+    pub fn as_identifier(&self) -> Option<&str> {
+        match &self.ast {
+            ArenaAst::Identifier(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Returns the string, if the node is one.
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.ast {
+            ArenaAst::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    /// The byte index the identifier starts at.
+    Identifier(usize),
+    /// The byte index after the opening quote.
+    String(usize),
+    Comment,
+}
+
+/// A list that hasn't been closed yet.
+struct Open {
+    location: Location,
+    /// The number of pending nodes before the list started.
+    pending: usize,
+    first: u32,
+}
+
+/// Reads the contents in a single pass, following the same rules as the tokenizer and parser.
+struct Scanner<'src, 'a> {
+    contents: &'src str,
+    options: &'a ParseOptions,
+    location: Location,
+    state: State,
+    /// Where the current token started.
+    start: Location,
+    arena: Arena<'src>,
+    /// The finished nodes not yet added to a list.
+    pending: Vec<NodeId>,
+    open: Vec<Open>,
+    tokens: usize,
+    depth: usize,
+    /// Returned once the input has been read, since errors reading later tokens take priority.
+    parse_error: Option<Error>,
+}
+
+impl<'src> Scanner<'src, '_> {
+    fn scan(mut self) -> Result<Arena<'src>, Error> {
+        let mut chars = self.contents.char_indices().peekable();
+        let mut prev_char = None;
+        while let Some((i, mut c)) = chars.next() {
+            // Line endings are read as a single new line
+            if c == '\r' {
+                chars.next_if(|(_, c)| *c == NEW_LINE);
+                c = NEW_LINE;
+            }
+            self.push_character(i, c, prev_char)?;
+            prev_char = Some(c);
+
+            if c == NEW_LINE {
+                self.location.column = 0;
+                self.location.line += 1;
+            } else {
+                self.location.column += 1;
+            }
+        }
+
+        match self.state {
+            State::Idle => {}
+            State::Identifier(start) => self.end_identifier(start, self.contents.len())?,
+            State::String(start) => {
+                self.string(start, self.contents.len())?;
+                let contents = normalize_line_endings(&self.contents[start..]);
+                let msg = format!("Unclosed string: {contents}");
+                return Err(Error::new(msg, self.location));
+            }
+            State::Comment => self.token()?,
+        }
+
+        if let Some(open) = self.open.last() {
+            let location = open.location.clone();
+            self.parse_error
+                .get_or_insert_with(|| Error::new("Unclosed list".into(), location));
+        }
+        if let Some(e) = self.parse_error {
+            return Err(e);
+        }
+
+        self.arena.roots = self.pending;
+        Ok(self.arena)
+    }
+
+    /// Processes a single character, mirroring `Tokenizer::push_character`.
+    fn push_character(&mut self, i: usize, c: char, prev_char: Option<char>) -> Result<(), Error> {
+        let is_symbol = c == '(' || c == ')';
+        let is_terminal_character = is_symbol || c.is_whitespace() || c == COMMENT;
+
+        match self.state {
+            State::String(start) => {
+                let is_escaped = prev_char == Some(ESCAPE_CHARACTER)
+                    && (!self.options.backslash_escapes
+                        || ends_in_escape(&self.contents[start..i]));
+                if c == QUOTE && !is_escaped {
+                    self.end_string(start, i)?;
+                }
+            }
+            State::Idle | State::Identifier(_) if c == QUOTE => {
+                if let State::Identifier(start) = self.state {
+                    self.end_identifier(start, i)?;
+                }
+                self.start = self.location.clone();
+                self.state = State::String(i + c.len_utf8());
+            }
+            _ if is_terminal_character => {
+                let was_comment = self.state == State::Comment;
+                if c.is_whitespace() && self.state == State::Idle {
+                    return Ok(());
+                }
+                if let State::Identifier(start) = self.state {
+                    self.end_identifier(start, i)?;
+                }
+
+                if c == COMMENT && !was_comment {
+                    self.start = self.location.clone();
+                    self.state = State::Comment;
+                }
+                if c == NEW_LINE && self.state == State::Comment {
+                    self.state = State::Idle;
+                    self.token()?;
+                }
+                if is_symbol && !was_comment {
+                    self.start = self.location.clone();
+                    match c {
+                        '(' => self.open_list()?,
+                        _ => self.close_list()?,
+                    }
+                }
+            }
+            State::Idle => {
+                self.start = self.location.clone();
+                self.state = State::Identifier(i);
+            }
+            State::Identifier(_) | State::Comment => {}
+        }
+        Ok(())
+    }
+
+    /// Counts a token starting at `self.start`.
+    fn token(&mut self) -> Result<(), Error> {
+        if self.options.max_tokens == Some(self.tokens) {
+            let msg = format!("Input exceeds the maximum of {} tokens", self.tokens);
+            return Err(Error::new(msg, self.start.clone()));
+        }
+        self.tokens += 1;
+        Ok(())
+    }
+
+    /// Adds a finished node, unless the parser would already have stopped.
+    fn add_node(&mut self, ast: ArenaAst<'src>, end: Option<Location>) -> NodeId {
+        let id = NodeId(self.arena.nodes.len() as u32);
+        if self.parse_error.is_none() {
+            self.arena.nodes.push(ArenaNode {
+                ast,
+                location: self.start.clone(),
+                end,
+            });
+            self.pending.push(id);
+        }
+        id
+    }
+
+    fn end_identifier(&mut self, start: usize, end: usize) -> Result<(), Error> {
+        self.state = State::Idle;
+        let contents = &self.contents[start..end];
+
+        let ast = if contents == FALSE || contents == TRUE {
+            ArenaAst::Bool(contents == TRUE)
+        } else if let Ok(n) = contents.parse::<f64>() {
+            ArenaAst::Number(n)
+        } else if contents.starts_with(char::is_numeric) {
+            let msg = format!("Identifier begins with number: {contents}");
+            return Err(Error::new(msg, self.start.clone()));
+        } else {
+            ArenaAst::Identifier(Cow::Borrowed(contents))
+        };
+        self.token()?;
+        self.add_node(ast, None);
+        Ok(())
+    }
+
+    fn end_string(&mut self, start: usize, end: usize) -> Result<(), Error> {
+        self.state = State::Idle;
+        let contents = self.string(start, end)?;
+        self.token()?;
+        self.add_node(ArenaAst::String(contents), None);
+        Ok(())
+    }
+
+    /// Returns the unescaped contents of a string, checking its length before the token is
+    /// counted as `TokenStream` does.
+    fn string(&self, start: usize, end: usize) -> Result<Cow<'src, str>, Error> {
+        let contents = match normalize_line_endings(&self.contents[start..end]) {
+            Cow::Borrowed(s) if !s.contains(ESCAPE_CHARACTER) => Cow::Borrowed(s),
+            s if self.options.backslash_escapes => Cow::Owned(unescape(&s)),
+            Cow::Borrowed(s) if !s.contains("\\\"") => Cow::Borrowed(s),
+            s => Cow::Owned(s.replace("\\\"", "\"")),
+        };
+
+        if let Some(max) = self.options.max_string_length {
+            if contents.len() > max {
+                let msg = format!("String exceeds the maximum length of {max} bytes");
+                return Err(Error::new(msg, self.start.clone()));
+            }
+        }
+        Ok(contents)
+    }
+
+    fn open_list(&mut self) -> Result<(), Error> {
+        self.token()?;
+        self.depth += 1;
+        if let Some(max) = self.options.max_depth {
+            if self.depth > max {
+                let msg = format!("List nesting exceeds the maximum depth of {max}");
+                return Err(Error::new(msg, self.start.clone()));
+            }
+        }
+        self.open.push(Open {
+            location: self.start.clone(),
+            pending: self.pending.len(),
+            first: self.arena.nodes.len() as u32,
+        });
+        Ok(())
+    }
+
+    fn close_list(&mut self) -> Result<(), Error> {
+        self.token()?;
+        self.depth = self.depth.saturating_sub(1);
+        let open = match self.open.pop() {
+            Some(open) => open,
+            None => {
+                let location = self.start.clone();
+                self.parse_error
+                    .get_or_insert_with(|| Error::new("List not started".into(), location));
+                return Ok(());
+            }
+        };
+        if self.parse_error.is_some() {
+            return Ok(());
+        }
+
+        let start = self.arena.children.len() as u32;
+        self.arena
+            .children
+            .extend(self.pending.drain(open.pending..));
+        let children = Children {
+            first: open.first,
+            start,
+            len: self.arena.children.len() as u32 - start,
+        };
+        let end = self.start.clone();
+        self.start = open.location;
+        self.add_node(ArenaAst::List(children), Some(end));
+        Ok(())
+    }
+}
+
+/// Replaces `\r\n` and `\r` with `\n`, as the tokenizer does before reading.
+fn normalize_line_endings(contents: &str) -> Cow<'_, str> {
+    match contents.contains('\r') {
+        true => Cow::Owned(contents.replace("\r\n", "\n").replace('\r', "\n")),
+        false => Cow::Borrowed(contents),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, parser_error, strip_comments, token_error, tokenizer::Tokenizer};
+
+    /// Parses with the tokenizer and parser.
+    fn parse_tokens(contents: &str) -> Result<Vec<Node>, Error> {
+        let tokens = Tokenizer::tokenize(contents, None).map_err(token_error)?;
+        let mut nodes = Parser::parse(tokens).map_err(parser_error)?;
+        strip_comments(&mut nodes);
+        Ok(nodes)
+    }
+
+    #[test]
+    fn parse_borrows_identifiers_and_strings() {
+        let contents = "(def x \"hi\" \"say \\\"hi\\\"\") 2";
+        let arena = Arena::parse(contents).unwrap();
+
+        assert_eq!(2, arena.roots().len());
+        let list = arena.children(arena.roots()[0]).unwrap();
+        let asts: Vec<&ArenaAst> = list.iter().map(|id| &arena[*id].ast).collect();
+        assert!(matches!(
+            asts[0],
+            ArenaAst::Identifier(Cow::Borrowed("def"))
+        ));
+        assert!(matches!(asts[2], ArenaAst::String(Cow::Borrowed("hi"))));
+        assert!(matches!(asts[3], ArenaAst::String(Cow::Owned(s)) if s == "say \"hi\""));
+        assert_eq!(Some("x"), arena[list[1]].as_identifier());
+        assert_eq!(ArenaAst::Number(2.0), arena[arena.roots()[1]].ast);
+        assert_eq!(None, arena.children(arena.roots()[1]));
+    }
+
+    #[test]
+    fn to_nodes_matches_tokenizer_and_parser() {
+        let inputs = [
+            "(a \"b\\\"c\" 1.5 true ; comment (\n (d (e)) false)",
+            "\r\n(a\r\n\"x\r\ny\"\r\n)\r;end",
+            "(a;b;c\n b\"s\"c)",
+            "(inf -2 \"\\\\\" x)",
+            "(\"a\\\\\" \"b\\\\\\\"c\" d)",
+            "())",
+            "((a) (b)",
+            "(\"unclosed",
+            "(1abc)",
+            "",
+        ];
+
+        for input in inputs {
+            let expected = parse_tokens(input);
+            let actual = Arena::parse(input).map(|a| a.to_nodes());
+            assert_eq!(expected, actual, "{input:?}");
+            if let (Err(expected), Err(actual)) = (expected, actual) {
+                assert_eq!(expected.location, actual.location, "{input:?}");
+            }
+        }
+    }
+
+    /// Yields the same inputs on every run, built from pieces that exercise each token.
+    fn generated_inputs(count: usize) -> impl Iterator<Item = String> {
+        const PIECES: [&str; 22] = [
+            "(", ")", "(", ")", " ", "\n", "\r\n", "\r", "\t", ";", "\"", "\\", "\\\"", "\\\\",
+            "a", "bc", "1", "-2.5", "1abc", "true", "inf", "é",
+        ];
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        (0..count).map(move |_| {
+            let len = next() % 24;
+            (0..len).map(|_| PIECES[next() % PIECES.len()]).collect()
+        })
+    }
+
+    #[test]
+    fn to_nodes_matches_tokenizer_and_parser_on_generated_inputs() {
+        let limited = ParseOptions {
+            max_depth: Some(2),
+            max_tokens: Some(6),
+            max_string_length: Some(3),
+            ..Default::default()
+        };
+        let escapes = ParseOptions {
+            backslash_escapes: true,
+            ..Default::default()
+        };
+
+        for input in generated_inputs(20_000) {
+            for options in [&ParseOptions::default(), &limited, &escapes] {
+                let expected = crate::parse_nodes(&input, None, options);
+                let actual = Arena::parse_with_options(&input, None, options).map(|a| a.to_nodes());
+                assert_eq!(expected, actual, "{input:?} {options:?}");
+                if let (Err(expected), Err(actual)) = (expected, actual) {
+                    assert_eq!(expected.location, actual.location, "{input:?} {options:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn to_node_copies_subtree() {
+        let arena = Arena::parse("(a (b (c)) d)").unwrap();
+        let list = arena.children(arena.roots()[0]).unwrap();
+
+        assert_eq!("(b (c))", arena.to_node(list[1]).to_string());
+        assert_eq!("d", arena.to_node(list[2]).to_string());
+        assert_eq!(7, arena.len());
+    }
+}
@@ -0,0 +1,223 @@
+//! Checks Lisper files against lint rules.
+use lisper::{
+    lint::{Diagnostic, Linter},
+    Location,
+};
+use serde_json::{json, Value};
+use std::{path::PathBuf, process::ExitCode};
+
+const USAGE: &str = "Usage: lisper-check [--format human|json] [--disable RULE]... FILE...";
+
+/// The rule name used for files that couldn't be read or parsed.
+const SYNTAX_RULE: &str = "syntax";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Human,
+    Json,
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    format: Format,
+    disabled: Vec<String>,
+    files: Vec<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("lisper-check: {e}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut linter = Linter::new();
+    for rule in &options.disabled {
+        if !linter.disable(rule) {
+            eprintln!(
+                "lisper-check: Unknown rule `{rule}`, expected one of {}",
+                linter.rules().join(", ")
+            );
+            return ExitCode::from(2);
+        }
+    }
+
+    let diagnostics: Vec<Diagnostic> = options
+        .files
+        .iter()
+        .flat_map(|file| check_file(&linter, file))
+        .collect();
+
+    match options.format {
+        Format::Human => {
+            for diagnostic in &diagnostics {
+                println!("{diagnostic}");
+            }
+        }
+        Format::Json => println!("{}", to_json(&diagnostics)),
+    }
+
+    match diagnostics.is_empty() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options {
+        format: Format::Human,
+        disabled: vec![],
+        files: vec![],
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                options.format = match args.next().as_deref() {
+                    Some("human") => Format::Human,
+                    Some("json") => Format::Json,
+                    Some(other) => return Err(format!("Unknown format `{other}`")),
+                    None => return Err("Expected a format after `--format`".into()),
+                }
+            }
+            "--disable" => match args.next() {
+                Some(rule) => options.disabled.push(rule),
+                None => return Err("Expected a rule after `--disable`".into()),
+            },
+            _ if arg.starts_with("--") => return Err(format!("Unknown option `{arg}`")),
+            _ => options.files.push(arg.into()),
+        }
+    }
+
+    if options.files.is_empty() {
+        return Err("Expected at least one file".into());
+    }
+    Ok(options)
+}
+
+/// Runs the linter on the file, reporting read and parse errors as diagnostics.
+fn check_file(linter: &Linter, file: &PathBuf) -> Vec<Diagnostic> {
+    let result = std::fs::read_to_string(file)
+        .map_err(|e| lisper::Error {
+            message: format!("Error reading file: {e}"),
+            location: Location::new(Some(file.clone())),
+        })
+        .and_then(|text| linter.check(&text, Some(file.clone())));
+
+    match result {
+        Ok(diagnostics) => diagnostics,
+        Err(e) => vec![Diagnostic {
+            rule: SYNTAX_RULE,
+            message: e.message,
+            location: e.location,
+        }],
+    }
+}
+
+fn to_json(diagnostics: &[Diagnostic]) -> Value {
+    let diagnostics = diagnostics
+        .iter()
+        .map(|d| {
+            json!({
+                "rule": d.rule,
+                "message": d.message,
+                "location": {
+                    "path": d.location.path().map(|p| p.display().to_string()),
+                    "line": d.location.line,
+                    "column": d.location.column,
+                },
+            })
+        })
+        .collect();
+    Value::Array(diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
+
+    /// A directory under the system temp dir, removed when dropped.
+    struct TempDir(PathBuf);
+    impl TempDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("lisper-check-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn write(&self, name: &str, contents: &str) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn parse_args_reads_options_and_files() {
+        assert_eq!(
+            Ok(Options {
+                format: Format::Json,
+                disabled: vec!["empty-list".into()],
+                files: vec!["a.lisp".into(), "b.lisp".into()],
+            }),
+            args(&[
+                "a.lisp",
+                "--format",
+                "json",
+                "--disable",
+                "empty-list",
+                "b.lisp"
+            ])
+        );
+        assert_eq!(
+            Err("Unknown format `xml`".into()),
+            args(&["--format", "xml", "a.lisp"])
+        );
+        assert_eq!(Err("Expected at least one file".into()), args(&[]));
+    }
+
+    #[test]
+    fn check_file_reports_rules_and_syntax_errors() {
+        let linter = Linter::new();
+        let dir = TempDir::new();
+        let good = dir.write("good.lisp", "(a ())\n");
+        let bad = dir.write("bad.lisp", "(a\n");
+
+        let good_diagnostics = check_file(&linter, &good);
+        let bad_diagnostics = check_file(&linter, &bad);
+
+        assert_eq!(1, good_diagnostics.len());
+        assert_eq!("empty-list", good_diagnostics[0].rule);
+        assert_eq!(
+            format!("{}:1:0: Unclosed list [syntax]", bad.display()),
+            bad_diagnostics[0].to_string()
+        );
+    }
+
+    #[test]
+    fn to_json_includes_locations() {
+        let diagnostics = Linter::new()
+            .check("(a ())", Some("a.lisp".into()))
+            .unwrap();
+
+        assert_eq!(
+            json!([{
+                "rule": "empty-list",
+                "message": "Empty list",
+                "location": {"path": "a.lisp", "line": 1, "column": 3},
+            }]),
+            to_json(&diagnostics)
+        );
+    }
+}
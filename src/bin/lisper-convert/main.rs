@@ -0,0 +1,173 @@
+//! Converts files between JSON and Lisper.
+use lisper::json::{to_json, to_lisper, ObjectStyle};
+use std::{io::Read, path::PathBuf, process::ExitCode};
+
+const USAGE: &str = "Usage: lisper-convert [--to json|lisper] [--map] [--width N] [-o OUTPUT] [INPUT]
+Reads from stdin when no input is given. The direction defaults to the opposite of the input's extension.";
+
+/// The width Lisper output is fit to by default.
+const DEFAULT_WIDTH: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    Json,
+    Lisper,
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    target: Option<Target>,
+    style: ObjectStyle,
+    width: usize,
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("lisper-convert: {e}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("lisper-convert: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options {
+        target: None,
+        style: ObjectStyle::KeywordList,
+        width: DEFAULT_WIDTH,
+        input: None,
+        output: None,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--to" => {
+                options.target = match args.next().as_deref() {
+                    Some("json") => Some(Target::Json),
+                    Some("lisper") => Some(Target::Lisper),
+                    Some(other) => return Err(format!("Unknown target `{other}`")),
+                    None => return Err("Expected a target after `--to`".into()),
+                }
+            }
+            "--map" => options.style = ObjectStyle::Map,
+            "--width" => {
+                options.width = args
+                    .next()
+                    .and_then(|w| w.parse().ok())
+                    .ok_or("Expected a number after `--width`")?
+            }
+            "-o" => match args.next() {
+                Some(output) => options.output = Some(output.into()),
+                None => return Err("Expected a file after `-o`".into()),
+            },
+            _ if arg.starts_with('-') => return Err(format!("Unknown option `{arg}`")),
+            _ if options.input.is_some() => return Err("Expected a single input".into()),
+            _ => options.input = Some(arg.into()),
+        }
+    }
+
+    Ok(options)
+}
+
+/// Returns the format to convert to, based on the input's extension if not given.
+fn target(options: &Options) -> Target {
+    options.target.unwrap_or_else(|| {
+        let extension = options.input.as_ref().and_then(|i| i.extension());
+        match extension.and_then(|e| e.to_str()) {
+            Some("json") => Target::Lisper,
+            _ => Target::Json,
+        }
+    })
+}
+
+fn convert(contents: &str, options: &Options) -> Result<String, String> {
+    match target(options) {
+        Target::Json => {
+            let value = to_json(contents).map_err(String::from)?;
+            let json = serde_json::to_string_pretty(&value).map_err(|e| e.to_string())?;
+            Ok(json + "\n")
+        }
+        Target::Lisper => {
+            let value: serde_json::Value =
+                serde_json::from_str(contents).map_err(|e| format!("Invalid JSON: {e}"))?;
+            to_lisper(&value, options.style, options.width).map_err(|e| e.message)
+        }
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let contents = match &options.input {
+        Some(input) => std::fs::read_to_string(input)
+            .map_err(|e| format!("Error reading file '{}': {e}", input.display()))?,
+        None => {
+            let mut contents = String::new();
+            std::io::stdin()
+                .read_to_string(&mut contents)
+                .map_err(|e| format!("Error reading stdin: {e}"))?;
+            contents
+        }
+    };
+
+    let converted = convert(&contents, options)?;
+    match &options.output {
+        Some(output) => std::fs::write(output, converted)
+            .map_err(|e| format!("Error writing file '{}': {e}", output.display())),
+        None => {
+            print!("{converted}");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(args: &[&str]) -> Options {
+        parse_args(args.iter().map(|a| a.to_string())).unwrap()
+    }
+
+    #[test]
+    fn target_defaults_to_opposite_of_extension() {
+        assert_eq!(Target::Lisper, target(&options(&["config.json"])));
+        assert_eq!(Target::Json, target(&options(&["config.lisp"])));
+        assert_eq!(Target::Json, target(&options(&[])));
+        assert_eq!(Target::Lisper, target(&options(&["--to", "lisper"])));
+    }
+
+    #[test]
+    fn convert_in_both_directions() {
+        let to_lisper = options(&["--to", "lisper", "--width", "20"]);
+        let to_json = options(&["--to", "json"]);
+        let json = "{\n  \"name\": \"box\",\n  \"size\": [\n    10,\n    20\n  ]\n}\n";
+
+        let lisper = convert(json, &to_lisper).unwrap();
+
+        assert_eq!("(:name \"box\"\n :size (10 20))\n", lisper);
+        assert_eq!(json, convert(&lisper, &to_json).unwrap());
+    }
+
+    #[test]
+    fn convert_reports_errors() {
+        let to_json = options(&["--to", "json"]);
+
+        assert_eq!(
+            Err("1:0: Unclosed list".into()),
+            convert("(:name", &to_json)
+        );
+        assert!(convert("{", &options(&["--to", "lisper"]))
+            .unwrap_err()
+            .starts_with("Invalid JSON"));
+    }
+}
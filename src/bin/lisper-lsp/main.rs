@@ -0,0 +1,16 @@
+//! A language server for Lisper files, communicating over stdio.
+mod server;
+mod transport;
+
+fn main() {
+    let stdin = std::io::stdin().lock();
+    let stdout = std::io::stdout().lock();
+    match server::run(stdin, stdout) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("lisper-lsp: {e}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,745 @@
+use crate::transport::{read_body, write_message};
+use lisper::{
+    tokenizer::{Token, TokenKind},
+    Ast, Document, Location, Node,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::{BufRead, Error, Write},
+    path::PathBuf,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SYMBOL_KIND_FUNCTION: i64 = 12;
+const SYMBOL_KIND_VARIABLE: i64 = 13;
+const INDENT: &str = "  ";
+
+/// Serves requests from the reader until the client exits.
+/// Returns whether the client shut down cleanly first.
+pub fn run<R: BufRead, W: Write>(mut reader: R, mut writer: W) -> Result<bool, Error> {
+    let mut server = Server::default();
+    while let Some(body) = read_body(&mut reader)? {
+        let responses = match serde_json::from_slice::<Value>(&body) {
+            Ok(message) => server.handle(&message),
+            Err(e) => vec![json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": {"code": PARSE_ERROR, "message": format!("Parse error: {e}")}
+            })],
+        };
+        for response in responses {
+            write_message(&mut writer, &response)?;
+        }
+        if server.exited {
+            break;
+        }
+    }
+    Ok(server.shut_down)
+}
+
+/// The open documents and the state of the session.
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+    exited: bool,
+}
+
+impl Server {
+    /// Handles a request or notification, returning the messages to send back.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+            None => return self.notify(method, params),
+        };
+
+        let result = match method {
+            "initialize" => Ok(capabilities()),
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/documentSymbol" => self.with_document(params, document_symbols),
+            "textDocument/documentHighlight" => self.with_document(params, |document| {
+                matching_parens(document, &params["position"])
+            }),
+            "textDocument/foldingRange" => self.with_document(params, folding_ranges),
+            "textDocument/formatting" => self.with_document(params, formatting),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method `{method}`"))),
+        };
+
+        vec![match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": code, "message": message}
+            }),
+        }]
+    }
+
+    /// Handles a notification, returning any notifications to send back.
+    fn notify(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        match method {
+            "exit" => self.exited = true,
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents
+                    .insert(uri.clone(), Document::new(text, Some(uri_path(&uri))));
+                return vec![self.diagnostics(&uri)];
+            }
+            "textDocument/didChange" => {
+                let Some(document) = self.documents.get_mut(&uri) else {
+                    return vec![];
+                };
+                for change in params["contentChanges"].as_array().into_iter().flatten() {
+                    let text = change["text"].as_str().unwrap_or_default();
+                    match change.get("range") {
+                        Some(range) => {
+                            let lines = Lines::new(document.text());
+                            let start = lines.offset(&range["start"]);
+                            let end = lines.offset(&range["end"]);
+                            document.apply_edit(start..end.max(start), text);
+                        }
+                        None => *document = Document::new(text, document.path().cloned()),
+                    }
+                }
+                return vec![self.diagnostics(&uri)];
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+            }
+            _ => {}
+        }
+
+        vec![]
+    }
+
+    /// Publishes the error in the document, or clears the previous one.
+    fn diagnostics(&self, uri: &str) -> Value {
+        let diagnostics = match self.documents.get(uri).and_then(|d| Some((d, d.error()?))) {
+            Some((document, error)) => {
+                let lines = Lines::new(document.text());
+                vec![json!({
+                    "range": lines.range(&error.location, 1),
+                    "severity": 1,
+                    "source": "lisper",
+                    "message": error.message,
+                })]
+            }
+            None => vec![],
+        };
+
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {"uri": uri, "diagnostics": diagnostics},
+        })
+    }
+
+    /// Runs the request against the document it names.
+    fn with_document<F>(&self, params: &Value, f: F) -> Result<Value, (i64, String)>
+    where
+        F: FnOnce(&Document) -> Value,
+    {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match self.documents.get(uri) {
+            Some(document) => Ok(f(document)),
+            None => Err((INVALID_PARAMS, format!("Unknown document `{uri}`"))),
+        }
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "capabilities": {
+            // Incremental
+            "textDocumentSync": 2,
+            "documentSymbolProvider": true,
+            "documentHighlightProvider": true,
+            "foldingRangeProvider": true,
+            "documentFormattingProvider": true,
+        },
+        "serverInfo": {"name": "lisper-lsp", "version": env!("CARGO_PKG_VERSION")},
+    })
+}
+
+/// Returns a symbol for every top-level `(def NAME ...)` or `(def (NAME args...) ...)`.
+fn document_symbols(document: &Document) -> Value {
+    let lines = Lines::new(document.text());
+    let mut symbols = vec![];
+    for node in document.nodes() {
+        let Ast::List(children) = &node.ast else {
+            continue;
+        };
+        if !matches!(children.first().map(|n| &n.ast), Some(Ast::Identifier(id)) if id == "def") {
+            continue;
+        }
+
+        let (name, kind) = match children.get(1).map(|n| (n, &n.ast)) {
+            Some((n, Ast::Identifier(_))) => (n, SYMBOL_KIND_VARIABLE),
+            Some((_, Ast::List(signature))) => match signature.first() {
+                Some(n) if matches!(n.ast, Ast::Identifier(_)) => (n, SYMBOL_KIND_FUNCTION),
+                _ => continue,
+            },
+            _ => continue,
+        };
+        let name_text = name.as_identifier().unwrap_or_default();
+
+        symbols.push(json!({
+            "name": name_text,
+            "kind": kind,
+            "range": list_range(&lines, node),
+            "selectionRange": lines.range(&name.first_location(), name_text.chars().count()),
+        }));
+    }
+    Value::Array(symbols)
+}
+
+/// Returns the range from a list's opening paren to its closing paren.
+fn list_range(lines: &Lines, node: &Node) -> Value {
+    let start = lines.range(&node.tokens[0].location, 1);
+    let end = lines.range(&node.tokens[node.tokens.len() - 1].location, 1);
+    json!({"start": start["start"], "end": end["end"]})
+}
+
+/// Returns each opening paren with its closing paren.
+fn paren_pairs(document: &Document) -> Vec<(&Token, &Token)> {
+    let mut pairs = vec![];
+    let mut open = vec![];
+    for token in document.tokens() {
+        match token.kind {
+            TokenKind::Symbol('(') => open.push(token),
+            TokenKind::Symbol(')') => {
+                if let Some(start) = open.pop() {
+                    pairs.push((start, token));
+                }
+            }
+            _ => {}
+        }
+    }
+    pairs
+}
+
+/// Highlights the paren at the position, or just before it, along with its match.
+fn matching_parens(document: &Document, position: &Value) -> Value {
+    let lines = Lines::new(document.text());
+    let (line, column) = lines.location(position);
+    let at = |token: &Token, column: usize| {
+        token.location.line == line && token.location.column == column
+    };
+
+    let pairs = paren_pairs(document);
+    let pair = pairs
+        .iter()
+        .find(|(open, close)| at(open, column) || at(close, column))
+        .or_else(|| {
+            let column = column.checked_sub(1)?;
+            pairs
+                .iter()
+                .find(|(open, close)| at(open, column) || at(close, column))
+        });
+
+    match pair {
+        Some((open, close)) => json!([
+            {"range": lines.range(&open.location, 1), "kind": 1},
+            {"range": lines.range(&close.location, 1), "kind": 1},
+        ]),
+        None => Value::Null,
+    }
+}
+
+/// Folds lists spanning multiple lines and runs of comment lines.
+fn folding_ranges(document: &Document) -> Value {
+    let mut ranges = vec![];
+    for (open, close) in paren_pairs(document) {
+        if close.location.line > open.location.line {
+            ranges.push(json!({
+                "startLine": open.location.line - 1,
+                "endLine": close.location.line - 1,
+            }));
+        }
+    }
+
+    let mut comment_lines: Vec<usize> = document
+        .tokens()
+        .filter(|t| matches!(t.kind, TokenKind::Comment(_)))
+        .map(|t| t.location.line)
+        .collect();
+    comment_lines.dedup();
+    let mut i = 0;
+    while i < comment_lines.len() {
+        let mut end = i;
+        while comment_lines.get(end + 1) == Some(&(comment_lines[end] + 1)) {
+            end += 1;
+        }
+        if end > i {
+            ranges.push(json!({
+                "startLine": comment_lines[i] - 1,
+                "endLine": comment_lines[end] - 1,
+                "kind": "comment",
+            }));
+        }
+        i = end + 1;
+    }
+
+    Value::Array(ranges)
+}
+
+/// Replaces the whole document with its formatted text. Documents with errors are left alone.
+fn formatting(document: &Document) -> Value {
+    if document.error().is_some() {
+        return Value::Null;
+    }
+
+    let formatted = format(document);
+    if formatted == document.text() {
+        return json!([]);
+    }
+
+    let lines = Lines::new(document.text());
+    let last = lines.starts.len() - 1;
+    let end = lines.line(last).encode_utf16().count();
+    json!([{
+        "range": {
+            "start": {"line": 0, "character": 0},
+            "end": {"line": last, "character": end},
+        },
+        "newText": formatted,
+    }])
+}
+
+/// Indents each line by the depth of the lists it is in, with closing parens outdented.
+/// Trailing whitespace is removed. Lines that start inside a string are kept as is.
+/// Depth and strings are taken from the document's tokens, so they match how it was parsed.
+pub fn format(document: &Document) -> String {
+    let text = document.text();
+    let count = text.split('\n').count();
+
+    // The depth at the start of each line, and whether it starts or ends inside a string
+    let mut depths = Vec::with_capacity(count);
+    let mut starts_in_string = vec![false; count];
+    let mut ends_in_string = vec![false; count];
+    let mut depth: usize = 0;
+    for token in document.tokens() {
+        let line = token.location.line - 1;
+        while depths.len() <= line.min(count - 1) {
+            depths.push(depth);
+        }
+        match &token.kind {
+            TokenKind::Symbol('(') => depth += 1,
+            TokenKind::Symbol(')') => depth = depth.saturating_sub(1),
+            TokenKind::String(s) => {
+                let end = (line + s.matches('\n').count()).min(count - 1);
+                for line in line..end {
+                    ends_in_string[line] = true;
+                    starts_in_string[line + 1] = true;
+                }
+            }
+            _ => {}
+        }
+    }
+    depths.resize(count, depth);
+
+    let mut formatted = vec![];
+    for (i, line) in text.split('\n').enumerate() {
+        if starts_in_string[i] {
+            formatted.push(line.to_string());
+            continue;
+        }
+
+        let content = line.trim_start();
+        let content = if ends_in_string[i] {
+            content
+        } else {
+            content.trim_end()
+        };
+        if content.is_empty() {
+            formatted.push(String::new());
+            continue;
+        }
+
+        let closing = content.chars().take_while(|c| *c == ')').count();
+        let indent = INDENT.repeat(depths[i].saturating_sub(closing));
+        formatted.push(format!("{indent}{content}"));
+    }
+    formatted.join("\n")
+}
+
+/// Converts a `file://` URI to a path, decoding percent escapes.
+fn uri_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let path = match path.strip_prefix("localhost") {
+        Some(rest) if rest.starts_with('/') => rest,
+        _ => path,
+    };
+
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = match (byte, tail) {
+            (b'%', [high, low, ..]) if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {
+                std::str::from_utf8(&tail[..2])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            }
+            _ => None,
+        };
+        match escaped {
+            Some(escaped) => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+
+    let path = String::from_utf8_lossy(&bytes).into_owned();
+    // Windows drive paths are written as `/C:/...`
+    match path.as_bytes() {
+        [b'/', drive, b':', ..] if cfg!(windows) && drive.is_ascii_alphabetic() => {
+            PathBuf::from(&path[1..])
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Converts between LSP positions, which count UTF-16 code units, and locations.
+struct Lines<'a> {
+    text: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(text: &'a str) -> Self {
+        let mut starts = vec![0];
+        starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        Self { text, starts }
+    }
+
+    /// Returns the text of the zero based line, without its line ending.
+    fn line(&self, line: usize) -> &'a str {
+        let start = self.starts[line];
+        let end = self
+            .starts
+            .get(line + 1)
+            .map_or(self.text.len(), |next| next - 1);
+        &self.text[start..end]
+    }
+
+    /// Returns the byte offset of the position, clamped to the text.
+    fn offset(&self, position: &Value) -> usize {
+        let line = position["line"].as_u64().unwrap_or_default() as usize;
+        if line >= self.starts.len() {
+            return self.text.len();
+        }
+
+        let mut units = position["character"].as_u64().unwrap_or_default() as usize;
+        let mut offset = self.starts[line];
+        for c in self.line(line).chars() {
+            if units < c.len_utf16() {
+                break;
+            }
+            units -= c.len_utf16();
+            offset += c.len_utf8();
+        }
+        offset
+    }
+
+    /// Returns the one based line and character column of the position.
+    fn location(&self, position: &Value) -> (usize, usize) {
+        let offset = self.offset(position);
+        let line = self.starts.partition_point(|start| *start <= offset) - 1;
+        let column = self.text[self.starts[line]..offset].chars().count();
+        (line + 1, column)
+    }
+
+    /// Returns the range covering the given number of characters from the location.
+    fn range(&self, location: &Location, length: usize) -> Value {
+        let line = location.line.saturating_sub(1).min(self.starts.len() - 1);
+        let mut chars = self.line(line).chars();
+        let start: usize = chars
+            .by_ref()
+            .take(location.column)
+            .map(char::len_utf16)
+            .sum();
+        let length: usize = chars.take(length).map(char::len_utf16).sum();
+        json!({
+            "start": {"line": line, "character": start},
+            "end": {"line": line, "character": start + length},
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays the messages through a server as a client would, returning everything sent back.
+    fn script(messages: &[Value]) -> (bool, Vec<Value>) {
+        let mut input = vec![];
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+
+        let mut output = vec![];
+        let shut_down = run(input.as_slice(), &mut output).unwrap();
+
+        let mut reader = output.as_slice();
+        let mut responses = vec![];
+        while let Some(body) = read_body(&mut reader).unwrap() {
+            responses.push(serde_json::from_slice(&body).unwrap());
+        }
+        (shut_down, responses)
+    }
+
+    fn open(text: &str) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {"textDocument": {"uri": "file:///a.lisp", "languageId": "lisper", "version": 1, "text": text}},
+        })
+    }
+
+    fn request(id: i64, method: &str, params: Value) -> Value {
+        let mut params = params;
+        params["textDocument"] = json!({"uri": "file:///a.lisp"});
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+    }
+
+    fn handle(server: &mut Server, message: Value) -> Value {
+        server.handle(&message).remove(0)
+    }
+
+    #[test]
+    fn run_scripted_session() {
+        let (shut_down, responses) = script(&[
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+            json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+            open("(def x 1)"),
+            request(2, "textDocument/documentSymbol", json!({})),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "shutdown"}),
+            json!({"jsonrpc": "2.0", "method": "exit"}),
+            json!({"jsonrpc": "2.0", "id": 4, "method": "ignored after exit"}),
+        ]);
+
+        assert!(shut_down);
+        assert_eq!(4, responses.len());
+        assert_eq!(
+            2,
+            responses[0]["result"]["capabilities"]["textDocumentSync"]
+        );
+        assert_eq!(json!([]), responses[1]["params"]["diagnostics"]);
+        assert_eq!("x", responses[2]["result"][0]["name"]);
+        assert_eq!(
+            json!({"jsonrpc": "2.0", "id": 3, "result": null}),
+            responses[3]
+        );
+    }
+
+    #[test]
+    fn run_answers_malformed_json_and_continues() {
+        let mut input = b"Content-Length: 8\r\n\r\n{\"id\": 1".to_vec();
+        write_message(
+            &mut input,
+            &json!({"jsonrpc": "2.0", "id": 2, "method": "shutdown"}),
+        )
+        .unwrap();
+
+        let mut output = vec![];
+        let shut_down = run(input.as_slice(), &mut output).unwrap();
+
+        let mut reader = output.as_slice();
+        let parse_error: Value =
+            serde_json::from_slice(&read_body(&mut reader).unwrap().unwrap()).unwrap();
+        let shutdown: Value =
+            serde_json::from_slice(&read_body(&mut reader).unwrap().unwrap()).unwrap();
+        assert!(shut_down);
+        assert_eq!(PARSE_ERROR, parse_error["error"]["code"]);
+        assert_eq!(Value::Null, parse_error["id"]);
+        assert_eq!(json!(2), shutdown["id"]);
+    }
+
+    #[test]
+    fn diagnostics_are_published_on_open_and_change() {
+        let mut server = Server::default();
+
+        let opened = handle(&mut server, open("(a\n  (b"));
+        let diagnostic = &opened["params"]["diagnostics"][0];
+        assert_eq!("Unclosed list", diagnostic["message"]);
+        assert_eq!(
+            json!({"line": 1, "character": 2}),
+            diagnostic["range"]["start"]
+        );
+
+        let changed = handle(
+            &mut server,
+            json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didChange",
+                "params": {
+                    "textDocument": {"uri": "file:///a.lisp", "version": 2},
+                    "contentChanges": [
+                        {"range": {"start": {"line": 1, "character": 4}, "end": {"line": 1, "character": 4}}, "text": "))"}
+                    ]
+                },
+            }),
+        );
+        assert_eq!(json!([]), changed["params"]["diagnostics"]);
+        assert_eq!("(a\n  (b))", server.documents["file:///a.lisp"].text());
+    }
+
+    #[test]
+    fn document_symbols_for_defs() {
+        let mut server = Server::default();
+        server.handle(&open(
+            "(def width 10)\n(def (add a b)\n  (+ a b))\n(print width)",
+        ));
+
+        let response = handle(
+            &mut server,
+            request(1, "textDocument/documentSymbol", json!({})),
+        );
+        let symbols = response["result"].as_array().unwrap();
+
+        assert_eq!(2, symbols.len());
+        assert_eq!("width", symbols[0]["name"]);
+        assert_eq!(SYMBOL_KIND_VARIABLE, symbols[0]["kind"]);
+        assert_eq!(
+            json!({"start": {"line": 0, "character": 5}, "end": {"line": 0, "character": 10}}),
+            symbols[0]["selectionRange"]
+        );
+        assert_eq!("add", symbols[1]["name"]);
+        assert_eq!(SYMBOL_KIND_FUNCTION, symbols[1]["kind"]);
+        assert_eq!(
+            json!({"start": {"line": 1, "character": 0}, "end": {"line": 2, "character": 10}}),
+            symbols[1]["range"]
+        );
+    }
+
+    #[test]
+    fn document_highlight_matches_parens() {
+        let mut server = Server::default();
+        server.handle(&open("(a \"é\" (b))"));
+
+        let position = |character| json!({"position": {"line": 0, "character": character}});
+        let on_open = handle(
+            &mut server,
+            request(1, "textDocument/documentHighlight", position(7)),
+        );
+        let after_close = handle(
+            &mut server,
+            request(2, "textDocument/documentHighlight", position(11)),
+        );
+        let on_atom = handle(
+            &mut server,
+            request(3, "textDocument/documentHighlight", position(2)),
+        );
+
+        assert_eq!(7, on_open["result"][0]["range"]["start"]["character"]);
+        assert_eq!(9, on_open["result"][1]["range"]["start"]["character"]);
+        assert_eq!(0, after_close["result"][0]["range"]["start"]["character"]);
+        assert_eq!(10, after_close["result"][1]["range"]["start"]["character"]);
+        assert_eq!(Value::Null, on_atom["result"]);
+    }
+
+    #[test]
+    fn folding_ranges_for_lists_and_comments() {
+        let mut server = Server::default();
+        server.handle(&open("; a\n; b\n(def x\n  (y))\n; c"));
+
+        let response = handle(
+            &mut server,
+            request(1, "textDocument/foldingRange", json!({})),
+        );
+
+        assert_eq!(
+            json!([
+                {"startLine": 2, "endLine": 3},
+                {"startLine": 0, "endLine": 1, "kind": "comment"},
+            ]),
+            response["result"]
+        );
+    }
+
+    #[test]
+    fn formatting_reindents_by_depth() {
+        let mut server = Server::default();
+        server.handle(&open(
+            "(def (add a b)   \n(+ a\n      b)\n    )\n(s \"x\n   y\")",
+        ));
+
+        let response = handle(
+            &mut server,
+            request(1, "textDocument/formatting", json!({})),
+        );
+
+        assert_eq!(
+            "(def (add a b)\n  (+ a\n    b)\n)\n(s \"x\n   y\")",
+            response["result"][0]["newText"]
+        );
+        assert_eq!(
+            json!({"line": 5, "character": 6}),
+            response["result"][0]["range"]["end"]
+        );
+    }
+
+    #[test]
+    fn formatting_follows_strings_from_tokens() {
+        let mut server = Server::default();
+        server.handle(&open("(a \"x\\\" (\n  y\")\n(b\n c)"));
+
+        let response = handle(
+            &mut server,
+            request(1, "textDocument/formatting", json!({})),
+        );
+
+        assert_eq!(
+            "(a \"x\\\" (\n  y\")\n(b\n  c)",
+            response["result"][0]["newText"]
+        );
+    }
+
+    #[test]
+    fn open_decodes_file_uris() {
+        let mut server = Server::default();
+        let uri = "file:///tmp/my%20dir/%C3%A9%2.lisp";
+        server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {"textDocument": {"uri": uri, "text": "(a)"}},
+        }));
+
+        assert_eq!(
+            Some(&PathBuf::from("/tmp/my dir/é%2.lisp")),
+            server.documents[uri].path()
+        );
+        assert_eq!(
+            PathBuf::from("/a b.lisp"),
+            uri_path("file://localhost/a%20b.lisp")
+        );
+    }
+
+    #[test]
+    fn unknown_method_returns_error() {
+        let mut server = Server::default();
+
+        let response = handle(&mut server, request(1, "textDocument/hover", json!({})));
+
+        assert_eq!(METHOD_NOT_FOUND, response["error"]["code"]);
+    }
+}
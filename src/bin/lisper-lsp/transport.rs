@@ -0,0 +1,83 @@
+use serde_json::Value;
+use std::io::{BufRead, Error, ErrorKind, Write};
+
+/// Reads the body of a single `Content-Length` framed message. Returns `None` at the end of
+/// the input. The body is returned unparsed so invalid JSON can be answered without ending
+/// the session.
+pub fn read_body<R: BufRead>(reader: &mut R) -> Result<Option<Vec<u8>>, Error> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return match length {
+                None => Ok(None),
+                Some(_) => Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "Unexpected end of headers",
+                )),
+            };
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                let value = value.trim().parse::<usize>().map_err(|e| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Invalid Content-Length: {e}"),
+                    )
+                })?;
+                length = Some(value);
+            }
+        }
+    }
+
+    let length =
+        length.ok_or_else(|| Error::new(ErrorKind::InvalidData, "Missing Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+/// Writes a single `Content-Length` framed message.
+pub fn write_message<W: Write>(writer: &mut W, message: &Value) -> Result<(), Error> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn read_body_round_trips_write_message() {
+        let mut buffer = vec![];
+        write_message(&mut buffer, &json!({"id": 1, "text": "héllo"})).unwrap();
+        write_message(&mut buffer, &json!({"id": 2})).unwrap();
+
+        let mut reader = buffer.as_slice();
+        let mut read = || {
+            read_body(&mut reader)
+                .unwrap()
+                .map(|body| serde_json::from_slice::<Value>(&body).unwrap())
+        };
+
+        assert_eq!(Some(json!({"id": 1, "text": "héllo"})), read());
+        assert_eq!(Some(json!({"id": 2})), read());
+        assert_eq!(None, read());
+    }
+
+    #[test]
+    fn read_body_returns_err_on_missing_length() {
+        let mut reader = "Content-Type: json\r\n\r\n{}".as_bytes();
+
+        let result = read_body(&mut reader).unwrap_err();
+
+        assert_eq!("Missing Content-Length", result.to_string());
+    }
+}
@@ -0,0 +1,53 @@
+//! An interactive prompt that echoes how Lisper input is parsed.
+mod print;
+mod session;
+
+use rustyline::{error::ReadlineError, DefaultEditor};
+use session::{Reply, Session};
+use std::path::PathBuf;
+
+const PROMPT: &str = "lisper> ";
+const CONTINUATION_PROMPT: &str = "   ...> ";
+const HISTORY_FILE: &str = ".lisper_history";
+
+fn main() -> Result<(), ReadlineError> {
+    let mut editor = DefaultEditor::new()?;
+    let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+    if let Some(history) = &history {
+        // No history yet on the first run
+        let _ = editor.load_history(history);
+    }
+
+    println!("Lisper {}, :help for commands", env!("CARGO_PKG_VERSION"));
+    let mut session = Session::new();
+    loop {
+        let prompt = match session.is_continuing() {
+            true => CONTINUATION_PROMPT,
+            false => PROMPT,
+        };
+
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                session.cancel();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e),
+        };
+
+        match session.enter(&line) {
+            Reply::Continue => {}
+            Reply::Output { input, output } => {
+                editor.add_history_entry(input)?;
+                println!("{output}");
+            }
+            Reply::Quit => break,
+        }
+    }
+
+    if let Some(history) = &history {
+        editor.save_history(history)?;
+    }
+    Ok(())
+}
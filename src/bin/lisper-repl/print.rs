@@ -0,0 +1,52 @@
+use lisper::{Ast, Node};
+
+const INDENT: usize = 2;
+
+/// Prints each node on its own line with its type and location, indented by depth.
+pub fn tree(node: &Node) -> String {
+    let mut lines = vec![];
+    let mut stack = vec![(node, 0)];
+    while let Some((node, depth)) = stack.pop() {
+        let description = match &node.ast {
+            Ast::Bool(b) => format!("bool {b}"),
+            Ast::Comment(c) => format!("comment {c:?}"),
+            Ast::Identifier(id) => format!("identifier {id}"),
+            Ast::List(children) => {
+                stack.extend(children.iter().rev().map(|child| (child, depth + 1)));
+                "list".to_string()
+            }
+            Ast::Number(n) => format!("number {n}"),
+            Ast::String(s) => format!("string {s:?}"),
+        };
+        lines.push(format!(
+            "{}{description} at {}",
+            " ".repeat(depth * INDENT),
+            node.first_location()
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(contents: &str) -> Node {
+        lisper::parse_str(contents).unwrap().remove(0).into_node()
+    }
+
+    #[test]
+    fn tree_shows_types_and_locations() {
+        let node = node("(a \"s\"\n (1 true))");
+
+        assert_eq!(
+            "list at 1:0
+  identifier a at 1:1
+  string \"s\" at 1:3
+  list at 2:1
+    number 1 at 2:2
+    bool true at 2:4",
+            tree(&node)
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use crate::print::tree;
+use lisper::{
+    parser::{ListErr, Parser, ParserErr},
+    pretty,
+    tokenizer::{StringErr, TokenErr, Tokenizer},
+};
+
+/// The width pretty printed forms are fit to.
+const WIDTH: usize = 60;
+
+pub const HELP: &str = "Enter lists to see how they are parsed. Lists may span multiple lines.
+  :pretty  print each list formatted (default)
+  :tree    print each node with its type and location
+  :help    show this message
+  :quit    exit";
+
+/// How parsed forms are echoed back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Pretty,
+    Tree,
+}
+
+/// The result of entering a line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    /// The input so far is incomplete and needs another line.
+    Continue,
+    /// The input was complete. Holds the full input and what to print.
+    Output {
+        input: String,
+        output: String,
+    },
+    Quit,
+}
+
+/// Collects lines until they form complete input, then parses it.
+pub struct Session {
+    buffer: String,
+    mode: Mode,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            buffer: String::new(),
+            mode: Mode::Pretty,
+        }
+    }
+
+    /// Returns whether earlier lines are waiting to be completed.
+    pub fn is_continuing(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    /// Discards any incomplete input.
+    pub fn cancel(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Adds a line of input.
+    pub fn enter(&mut self, line: &str) -> Reply {
+        if !self.is_continuing() {
+            let command = line.trim();
+            let output = match command {
+                "" => return Reply::Continue,
+                ":quit" | ":q" => return Reply::Quit,
+                ":help" => HELP.to_string(),
+                ":pretty" => {
+                    self.mode = Mode::Pretty;
+                    "Printing formatted lists".to_string()
+                }
+                ":tree" => {
+                    self.mode = Mode::Tree;
+                    "Printing node trees".to_string()
+                }
+                _ if command.starts_with(':') => {
+                    format!("Unknown command `{command}`, try :help")
+                }
+                _ => String::new(),
+            };
+            if !output.is_empty() {
+                return Reply::Output {
+                    input: line.to_string(),
+                    output,
+                };
+            }
+        }
+
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+        if is_incomplete(&self.buffer) {
+            return Reply::Continue;
+        }
+
+        let input = std::mem::take(&mut self.buffer);
+        let output = match lisper::parse_str(&input) {
+            Ok(lists) => lists
+                .into_iter()
+                .map(|l| match self.mode {
+                    Mode::Pretty => pretty(&l.into_node(), WIDTH),
+                    Mode::Tree => tree(&l.into_node()),
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Err(e) => format!("error: {}", String::from(e)),
+        };
+        Reply::Output {
+            input: input.trim_end().to_string(),
+            output,
+        }
+    }
+}
+
+/// Returns whether the input ends inside a list or string, so more lines could complete it.
+fn is_incomplete(input: &str) -> bool {
+    match Tokenizer::tokenize(input, None) {
+        Ok(tokens) => matches!(
+            Parser::parse(tokens),
+            Err(e) if e.kind == ParserErr::List(ListErr::UnclosedList)
+        ),
+        Err(e) => matches!(e.kind, TokenErr::String(StringErr::Unclosed(_))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(reply: Reply) -> String {
+        match reply {
+            Reply::Output { output, .. } => output,
+            reply => panic!("expected output, got {reply:?}"),
+        }
+    }
+
+    #[test]
+    fn enter_echoes_complete_lists() {
+        let mut session = Session::new();
+
+        assert_eq!("(a 1)\n(b)", output(session.enter("(a   1) (b)")));
+    }
+
+    #[test]
+    fn enter_continues_unclosed_lists_and_strings() {
+        let mut session = Session::new();
+
+        assert_eq!(Reply::Continue, session.enter("(def name"));
+        assert_eq!(Reply::Continue, session.enter("  \"multi"));
+        assert!(session.is_continuing());
+        assert_eq!(
+            Reply::Output {
+                input: "(def name\n  \"multi\nline\")".into(),
+                output: "(def name \"multi\nline\")".into(),
+            },
+            session.enter("line\")")
+        );
+        assert!(!session.is_continuing());
+    }
+
+    #[test]
+    fn enter_reports_other_errors_immediately() {
+        let mut session = Session::new();
+
+        assert_eq!(
+            "error: 1:3: List not started",
+            output(session.enter("(a))"))
+        );
+        assert_eq!("error: 1:0: Expected list", output(session.enter("5")));
+    }
+
+    #[test]
+    fn enter_switches_modes() {
+        let mut session = Session::new();
+
+        output(session.enter(":tree"));
+        assert_eq!(
+            "list at 1:0\n  identifier a at 1:1",
+            output(session.enter("(a)"))
+        );
+        output(session.enter(":pretty"));
+        assert_eq!("(a)", output(session.enter("(a)")));
+        assert_eq!(Reply::Quit, session.enter(":quit"));
+    }
+
+    #[test]
+    fn cancel_discards_incomplete_input() {
+        let mut session = Session::new();
+
+        session.enter("(a");
+        session.cancel();
+
+        assert_eq!("(b)", output(session.enter("(b)")));
+    }
+}
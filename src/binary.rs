@@ -0,0 +1,699 @@
+//! A compact binary encoding of parsed trees, for reloading without parsing again.
+//!
+//! The encoding starts with a header of [`MAGIC`], the [`FORMAT_VERSION`], flags for what
+//! location data is kept and whether it holds nodes or lists. It ends with a checksum of
+//! everything before it. Decoding fails if any of these don't match.
+use crate::{
+    list::into_list,
+    tokenizer::{Token, TokenKind},
+    Ast, Error, FileId, List, Location, Node, Symbol,
+};
+use std::collections::HashMap;
+
+/// The first bytes of every encoding.
+pub const MAGIC: &[u8; 4] = b"LSPB";
+/// The version of the format, changed whenever the layout changes.
+pub const FORMAT_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = MAGIC.len() + 2 + 1 + 1;
+const CHECKSUM_LEN: usize = 8;
+
+const FLAG_LOCATIONS: u8 = 1;
+const FLAG_PATHS: u8 = 1 << 1;
+
+const CONTENT_NODES: u8 = 0;
+const CONTENT_LISTS: u8 = 1;
+
+const TAG_FALSE: u8 = 0;
+const TAG_TRUE: u8 = 1;
+const TAG_COMMENT: u8 = 2;
+const TAG_IDENTIFIER: u8 = 3;
+const TAG_LIST: u8 = 4;
+const TAG_NUMBER: u8 = 5;
+const TAG_STRING: u8 = 6;
+const TAG_SYMBOL: u8 = 7;
+/// A token with the same kind and contents as the node it belongs to.
+const TAG_SAME_AS_NODE: u8 = 8;
+
+/// What to keep when encoding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncodeOptions {
+    /// Keep the line and column of every token. Without these, decoded tokens have default locations.
+    pub locations: bool,
+    /// Keep the path of every location, stored once in a table.
+    pub paths: bool,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            locations: true,
+            paths: true,
+        }
+    }
+}
+
+impl EncodeOptions {
+    /// Options keeping only the structure, for the smallest encoding.
+    pub fn stripped() -> Self {
+        Self {
+            locations: false,
+            paths: false,
+        }
+    }
+}
+
+/// Encodes the nodes.
+pub fn encode_nodes(nodes: &[Node], options: &EncodeOptions) -> Vec<u8> {
+    let mut writer = Writer::new(options);
+    writer.varint(nodes.len());
+    writer.nodes(nodes);
+    writer.finish(CONTENT_NODES)
+}
+
+/// Decodes nodes written by [`encode_nodes`].
+pub fn decode_nodes(bytes: &[u8]) -> Result<Vec<Node>, Error> {
+    let mut reader = Reader::new(bytes, CONTENT_NODES)?;
+    let count = reader.varint()?;
+    let nodes = reader.nodes(count)?;
+    reader.finish()?;
+    Ok(nodes)
+}
+
+/// Encodes the lists.
+pub fn encode_lists(lists: &[List], options: &EncodeOptions) -> Vec<u8> {
+    let mut writer = Writer::new(options);
+    writer.varint(lists.len());
+    for list in lists {
+        writer.location(&list.location());
+        writer.varint(list.len());
+        writer.nodes(list.iter());
+    }
+    writer.finish(CONTENT_LISTS)
+}
+
+/// Decodes lists written by [`encode_lists`].
+pub fn decode_lists(bytes: &[u8]) -> Result<Vec<List>, Error> {
+    let mut reader = Reader::new(bytes, CONTENT_LISTS)?;
+    let count = reader.varint()?;
+    let mut lists = Vec::with_capacity(count.min(bytes.len()));
+    for _ in 0..count {
+        let location = reader.location()?;
+        let len = reader.varint()?;
+        let node = Node {
+            ast: Ast::List(reader.nodes(len)?),
+            tokens: vec![Token {
+                kind: TokenKind::Symbol('('),
+                location,
+            }],
+        };
+        lists.push(into_list(node, "list")?);
+    }
+    reader.finish()?;
+    Ok(lists)
+}
+
+/// Returns the 64 bit FNV-1a hash of the bytes.
+pub(crate) fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn invalid<T>(message: &str) -> Result<T, Error> {
+    Err(format!("Invalid binary encoding: {message}").into())
+}
+
+struct Writer {
+    options: EncodeOptions,
+    body: Vec<u8>,
+    paths: HashMap<FileId, usize>,
+}
+
+impl Writer {
+    fn new(options: &EncodeOptions) -> Self {
+        Self {
+            options: *options,
+            body: vec![],
+            paths: HashMap::new(),
+        }
+    }
+
+    /// Writes the header, path table, body and checksum.
+    fn finish(self, content: u8) -> Vec<u8> {
+        let mut flags = 0;
+        if self.options.locations {
+            flags |= FLAG_LOCATIONS;
+        }
+        if self.options.locations && self.options.paths {
+            flags |= FLAG_PATHS;
+        }
+
+        let mut paths: Vec<(&FileId, &usize)> = self.paths.iter().collect();
+        paths.sort_by_key(|(_, i)| **i);
+        let mut table = Writer::new(&self.options);
+        table.varint(paths.len());
+        for (path, _) in paths {
+            table.str(&path.path().to_string_lossy());
+        }
+
+        let mut bytes =
+            Vec::with_capacity(HEADER_LEN + table.body.len() + self.body.len() + CHECKSUM_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.push(flags);
+        bytes.push(content);
+        if flags & FLAG_PATHS != 0 {
+            bytes.extend_from_slice(&table.body);
+        }
+        bytes.extend_from_slice(&self.body);
+        bytes.extend_from_slice(&checksum(&bytes).to_le_bytes());
+        bytes
+    }
+
+    fn varint(&mut self, value: usize) {
+        let mut value = value as u64;
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.body.push(byte);
+                return;
+            }
+            self.body.push(byte | 0x80);
+        }
+    }
+
+    fn str(&mut self, s: &str) {
+        self.varint(s.len());
+        self.body.extend_from_slice(s.as_bytes());
+    }
+
+    fn number(&mut self, n: f64) {
+        self.body.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn location(&mut self, location: &Location) {
+        if !self.options.locations {
+            return;
+        }
+
+        let mut chain = vec![location];
+        while let Some(from) = &chain[chain.len() - 1].expanded_from {
+            chain.push(from);
+        }
+        self.varint(chain.len());
+        for location in chain {
+            self.varint(location.line);
+            self.varint(location.column);
+            if self.options.paths {
+                let index = location.file.map(|file| {
+                    let next = self.paths.len();
+                    *self.paths.entry(file).or_insert(next)
+                });
+                self.varint(index.map_or(0, |i| i + 1));
+            }
+        }
+    }
+
+    /// Writes each node before its children.
+    fn nodes<'a, I>(&mut self, nodes: I)
+    where
+        I: IntoIterator<Item = &'a Node>,
+        I::IntoIter: DoubleEndedIterator,
+    {
+        let mut stack: Vec<&Node> = nodes.into_iter().rev().collect();
+        while let Some(node) = stack.pop() {
+            let kind = match &node.ast {
+                Ast::Bool(false) => {
+                    self.body.push(TAG_FALSE);
+                    TokenKind::Bool(false)
+                }
+                Ast::Bool(true) => {
+                    self.body.push(TAG_TRUE);
+                    TokenKind::Bool(true)
+                }
+                Ast::Comment(c) => {
+                    self.body.push(TAG_COMMENT);
+                    self.str(c);
+                    TokenKind::Comment(c.clone())
+                }
+                Ast::Identifier(id) => {
+                    self.body.push(TAG_IDENTIFIER);
+                    self.str(id);
+                    TokenKind::Identifier(*id)
+                }
+                Ast::List(children) => {
+                    self.body.push(TAG_LIST);
+                    self.varint(children.len());
+                    stack.extend(children.iter().rev());
+                    TokenKind::Symbol('(')
+                }
+                Ast::Number(n) => {
+                    self.body.push(TAG_NUMBER);
+                    self.number(*n);
+                    TokenKind::Number(*n)
+                }
+                Ast::String(s) => {
+                    self.body.push(TAG_STRING);
+                    self.str(s);
+                    TokenKind::String(s.clone())
+                }
+            };
+
+            self.varint(node.tokens.len());
+            for token in &node.tokens {
+                self.token_kind(&token.kind, &kind);
+                self.location(&token.location);
+            }
+        }
+    }
+
+    fn token_kind(&mut self, kind: &TokenKind, node_kind: &TokenKind) {
+        if kind == node_kind && !matches!(kind, TokenKind::Symbol(_)) {
+            self.body.push(TAG_SAME_AS_NODE);
+            return;
+        }
+
+        match kind {
+            TokenKind::Bool(false) => self.body.push(TAG_FALSE),
+            TokenKind::Bool(true) => self.body.push(TAG_TRUE),
+            TokenKind::Comment(c) => {
+                self.body.push(TAG_COMMENT);
+                self.str(c);
+            }
+            TokenKind::Identifier(id) => {
+                self.body.push(TAG_IDENTIFIER);
+                self.str(id);
+            }
+            TokenKind::Number(n) => {
+                self.body.push(TAG_NUMBER);
+                self.number(*n);
+            }
+            TokenKind::String(s) => {
+                self.body.push(TAG_STRING);
+                self.str(s);
+            }
+            TokenKind::Symbol(c) => {
+                self.body.push(TAG_SYMBOL);
+                self.varint(*c as usize);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    flags: u8,
+    paths: Vec<FileId>,
+}
+
+/// A list being decoded, waiting on its children.
+struct Frame {
+    children: Vec<Node>,
+    remaining: usize,
+    tokens: Vec<Token>,
+}
+
+impl<'a> Reader<'a> {
+    /// Checks the header and checksum, then reads the path table.
+    fn new(bytes: &'a [u8], content: u8) -> Result<Self, Error> {
+        if bytes.len() < HEADER_LEN + CHECKSUM_LEN || &bytes[..MAGIC.len()] != MAGIC {
+            return invalid("missing header");
+        }
+
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != FORMAT_VERSION {
+            return invalid(&format!(
+                "format version {version} is not supported, expected {FORMAT_VERSION}"
+            ));
+        }
+
+        let (data, expected) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        let expected = u64::from_le_bytes(expected.try_into().unwrap());
+        let actual = checksum(data);
+        if expected != actual {
+            return invalid(&format!(
+                "checksum mismatch, expected {expected:016x} but got {actual:016x}"
+            ));
+        }
+
+        if bytes[7] != content {
+            return match content {
+                CONTENT_NODES => invalid("expected nodes but found lists"),
+                _ => invalid("expected lists but found nodes"),
+            };
+        }
+
+        let mut reader = Self {
+            bytes: data,
+            position: HEADER_LEN,
+            flags: bytes[6],
+            paths: vec![],
+        };
+        if reader.flags & FLAG_PATHS != 0 {
+            let count = reader.varint()?;
+            for _ in 0..count {
+                let path = reader.str()?;
+                reader.paths.push(FileId::new(path));
+            }
+        }
+        Ok(reader)
+    }
+
+    /// Checks every byte was read.
+    fn finish(&self) -> Result<(), Error> {
+        match self.position == self.bytes.len() {
+            true => Ok(()),
+            false => invalid("unexpected data after the end"),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        match self.bytes.get(self.position) {
+            Some(byte) => {
+                self.position += 1;
+                Ok(*byte)
+            }
+            None => invalid("unexpected end of data"),
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        match self
+            .bytes
+            .get(self.position..self.position.saturating_add(len))
+        {
+            Some(bytes) => {
+                self.position += len;
+                Ok(bytes)
+            }
+            None => invalid("unexpected end of data"),
+        }
+    }
+
+    fn varint(&mut self) -> Result<usize, Error> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return usize::try_from(value).or_else(|_| invalid("length too large"));
+            }
+        }
+        invalid("length too large")
+    }
+
+    fn str(&mut self) -> Result<String, Error> {
+        let len = self.varint()?;
+        match std::str::from_utf8(self.take(len)?) {
+            Ok(s) => Ok(s.to_string()),
+            Err(_) => invalid("string is not UTF-8"),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, Error> {
+        let bytes = self.take(8)?;
+        Ok(f64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn location(&mut self) -> Result<Location, Error> {
+        if self.flags & FLAG_LOCATIONS == 0 {
+            return Ok(Location::default());
+        }
+
+        let len = self.varint()?;
+        let mut chain = vec![];
+        for _ in 0..len {
+            let line = self.varint()?;
+            let column = self.varint()?;
+            let file = match self.flags & FLAG_PATHS != 0 {
+                true => match self.varint()? {
+                    0 => None,
+                    i => match self.paths.get(i - 1) {
+                        Some(file) => Some(*file),
+                        None => return invalid("path index out of range"),
+                    },
+                },
+                false => None,
+            };
+            chain.push(Location {
+                line,
+                column,
+                file,
+                expanded_from: None,
+            });
+        }
+
+        let mut location = match chain.pop() {
+            Some(location) => location,
+            None => return invalid("empty location"),
+        };
+        while let Some(mut next) = chain.pop() {
+            next.expanded_from = Some(Box::new(location));
+            location = next;
+        }
+        Ok(location)
+    }
+
+    fn tokens(&mut self, node_kind: &TokenKind) -> Result<Vec<Token>, Error> {
+        let len = self.varint()?;
+        let mut tokens = vec![];
+        for _ in 0..len {
+            let kind = match self.u8()? {
+                TAG_SAME_AS_NODE => node_kind.clone(),
+                tag => self.token_kind(tag)?,
+            };
+            tokens.push(Token {
+                kind,
+                location: self.location()?,
+            });
+        }
+        Ok(tokens)
+    }
+
+    fn token_kind(&mut self, tag: u8) -> Result<TokenKind, Error> {
+        Ok(match tag {
+            TAG_FALSE => TokenKind::Bool(false),
+            TAG_TRUE => TokenKind::Bool(true),
+            TAG_COMMENT => TokenKind::Comment(self.str()?),
+            TAG_IDENTIFIER => TokenKind::Identifier(Symbol::new(&self.str()?)),
+            TAG_NUMBER => TokenKind::Number(self.number()?),
+            TAG_STRING => TokenKind::String(self.str()?),
+            TAG_SYMBOL => match u32::try_from(self.varint()?).ok().and_then(char::from_u32) {
+                Some(c) => TokenKind::Symbol(c),
+                None => return invalid("symbol is not a character"),
+            },
+            tag => return invalid(&format!("unknown tag {tag}")),
+        })
+    }
+
+    /// Reads the nodes. Lists still waiting for children are kept as frames, so nesting in
+    /// the input doesn't grow the call stack. Lengths come from the input, so vectors grow as nodes are read instead of reserving
+    /// what each list claims to hold.
+    fn nodes(&mut self, count: usize) -> Result<Vec<Node>, Error> {
+        let mut roots = vec![];
+        let mut remaining_roots = count;
+        let mut stack: Vec<Frame> = vec![];
+        loop {
+            let remaining = match stack.last_mut() {
+                Some(frame) => &mut frame.remaining,
+                None => &mut remaining_roots,
+            };
+
+            let node = if *remaining == 0 {
+                match stack.pop() {
+                    Some(frame) => Node {
+                        ast: Ast::List(frame.children),
+                        tokens: frame.tokens,
+                    },
+                    None => return Ok(roots),
+                }
+            } else {
+                *remaining -= 1;
+                let (ast, kind) = match self.u8()? {
+                    TAG_FALSE => (Ast::Bool(false), TokenKind::Bool(false)),
+                    TAG_TRUE => (Ast::Bool(true), TokenKind::Bool(true)),
+                    TAG_COMMENT => {
+                        let c = self.str()?;
+                        (Ast::Comment(c.clone()), TokenKind::Comment(c))
+                    }
+                    TAG_IDENTIFIER => {
+                        let id = Symbol::new(&self.str()?);
+                        (Ast::Identifier(id), TokenKind::Identifier(id))
+                    }
+                    TAG_NUMBER => {
+                        let n = self.number()?;
+                        (Ast::Number(n), TokenKind::Number(n))
+                    }
+                    TAG_STRING => {
+                        let s = self.str()?;
+                        (Ast::String(s.clone()), TokenKind::String(s))
+                    }
+                    TAG_LIST => {
+                        let len = self.varint()?;
+                        let tokens = self.tokens(&TokenKind::Symbol('('))?;
+                        stack.push(Frame {
+                            children: vec![],
+                            remaining: len,
+                            tokens,
+                        });
+                        continue;
+                    }
+                    tag => return invalid(&format!("unknown tag {tag}")),
+                };
+                Node {
+                    tokens: self.tokens(&kind)?,
+                    ast,
+                }
+            };
+
+            match stack.last_mut() {
+                Some(frame) => frame.children.push(node),
+                None => roots.push(node),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        parse_file, parse_nodes,
+        template::{Bindings, Template},
+        ParseOptions,
+    };
+
+    const CONTENTS: &str = "; header
+(def (add a b) (+ a b))
+(def name \"box \\\"one\\\"\" true false -2.5)
+(empty ())";
+
+    fn nodes() -> Vec<Node> {
+        parse_nodes(CONTENTS, Some("data.lisp".into()), &ParseOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn nodes_round_trip() {
+        let nodes = nodes();
+
+        let bytes = encode_nodes(&nodes, &EncodeOptions::default());
+
+        assert_eq!(nodes, decode_nodes(&bytes).unwrap());
+    }
+
+    #[test]
+    fn lists_round_trip() {
+        let lists = parse_file(CONTENTS, "data.lisp".into()).unwrap();
+
+        let bytes = encode_lists(&lists, &EncodeOptions::default());
+
+        assert_eq!(lists, decode_lists(&bytes).unwrap());
+    }
+
+    #[test]
+    fn expansion_chains_round_trip() {
+        let template = Template::parse_file("(inc ?x)", "template".into()).unwrap();
+        let mut bindings = Bindings::new(Location::new(Some("use".into())).increment_line());
+        bindings.bind("x", nodes().remove(0));
+        let nodes = template.instantiate(&bindings).unwrap();
+
+        let bytes = encode_nodes(&nodes, &EncodeOptions::default());
+
+        assert_eq!(nodes, decode_nodes(&bytes).unwrap());
+    }
+
+    #[test]
+    fn stripped_encoding_drops_locations() {
+        let nodes = nodes();
+        let full = encode_nodes(&nodes, &EncodeOptions::default());
+        let without_paths = encode_nodes(
+            &nodes,
+            &EncodeOptions {
+                locations: true,
+                paths: false,
+            },
+        );
+        let stripped = encode_nodes(&nodes, &EncodeOptions::stripped());
+
+        assert!(stripped.len() < without_paths.len() && without_paths.len() < full.len());
+
+        let decoded = decode_nodes(&without_paths).unwrap();
+        assert_eq!(None, decoded[0].first_location().path());
+        assert_eq!(2, decoded[0].first_location().line);
+
+        let decoded = decode_nodes(&stripped).unwrap();
+        assert_eq!(Location::default(), decoded[0].first_location());
+        let display = |nodes: &[Node]| nodes.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(display(&nodes), display(&decoded));
+    }
+
+    #[test]
+    fn decode_returns_err_on_corruption() {
+        let mut bytes = encode_nodes(&nodes(), &EncodeOptions::default());
+        bytes[20] ^= 1;
+
+        let result = decode_nodes(&bytes).unwrap_err();
+
+        assert!(result
+            .message
+            .starts_with("Invalid binary encoding: checksum mismatch"));
+    }
+
+    #[test]
+    fn decode_returns_err_on_version_mismatch() {
+        let mut bytes = encode_nodes(&nodes(), &EncodeOptions::default());
+        bytes[4] = 2;
+
+        let result = decode_nodes(&bytes).unwrap_err();
+
+        assert_eq!(
+            "Invalid binary encoding: format version 2 is not supported, expected 1",
+            result.message
+        );
+    }
+
+    #[test]
+    fn decode_returns_err_on_wrong_content() {
+        let bytes = encode_nodes(&nodes(), &EncodeOptions::default());
+
+        assert_eq!(
+            "Invalid binary encoding: expected lists but found nodes",
+            decode_lists(&bytes).unwrap_err().message
+        );
+        assert_eq!(
+            "Invalid binary encoding: missing header",
+            decode_nodes(b"(def x 1)").unwrap_err().message
+        );
+    }
+
+    #[test]
+    fn decode_returns_err_on_hostile_lengths() {
+        let mut writer = Writer::new(&EncodeOptions::stripped());
+        writer.varint(1);
+        for _ in 0..10_000 {
+            writer.body.push(TAG_LIST);
+            writer.varint(u32::MAX as usize);
+            writer.varint(0);
+        }
+        let bytes = writer.finish(CONTENT_NODES);
+
+        assert_eq!(
+            "Invalid binary encoding: unexpected end of data",
+            decode_nodes(&bytes).unwrap_err().message
+        );
+    }
+
+    #[test]
+    fn deeply_nested_lists_do_not_overflow_the_stack() {
+        let depth = 100_000;
+        let contents = format!("{}{}", "(".repeat(depth), ")".repeat(depth));
+        let nodes = parse_nodes(&contents, None, &ParseOptions::default()).unwrap();
+
+        let decoded = decode_nodes(&encode_nodes(&nodes, &EncodeOptions::default())).unwrap();
+
+        assert_eq!(contents, decoded[0].to_string());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    binary::{checksum, decode_lists, encode_lists, EncodeOptions},
+    parse_file_with_options, Error, List, ParseOptions,
+};
+use std::path::{Path, PathBuf};
+
+/// The version entries were written with. Entries from other versions are ignored.
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+const EXTENSION: &str = "lispercache";
+
+/// An on-disk cache of parsed files, keyed by path, content hash, parse options and crate version.
+/// Entries are stored as one file per source file, overwritten whenever the contents change.
+/// The cache is best effort: entries that can't be read or written are parsed as normal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseCache {
+    dir: Option<PathBuf>,
+}
+
+impl Default for ParseCache {
+    fn default() -> Self {
+        Self::disabled()
+    }
+}
+
+impl ParseCache {
+    /// Creates a cache storing its entries in the directory, which is created when first written to.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+        }
+    }
+
+    /// Creates a cache that always parses and never writes.
+    pub fn disabled() -> Self {
+        Self { dir: None }
+    }
+
+    /// Returns whether entries are read and written.
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Parses the contents of the file, using the cached result if the contents haven't changed.
+    pub fn parse_file(&self, contents: &str, path: PathBuf) -> Result<Vec<List>, Error> {
+        self.parse_file_with_options(contents, path, &ParseOptions::default())
+    }
+
+    /// Parses the contents of the file with the options, using the cached result if the contents
+    /// haven't changed and it was parsed with the same options.
+    pub fn parse_file_with_options(
+        &self,
+        contents: &str,
+        path: PathBuf,
+        options: &ParseOptions,
+    ) -> Result<Vec<List>, Error> {
+        let entry = match self.entry_path(&path) {
+            Some(entry) => entry,
+            None => return parse_file_with_options(contents, path, options),
+        };
+
+        let key = key(&path, checksum(contents.as_bytes()), options);
+        if let Some(lists) = read_entry(&entry, &key) {
+            return Ok(lists);
+        }
+
+        let lists = parse_file_with_options(contents, path, options)?;
+        write_entry(&entry, key, &lists);
+        Ok(lists)
+    }
+
+    /// Removes the entry for the file, if any.
+    pub fn invalidate(&self, path: &Path) -> std::io::Result<()> {
+        match self.entry_path(path) {
+            Some(entry) if entry.exists() => std::fs::remove_file(entry),
+            _ => Ok(()),
+        }
+    }
+
+    /// Removes every entry.
+    pub fn clear(&self) -> std::io::Result<()> {
+        let dir = match &self.dir {
+            Some(dir) if dir.is_dir() => dir,
+            _ => return Ok(()),
+        };
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == EXTENSION) {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn entry_path(&self, path: &Path) -> Option<PathBuf> {
+        let hash = checksum(path.to_string_lossy().as_bytes());
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{hash:016x}.{EXTENSION}")))
+    }
+}
+
+/// Returns the key an entry is stored under: the crate version, source path, content hash and
+/// parse options.
+fn key(path: &Path, hash: u64, options: &ParseOptions) -> Vec<u8> {
+    let path = path.to_string_lossy();
+    let mut key = vec![];
+    for part in [VERSION.as_bytes(), path.as_bytes()] {
+        key.extend_from_slice(&(part.len() as u64).to_le_bytes());
+        key.extend_from_slice(part);
+    }
+    key.extend_from_slice(&hash.to_le_bytes());
+
+    let ParseOptions {
+        max_depth,
+        max_tokens,
+        max_string_length,
+        max_input_bytes,
+        backslash_escapes,
+    } = options;
+    for limit in [max_depth, max_tokens, max_string_length, max_input_bytes] {
+        match limit {
+            Some(limit) => {
+                key.push(1);
+                key.extend_from_slice(&(*limit as u64).to_le_bytes());
+            }
+            None => key.push(0),
+        }
+    }
+    key.push(*backslash_escapes as u8);
+    key
+}
+
+/// Reads the entry if it matches the key.
+fn read_entry(entry: &Path, key: &[u8]) -> Option<Vec<List>> {
+    let bytes = std::fs::read(entry).ok()?;
+    match bytes.strip_prefix(key) {
+        Some(encoded) => decode_lists(encoded).ok(),
+        None => None,
+    }
+}
+
+/// Writes the entry to a temporary file first so readers never see a partial entry.
+fn write_entry(entry: &Path, key: Vec<u8>, lists: &[List]) {
+    let mut bytes = key;
+    bytes.extend(encode_lists(lists, &EncodeOptions::default()));
+
+    let temp = entry.with_extension(format!("{}.tmp", std::process::id()));
+    let written = entry
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&temp, bytes))
+        .and_then(|_| std::fs::rename(&temp, entry));
+    if written.is_err() {
+        let _ = std::fs::remove_file(temp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    fn entries(dir: &Path) -> usize {
+        std::fs::read_dir(dir).map_or(0, |entries| entries.count())
+    }
+
+    #[test]
+    fn parse_file_reuses_entries_for_unchanged_contents() {
+        let dir = TempDir::new("cache-reuse");
+        let cache = ParseCache::new(dir.join("cache"));
+        let path = PathBuf::from("a.lisp");
+
+        let parsed = cache.parse_file("(a 1)", path.clone()).unwrap();
+
+        let entry = cache.entry_path(&path).unwrap();
+        let hash = checksum("(a 1)".as_bytes());
+        let options = ParseOptions::default();
+        assert_eq!(
+            Some(parsed.clone()),
+            read_entry(&entry, &key(&path, hash, &options))
+        );
+        assert_eq!(parsed, cache.parse_file("(a 1)", path.clone()).unwrap());
+        assert_eq!(None, read_entry(&entry, &key(&path, hash + 1, &options)));
+        let other = key(Path::new("b.lisp"), hash, &options);
+        assert_eq!(None, read_entry(&entry, &other));
+    }
+
+    #[test]
+    fn parse_file_with_options_applies_limits_to_cached_files() {
+        let dir = TempDir::new("cache-options");
+        let cache = ParseCache::new(dir.path());
+        let path = PathBuf::from("a.lisp");
+        let contents = "(a (b (c)))";
+        cache.parse_file(contents, path.clone()).unwrap();
+
+        let options = ParseOptions {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let result = cache.parse_file_with_options(contents, path.clone(), &options);
+
+        assert_eq!(
+            "List nesting exceeds the maximum depth of 2",
+            result.unwrap_err().message
+        );
+        let entry = cache.entry_path(&path).unwrap();
+        let hash = checksum(contents.as_bytes());
+        assert_eq!(None, read_entry(&entry, &key(&path, hash, &options)));
+        assert!(read_entry(&entry, &key(&path, hash, &ParseOptions::default())).is_some());
+    }
+
+    #[test]
+    fn parse_file_replaces_entries_for_changed_contents() {
+        let dir = TempDir::new("cache-change");
+        let cache = ParseCache::new(dir.path());
+        let path = PathBuf::from("a.lisp");
+        cache.parse_file("(a 1)", path.clone()).unwrap();
+
+        let changed = cache.parse_file("(a 2) (b)", path.clone()).unwrap();
+
+        assert_eq!(crate::parse_file("(a 2) (b)", path).unwrap(), changed);
+    }
+
+    #[test]
+    fn parse_file_ignores_corrupt_entries() {
+        let dir = TempDir::new("cache-corrupt");
+        let cache = ParseCache::new(dir.path());
+        let path = PathBuf::from("a.lisp");
+        cache.parse_file("(a 1)", path.clone()).unwrap();
+        let entry = cache.entry_path(&path).unwrap();
+        let mut bytes = std::fs::read(&entry).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        std::fs::write(&entry, bytes).unwrap();
+
+        let result = cache.parse_file("(a 1)", path.clone()).unwrap();
+
+        assert_eq!(crate::parse_file("(a 1)", path).unwrap(), result);
+    }
+
+    #[test]
+    fn parse_file_returns_err_without_writing_entries() {
+        let dir = TempDir::new("cache-error");
+        let cache = ParseCache::new(dir.path());
+
+        let result = cache.parse_file("(a", "a.lisp".into());
+
+        assert_eq!("Unclosed list", result.unwrap_err().message);
+        assert_eq!(0, entries(&dir));
+    }
+
+    #[test]
+    fn invalidate_and_clear_remove_entries() {
+        let dir = TempDir::new("cache-clear");
+        let cache = ParseCache::new(dir.path());
+        cache.parse_file("(a)", "a.lisp".into()).unwrap();
+        cache.parse_file("(b)", "b.lisp".into()).unwrap();
+
+        cache.invalidate(Path::new("a.lisp")).unwrap();
+        assert_eq!(1, entries(&dir));
+
+        cache.clear().unwrap();
+        assert_eq!(0, entries(&dir));
+    }
+
+    #[test]
+    fn disabled_cache_never_writes() {
+        let cache = ParseCache::disabled();
+
+        let result = cache.parse_file("(a)", "a.lisp".into()).unwrap();
+
+        assert!(!cache.is_enabled());
+        assert_eq!(crate::parse_file("(a)", "a.lisp".into()).unwrap(), result);
+        assert_eq!(None, cache.entry_path(Path::new("a.lisp")));
+    }
+}
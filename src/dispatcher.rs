@@ -0,0 +1,304 @@
+use crate::{err, Error, List, Node};
+use std::collections::HashMap;
+
+/// A value that can be popped off the front of a list as a command argument.
+pub trait Argument: Sized {
+    /// A description of the argument used in error messages.
+    const DESCRIPTION: &'static str;
+
+    /// Pops the argument off the list.
+    fn pop(list: &mut List, msg: &str) -> Result<Self, Error>;
+}
+
+impl Argument for bool {
+    const DESCRIPTION: &'static str = "a bool";
+    fn pop(list: &mut List, msg: &str) -> Result<Self, Error> {
+        Ok(list.pop_bool(msg)?.0)
+    }
+}
+
+impl Argument for f64 {
+    const DESCRIPTION: &'static str = "a number";
+    fn pop(list: &mut List, msg: &str) -> Result<Self, Error> {
+        Ok(list.pop_float(msg)?.0)
+    }
+}
+
+impl Argument for i64 {
+    const DESCRIPTION: &'static str = "an int";
+    fn pop(list: &mut List, msg: &str) -> Result<Self, Error> {
+        Ok(list.pop_integer(msg)?.0)
+    }
+}
+
+impl Argument for String {
+    const DESCRIPTION: &'static str = "a string";
+    fn pop(list: &mut List, msg: &str) -> Result<Self, Error> {
+        Ok(list.pop_string(msg)?.0)
+    }
+}
+
+impl Argument for List {
+    const DESCRIPTION: &'static str = "a list";
+    fn pop(list: &mut List, msg: &str) -> Result<Self, Error> {
+        list.pop_list(msg)
+    }
+}
+
+impl Argument for Node {
+    const DESCRIPTION: &'static str = "a value";
+    fn pop(list: &mut List, msg: &str) -> Result<Self, Error> {
+        list.pop_front(msg)
+    }
+}
+
+/// A function that can handle a command, converting each argument from the list.
+pub trait Handler<Args, T> {
+    /// The number of arguments the handler takes.
+    const ARITY: usize;
+
+    /// Converts the arguments and calls the handler.
+    fn call(&mut self, command: &str, args: &mut List) -> Result<T, Error>;
+}
+
+macro_rules! impl_handler {
+    ($($arg:ident),*) => {
+        impl<F, T, $($arg),*> Handler<($($arg,)*), T> for F
+        where
+            F: FnMut($($arg),*) -> T,
+            $($arg: Argument,)*
+        {
+            const ARITY: usize = <[&str]>::len(&[$(stringify!($arg)),*]);
+
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn call(&mut self, command: &str, args: &mut List) -> Result<T, Error> {
+                let mut position = 0;
+                $(
+                    position += 1;
+                    let $arg = $arg::pop(
+                        args,
+                        &format!("argument {position} of `{command}` to be {}", $arg::DESCRIPTION),
+                    )?;
+                )*
+                Ok(self($($arg),*))
+            }
+        }
+    };
+}
+
+impl_handler!();
+impl_handler!(A);
+impl_handler!(A, B);
+impl_handler!(A, B, C);
+impl_handler!(A, B, C, D);
+impl_handler!(A, B, C, D, E);
+impl_handler!(A, B, C, D, E, G);
+impl_handler!(A, B, C, D, E, G, H);
+impl_handler!(A, B, C, D, E, G, H, I);
+
+type BoxedHandler<T> = Box<dyn FnMut(&str, &mut List) -> Result<T, Error>>;
+
+/// Maps the identifier at the head of a list to a typed handler.
+///
+/// ```
+/// let mut dispatcher = lisper::dispatcher::Dispatcher::new();
+/// dispatcher.register("spawn", |name: String, x: f64, y: f64| format!("{name} at {x},{y}"));
+///
+/// let lists = lisper::parse_str("(spawn \"orc\" 10 20)").unwrap();
+/// assert_eq!(dispatcher.dispatch_all(lists).unwrap(), vec!["orc at 10,20"]);
+/// ```
+pub struct Dispatcher<T> {
+    handlers: HashMap<String, (usize, BoxedHandler<T>)>,
+}
+
+impl<T> Default for Dispatcher<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Dispatcher<T> {
+    /// Creates a dispatcher with no commands.
+    pub fn new() -> Self {
+        Self {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registers the handler for the command, replacing any existing one.
+    pub fn register<Args, H>(&mut self, command: &str, mut handler: H) -> &mut Self
+    where
+        H: Handler<Args, T> + 'static,
+    {
+        self.handlers.insert(
+            command.to_string(),
+            (
+                H::ARITY,
+                Box::new(move |command, args| handler.call(command, args)),
+            ),
+        );
+        self
+    }
+
+    /// Returns whether the command has a handler.
+    pub fn contains(&self, command: &str) -> bool {
+        self.handlers.contains_key(command)
+    }
+
+    /// Calls the handler for the command at the head of the list.
+    pub fn dispatch(&mut self, mut list: List) -> Result<T, Error> {
+        let location = list.location();
+        let (command, _) = list.pop_identifier("a command name")?;
+
+        let (arity, handler) = match self.handlers.get_mut(command.as_str()) {
+            Some(entry) => entry,
+            None => {
+                let message = match suggest(&command, self.handlers.keys()) {
+                    Some(suggestion) => {
+                        format!("Unknown command `{command}`, did you mean `{suggestion}`?")
+                    }
+                    None => format!("Unknown command `{command}`"),
+                };
+                return err(&message, &location);
+            }
+        };
+
+        if list.len() != *arity {
+            return err(
+                &format!(
+                    "`{command}` expects {arity} arguments but got {}",
+                    list.len()
+                ),
+                &location,
+            );
+        }
+
+        handler(&command, &mut list)
+    }
+
+    /// Dispatches each list in order, stopping at the first error.
+    pub fn dispatch_all(&mut self, lists: Vec<List>) -> Result<Vec<T>, Error> {
+        lists.into_iter().map(|list| self.dispatch(list)).collect()
+    }
+}
+
+/// Returns the closest known command if it is near enough to be a likely typo.
+fn suggest<'a>(command: &str, known: impl Iterator<Item = &'a String>) -> Option<&'a str> {
+    let max_distance = command.chars().count().div_ceil(3);
+    known
+        .map(|k| (edit_distance(command, k), k))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, k)| k.as_str())
+}
+
+/// Returns the Levenshtein distance between the two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    fn list(contents: &str) -> List {
+        crate::parse_str(contents).unwrap().remove(0)
+    }
+
+    #[test]
+    fn dispatch_converts_arguments() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.register("spawn", |name: String, x: f64, y: i64, hostile: bool| {
+            format!("{name} {x} {y} {hostile}")
+        });
+
+        let actual = dispatcher.dispatch(list("(spawn \"orc\" 1.5 20 true)"));
+
+        assert_eq!("orc 1.5 20 true", actual.unwrap());
+    }
+
+    #[test]
+    fn dispatch_calls_handlers_with_state() {
+        let count = Rc::new(RefCell::new(0));
+        let mut dispatcher = Dispatcher::new();
+        let c = count.clone();
+        dispatcher.register("tick", move || *c.borrow_mut() += 1);
+
+        dispatcher
+            .dispatch_all(crate::parse_str("(tick) (tick) (tick)").unwrap())
+            .unwrap();
+
+        assert_eq!(3, *count.borrow());
+    }
+
+    #[test]
+    fn dispatch_returns_err_on_wrong_arity() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.register("spawn", |_: String, _: f64, _: f64| ());
+
+        let result = dispatcher
+            .dispatch(list("\n  (spawn \"orc\" 10)"))
+            .unwrap_err();
+
+        assert_eq!("`spawn` expects 3 arguments but got 2", result.message);
+        assert_eq!(2, result.location.line);
+        assert_eq!(2, result.location.column);
+    }
+
+    #[test]
+    fn dispatch_returns_err_on_wrong_type() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.register("spawn", |_: String, _: f64| ());
+
+        let result = dispatcher.dispatch(list("(spawn 10 \"orc\")")).unwrap_err();
+
+        assert_eq!(
+            "Expected argument 1 of `spawn` to be a string",
+            result.message
+        );
+        assert_eq!(7, result.location.column);
+    }
+
+    #[test]
+    fn dispatch_suggests_similar_command() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.register("spawn", |_: String| ());
+        dispatcher.register("despawn", |_: String| ());
+
+        let result = dispatcher.dispatch(list("(spwan \"orc\")")).unwrap_err();
+
+        assert_eq!(
+            "Unknown command `spwan`, did you mean `spawn`?",
+            result.message
+        );
+    }
+
+    #[test]
+    fn dispatch_returns_err_on_unknown_command() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.register("spawn", |_: String| ());
+
+        let result = dispatcher.dispatch(list("(teleport)")).unwrap_err();
+
+        assert_eq!("Unknown command `teleport`", result.message);
+    }
+
+    #[test]
+    fn edit_distance_counts_edits() {
+        assert_eq!(0, edit_distance("spawn", "spawn"));
+        assert_eq!(2, edit_distance("spwan", "spawn"));
+        assert_eq!(2, edit_distance("spawn", "despawn"));
+        assert_eq!(5, edit_distance("", "spawn"));
+    }
+}
@@ -0,0 +1,395 @@
+use crate::{
+    list,
+    parser::Parser,
+    parser_error, strip_comments, token_error,
+    tokenizer::{Token, TokenKind, Tokenizer},
+    Error, List, Location, Node,
+};
+use std::{ops::Range, path::PathBuf};
+
+/// A parsed file that can be edited in place, for editor integrations.
+///
+/// Edits only re-tokenize and reparse the top-level forms they touch.
+/// Forms after the edit are reused, with their locations shifted to match the new text.
+/// The result always matches parsing the whole text again.
+#[derive(Debug, Clone)]
+pub struct Document {
+    text: String,
+    path: Option<PathBuf>,
+    forms: Vec<Form>,
+    error: Option<Error>,
+}
+
+/// A top-level node along with the tokens it was made from.
+#[derive(Debug, Clone)]
+struct Form {
+    /// The byte offset of the first token.
+    start: usize,
+    tokens: Vec<Token>,
+    /// `None` for top-level comments.
+    node: Option<Node>,
+}
+
+impl Document {
+    /// Parses the text into a document.
+    pub fn new(text: &str, path: Option<PathBuf>) -> Self {
+        let mut document = Self {
+            text: String::new(),
+            path,
+            forms: vec![],
+            error: None,
+        };
+        document.apply_edit(0..0, text);
+        document
+    }
+
+    /// Returns the text of the document, with line endings normalized to `\n`.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the path of the document.
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+
+    /// Returns the first error in the document, if any.
+    /// Nodes and tokens are only available up to the form containing the error.
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    /// Returns the top-level nodes with comments removed.
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.forms.iter().filter_map(|f| f.node.as_ref())
+    }
+
+    /// Returns every token, including comments.
+    pub fn tokens(&self) -> impl Iterator<Item = &Token> {
+        self.forms.iter().flat_map(|f| &f.tokens)
+    }
+
+    /// Returns the top-level lists, as `parse_file` would.
+    pub fn lists(&self) -> Result<Vec<List>, Error> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        self.nodes().map(|n| list(n, "list")).collect()
+    }
+
+    /// Replaces the byte range with the text and reparses the forms it touches.
+    /// Returns the byte range of the new text that was reparsed.
+    ///
+    /// Panics if the range is out of bounds or doesn't lie on character boundaries.
+    pub fn apply_edit(&mut self, range: Range<usize>, text: &str) -> Range<usize> {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+
+        // Start from the form before the edit, as the edit may extend its last token
+        let first = self.forms.iter().rposition(|f| f.start < range.start);
+        let (first, region_start, start_location) = match first {
+            Some(i) => (
+                i,
+                self.forms[i].start,
+                self.forms[i].tokens[0].location.clone(),
+            ),
+            None => (0, 0, Location::new(self.path.clone()).increment_line()),
+        };
+        let old_end = range.end;
+        let old_end_location = advance(start_location.clone(), &self.text[region_start..old_end]);
+        self.text.replace_range(range.clone(), &text);
+        let new_end = range.start + text.len();
+        let new_end_location = advance(start_location.clone(), &self.text[region_start..new_end]);
+
+        // Tokenize up to each following form in turn, stopping at the first that
+        // starts cleanly outside of any list, string or comment.
+        let candidates = self.forms.partition_point(|f| f.start <= old_end);
+        let mut tokenizer = Tokenizer::starting_at(start_location.clone());
+        let mut tokens = vec![];
+        let mut depth = 0;
+        let mut balanced = true;
+        let mut position = region_start;
+        let mut prev_char = None;
+        let mut reused = None;
+        let mut result = Ok(());
+        for i in candidates..=self.forms.len() {
+            let end = match self.forms.get(i) {
+                Some(form) => form.start - old_end + new_end,
+                None => self.text.len(),
+            };
+            let chunk = &self.text[position..end];
+            if let Err(e) = tokenizer.push_str(chunk, prev_char) {
+                result = Err(token_error(e));
+                break;
+            }
+            prev_char = chunk.chars().next_back().or(prev_char);
+            position = end;
+
+            for token in tokenizer.take_tokens() {
+                match token.kind {
+                    TokenKind::Symbol('(') => depth += 1,
+                    TokenKind::Symbol(')') if depth == 0 => balanced = false,
+                    TokenKind::Symbol(')') => depth -= 1,
+                    _ => {}
+                }
+                tokens.push(token);
+            }
+
+            // A stray paren is only reported if the rest of the file tokenizes
+            if i < self.forms.len() && tokenizer.is_idle() && depth == 0 && balanced {
+                reused = Some(i);
+                break;
+            }
+        }
+        if reused.is_none() && result.is_ok() {
+            result = tokenizer.flush().map_err(token_error);
+            tokens.append(&mut tokenizer.take_tokens());
+        }
+
+        let forms =
+            result.and_then(|()| make_forms(tokens, &self.text, region_start, start_location));
+        match (forms, reused) {
+            (Ok(forms), Some(reused)) => {
+                let mut tail = self.forms.split_off(reused);
+                for form in &mut tail {
+                    form.start = form.start - old_end + new_end;
+                    for token in &mut form.tokens {
+                        shift(&mut token.location, &old_end_location, &new_end_location);
+                    }
+                    if let Some(node) = &mut form.node {
+                        shift_node(node, &old_end_location, &new_end_location);
+                    }
+                }
+                if let Some(e) = &mut self.error {
+                    shift(&mut e.location, &old_end_location, &new_end_location);
+                }
+
+                self.forms.truncate(first);
+                self.forms.extend(forms);
+                self.forms.append(&mut tail);
+                region_start..position
+            }
+            (Ok(forms), None) => {
+                self.forms.truncate(first);
+                self.forms.extend(forms);
+                self.error = None;
+                region_start..self.text.len()
+            }
+            (Err(e), _) => {
+                self.forms.truncate(first);
+                self.error = Some(e);
+                region_start..self.text.len()
+            }
+        }
+    }
+}
+
+/// Splits the tokens into top-level forms and parses each one.
+fn make_forms(
+    tokens: Vec<Token>,
+    text: &str,
+    mut offset: usize,
+    mut location: Location,
+) -> Result<Vec<Form>, Error> {
+    let mut groups: Vec<Vec<Token>> = vec![];
+    let mut depth: usize = 0;
+    for token in tokens {
+        if depth == 0 {
+            groups.push(vec![]);
+        }
+        match token.kind {
+            TokenKind::Symbol('(') => depth += 1,
+            TokenKind::Symbol(')') => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        groups.last_mut().unwrap().push(token);
+    }
+
+    let mut forms = vec![];
+    for tokens in groups {
+        // Walk the text up to the first token to find its byte offset
+        let target = &tokens[0].location;
+        while (location.line, location.column) < (target.line, target.column) {
+            let c = text[offset..].chars().next().unwrap();
+            offset += c.len_utf8();
+            location = advance(location, &text[offset - c.len_utf8()..offset]);
+        }
+
+        let mut nodes = Parser::parse(tokens.clone()).map_err(parser_error)?;
+        strip_comments(&mut nodes);
+        forms.push(Form {
+            start: offset,
+            tokens,
+            node: nodes.pop(),
+        });
+    }
+
+    Ok(forms)
+}
+
+/// Moves the location past the text, the same way the tokenizer does.
+fn advance(mut location: Location, text: &str) -> Location {
+    for c in text.chars() {
+        if c == '\n' {
+            location.line += 1;
+            location.column = 0;
+        } else {
+            location.column += 1;
+        }
+    }
+    location
+}
+
+/// Moves a location following an edit from where the edit used to end to where it ends now.
+fn shift(location: &mut Location, old_end: &Location, new_end: &Location) {
+    if location.line == old_end.line {
+        location.column = location.column - old_end.column + new_end.column;
+    }
+    location.line = location.line - old_end.line + new_end.line;
+}
+
+fn shift_node(node: &mut Node, old_end: &Location, new_end: &Location) {
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        for token in &mut node.tokens {
+            shift(&mut token.location, old_end, new_end);
+        }
+        if let crate::Ast::List(children) = &mut node.ast {
+            stack.extend(children.iter_mut());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_nodes, ParseOptions};
+
+    /// Asserts the document matches a full parse of its text.
+    fn assert_matches_full_parse(document: &Document) {
+        let path = document.path().cloned();
+        match parse_nodes(document.text(), path.clone(), &ParseOptions::default()) {
+            Ok(nodes) => {
+                assert_eq!(None, document.error());
+                assert_eq!(nodes, document.nodes().cloned().collect::<Vec<_>>());
+                let tokens = Tokenizer::tokenize(document.text(), path).unwrap();
+                assert_eq!(tokens, document.tokens().cloned().collect::<Vec<_>>());
+            }
+            Err(e) => {
+                let actual = document.error().expect("expected an error");
+                assert_eq!(e.message, actual.message);
+                assert_eq!(e.location, actual.location);
+            }
+        }
+    }
+
+    fn edit(document: &mut Document, find: &str, replace: &str) -> Range<usize> {
+        let start = document.text().find(find).unwrap();
+        let range = document.apply_edit(start..start + find.len(), replace);
+        assert_matches_full_parse(document);
+        range
+    }
+
+    const CONTENTS: &str = "; config
+(def width 10)
+(def height (* width 2)) ; tall
+(def name \"box\")
+
+(print name width height)
+";
+
+    #[test]
+    fn new_matches_full_parse() {
+        let document = Document::new(CONTENTS, Some("doc".into()));
+
+        assert_matches_full_parse(&document);
+        assert_eq!(4, document.nodes().count());
+    }
+
+    #[test]
+    fn apply_edit_only_reparses_touched_forms() {
+        let mut document = Document::new(CONTENTS, Some("doc".into()));
+
+        let reparsed = edit(&mut document, "10", "100");
+
+        assert_eq!("(def width 100)\n", &document.text()[reparsed]);
+    }
+
+    #[test]
+    fn apply_edit_shifts_following_forms() {
+        let mut document = Document::new(CONTENTS, None);
+
+        edit(
+            &mut document,
+            "(def width 10)",
+            "(def width\n  10)\n(def depth 3)",
+        );
+        edit(&mut document, "\"box\"", "\"a \\\" quote\"");
+        edit(&mut document, "height (", "height ( ");
+        edit(&mut document, "; config\n", "");
+
+        assert_eq!(5, document.nodes().count());
+    }
+
+    #[test]
+    fn apply_edit_merges_and_splits_forms() {
+        let mut document = Document::new("(a) (b) (c)", None);
+
+        edit(&mut document, ") (b", " b");
+        assert_eq!(2, document.nodes().count());
+
+        edit(&mut document, "a b", "a) (b");
+        assert_eq!(3, document.nodes().count());
+
+        edit(&mut document, " (c)", "");
+        edit(&mut document, "(a)", "x(a)");
+    }
+
+    #[test]
+    fn apply_edit_handles_strings_and_comments_spanning_forms() {
+        let mut document = Document::new(CONTENTS, None);
+
+        let reparsed = edit(&mut document, "(def width", "\"(def width");
+        assert_eq!(document.text().len(), reparsed.end);
+        edit(&mut document, "\"(def width", "(def width");
+
+        edit(&mut document, "(def height", "; (def height");
+        edit(&mut document, "; (def height", "(def height");
+    }
+
+    #[test]
+    fn apply_edit_reports_and_recovers_from_errors() {
+        let mut document = Document::new(CONTENTS, None);
+
+        edit(&mut document, "(* width 2)", "(* width 2");
+        assert_eq!("Unclosed list", document.error().unwrap().message);
+        assert!(document.lists().is_err());
+
+        edit(&mut document, "(def width", "(def 1width");
+        edit(&mut document, "(def 1width", "(def width");
+        edit(&mut document, "(* width 2", "(* width 2)");
+        assert_eq!(None, document.error());
+
+        edit(&mut document, "(print", ") (print");
+        assert_eq!("List not started", document.error().unwrap().message);
+        edit(&mut document, "(def width", "(def width)");
+        edit(&mut document, ") (print", "(print");
+        edit(&mut document, "(def width)", "(def width");
+        assert_eq!(4, document.lists().unwrap().len());
+    }
+
+    #[test]
+    fn apply_edit_matches_full_parse_for_every_single_character_edit() {
+        let contents = "(a \"s t\" ; c\n (b 1))\n; d\n(e)";
+        let mut document = Document::new(contents, None);
+
+        for start in 0..contents.len() {
+            for replacement in ["", "(", ")", "\"", ";", "\n", " ", "x"] {
+                let end = start + 1;
+                document.apply_edit(start..end, replacement);
+                assert_matches_full_parse(&document);
+                document.apply_edit(start..start + replacement.len(), &contents[start..end]);
+                assert_matches_full_parse(&document);
+            }
+        }
+    }
+}
@@ -14,15 +14,6 @@ where
     Kind: ToString,
 {
     fn to_string(&self) -> String {
-        let loc_error = match self.location.path {
-            Some(ref path) => format!(
-                "{}:{}:{}",
-                path.display(),
-                self.location.line,
-                self.location.column
-            ),
-            None => format!("{}:{}", self.location.line, self.location.column),
-        };
-        format!("{}: {}", loc_error, self.kind.to_string())
+        format!("{}: {}", self.location, self.kind.to_string())
     }
 }
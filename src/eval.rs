@@ -0,0 +1,866 @@
+use crate::{err, parse_str, Ast, Error, List, Location, Node, Symbol};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
+
+/// A function implemented by the host.
+pub type BuiltinFn = dyn Fn(&[Value], &Location) -> Result<Value, Error>;
+
+/// A value produced by evaluation.
+#[derive(Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    List(Vec<Value>),
+    Lambda(Rc<Lambda>),
+    Builtin(Rc<Builtin>),
+}
+
+impl Value {
+    /// Returns whether the value counts as true in a condition. Only `false` and nil are false.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    /// Returns the name of the value's type for error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "bool",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Lambda(_) | Value::Builtin(_) => "function",
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Lambda(a), Value::Lambda(b)) => Rc::ptr_eq(a, b),
+            (Value::Builtin(a), Value::Builtin(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Debug for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Number(n) => write!(f, "{n}"),
+            Value::String(s) => write!(f, "\"{s}\""),
+            Value::List(values) => {
+                let contents = values
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                write!(f, "({contents})")
+            }
+            Value::Lambda(lambda) => match &lambda.name {
+                Some(name) => write!(f, "<lambda {name}>"),
+                None => write!(f, "<lambda>"),
+            },
+            Value::Builtin(builtin) => write!(f, "<builtin {}>", builtin.name),
+        }
+    }
+}
+
+/// A user defined function along with the environment it closes over.
+pub struct Lambda {
+    pub name: Option<String>,
+    pub params: Vec<String>,
+    pub body: Vec<Node>,
+    pub location: Location,
+    env: Env,
+}
+
+/// A function implemented by the host.
+pub struct Builtin {
+    pub name: String,
+    pub function: Box<BuiltinFn>,
+}
+
+/// A scope of variables, chained to the scope it was created in.
+#[derive(Clone, Default)]
+pub struct Env(Rc<RefCell<Scope>>);
+
+#[derive(Default)]
+struct Scope {
+    values: HashMap<String, Value>,
+    parent: Option<Env>,
+    runtime: Rc<Runtime>,
+    /// Whether the scope is in the runtime's captured scopes.
+    tracked: bool,
+}
+
+/// The default for `Interpreter::max_call_depth`, low enough for the 2 MiB stack of a
+/// spawned thread in a debug build.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 128;
+
+/// The fewest captured scopes to allow before collecting cycles.
+const MIN_COLLECT_THRESHOLD: usize = 256;
+
+/// State shared by a global scope and every scope created from it.
+struct Runtime {
+    /// Scopes that lambdas close over, along with their ancestors. A lambda stored in a
+    /// scope it can reach forms a reference cycle, so these are collected once only
+    /// cycles keep them alive.
+    captured: RefCell<Vec<Weak<RefCell<Scope>>>>,
+    /// How many captured scopes to allow before the next collection.
+    threshold: Cell<usize>,
+    depth: Cell<usize>,
+    max_depth: Cell<usize>,
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self {
+            captured: RefCell::new(vec![]),
+            threshold: Cell::new(MIN_COLLECT_THRESHOLD),
+            depth: Cell::new(0),
+            max_depth: Cell::new(DEFAULT_MAX_CALL_DEPTH),
+        }
+    }
+}
+
+impl Runtime {
+    /// Counts a call for as long as the returned guard lives.
+    fn enter(&self, location: &Location) -> Result<CallGuard<'_>, Error> {
+        let max = self.max_depth.get();
+        if self.depth.get() >= max {
+            return err(
+                &format!("Calls exceed the maximum depth of {max}"),
+                location,
+            );
+        }
+        self.depth.set(self.depth.get() + 1);
+        Ok(CallGuard(self))
+    }
+
+    /// Adds the scope and any untracked ancestors to the captured scopes, collecting
+    /// cycles when enough have been added since the last collection.
+    fn track(&self, env: &Env) {
+        let mut current = Some(env.clone());
+        while let Some(env) = current {
+            let mut scope = env.0.borrow_mut();
+            if scope.tracked {
+                break;
+            }
+            scope.tracked = true;
+            self.captured.borrow_mut().push(Rc::downgrade(&env.0));
+            current = scope.parent.clone();
+        }
+
+        if self.captured.borrow().len() >= self.threshold.get() {
+            self.collect();
+        }
+    }
+
+    /// Frees captured scopes that are only kept alive by references from other captured
+    /// scopes and the lambdas in them.
+    ///
+    /// Each scope and lambda's references from inside the captured scopes are subtracted
+    /// from its strong count. Anything left over is referenced from elsewhere, such as the
+    /// interpreter, the host or a call in progress, and keeps everything it reaches alive.
+    fn collect(&self) {
+        let scopes: Vec<_> = self
+            .captured
+            .borrow()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+        let index: HashMap<_, _> = (0..)
+            .zip(&scopes)
+            .map(|(i, scope)| (Rc::as_ptr(scope), i))
+            .collect();
+
+        // Scopes come first, followed by the lambdas found in them.
+        let mut objects: Vec<Object> = scopes
+            .iter()
+            .map(|scope| Object {
+                external: Rc::strong_count(scope) - 1,
+                edges: vec![],
+            })
+            .collect();
+        let mut lambdas: HashMap<*const Lambda, usize> = HashMap::new();
+
+        for (i, scope) in scopes.iter().enumerate() {
+            let scope = match scope.try_borrow() {
+                Ok(scope) => scope,
+                Err(_) => continue,
+            };
+            if let Some(parent) = scope
+                .parent
+                .as_ref()
+                .and_then(|p| index.get(&Rc::as_ptr(&p.0)))
+            {
+                objects[i].edges.push(*parent);
+                objects[*parent].external -= 1;
+            }
+
+            let mut values: Vec<&Value> = scope.values.values().collect();
+            while let Some(value) = values.pop() {
+                let lambda = match value {
+                    Value::List(list) => {
+                        values.extend(list);
+                        continue;
+                    }
+                    Value::Lambda(lambda) => lambda,
+                    _ => continue,
+                };
+                let l = *lambdas.entry(Rc::as_ptr(lambda)).or_insert_with(|| {
+                    let mut object = Object {
+                        external: Rc::strong_count(lambda),
+                        edges: vec![],
+                    };
+                    if let Some(env) = index.get(&Rc::as_ptr(&lambda.env.0)) {
+                        object.edges.push(*env);
+                        objects[*env].external -= 1;
+                    }
+                    objects.push(object);
+                    objects.len() - 1
+                });
+                objects[i].edges.push(l);
+                objects[l].external -= 1;
+            }
+        }
+
+        let mut live = vec![false; objects.len()];
+        let mut stack: Vec<usize> = (0..objects.len())
+            .filter(|i| objects[*i].external > 0)
+            .collect();
+        while let Some(i) = stack.pop() {
+            if !live[i] {
+                live[i] = true;
+                stack.extend(&objects[i].edges);
+            }
+        }
+
+        // Values are dropped once every borrow is released, since dropping them can free
+        // other scopes.
+        let mut garbage = vec![];
+        for (scope, _) in scopes.iter().zip(&live).filter(|(_, live)| !**live) {
+            let mut scope = scope.borrow_mut();
+            garbage.push((std::mem::take(&mut scope.values), scope.parent.take()));
+        }
+        drop(garbage);
+        drop(scopes);
+
+        let mut captured = self.captured.borrow_mut();
+        captured.retain(|scope| scope.strong_count() > 0);
+        self.threshold
+            .set((captured.len() * 2).max(MIN_COLLECT_THRESHOLD));
+    }
+}
+
+/// A scope or lambda while collecting cycles.
+struct Object {
+    /// References to the object from outside the captured scopes.
+    external: usize,
+    /// The objects it refers to.
+    edges: Vec<usize>,
+}
+
+/// Ends a call when dropped.
+struct CallGuard<'a>(&'a Runtime);
+
+impl Drop for CallGuard<'_> {
+    fn drop(&mut self) {
+        self.0.depth.set(self.0.depth.get() - 1);
+    }
+}
+
+impl Env {
+    /// Creates a new scope whose lookups fall back to this one.
+    pub fn child(&self) -> Env {
+        Env(Rc::new(RefCell::new(Scope {
+            values: HashMap::new(),
+            parent: Some(self.clone()),
+            runtime: self.runtime(),
+            tracked: false,
+        })))
+    }
+
+    fn runtime(&self) -> Rc<Runtime> {
+        self.0.borrow().runtime.clone()
+    }
+
+    /// Defines the value in this scope, shadowing any outer definitions.
+    pub fn define(&self, name: &str, value: Value) {
+        self.0.borrow_mut().values.insert(name.to_string(), value);
+    }
+
+    /// Looks up the value, checking outer scopes if not found.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        let scope = self.0.borrow();
+        match scope.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => scope.parent.as_ref().and_then(|p| p.get(name)),
+        }
+    }
+}
+
+/// A tree-walking interpreter over parsed lists.
+///
+/// Supports `define`, `lambda`, `if`, `let` and `begin` as special forms.
+/// Every other list is a call of its head with the evaluated arguments.
+pub struct Interpreter {
+    global: Env,
+}
+
+impl Drop for Interpreter {
+    /// Frees global functions, which refer back to the global scope.
+    fn drop(&mut self) {
+        let runtime = self.global.runtime();
+        drop(std::mem::take(&mut self.global));
+        runtime.collect();
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    /// Creates an interpreter with the standard builtins defined.
+    pub fn new() -> Self {
+        let mut interpreter = Self::empty();
+        define_standard_builtins(&mut interpreter);
+        interpreter
+    }
+
+    /// Creates an interpreter with nothing defined.
+    pub fn empty() -> Self {
+        Self {
+            global: Env::default(),
+        }
+    }
+
+    /// Returns the global environment.
+    pub fn global(&self) -> &Env {
+        &self.global
+    }
+
+    /// Sets how deeply lambdas may call each other before evaluation returns an error.
+    /// Defaults to `DEFAULT_MAX_CALL_DEPTH`.
+    pub fn max_call_depth(&mut self, depth: usize) -> &mut Self {
+        self.global.runtime().max_depth.set(depth);
+        self
+    }
+
+    /// Defines a global value.
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.global.define(name, value);
+    }
+
+    /// Defines a global function implemented by the host.
+    pub fn define_builtin<F>(&mut self, name: &str, function: F)
+    where
+        F: Fn(&[Value], &Location) -> Result<Value, Error> + 'static,
+    {
+        let builtin = Builtin {
+            name: name.to_string(),
+            function: Box::new(function),
+        };
+        self.define(name, Value::Builtin(Rc::new(builtin)));
+    }
+
+    /// Parses and evaluates the contents, returning the value of the last form.
+    pub fn eval_str(&mut self, contents: &str) -> Result<Value, Error> {
+        let lists = parse_str(contents)?;
+        self.eval_lists(&lists)
+    }
+
+    /// Evaluates each list in order, returning the value of the last one.
+    pub fn eval_lists(&mut self, lists: &[List]) -> Result<Value, Error> {
+        let mut value = Value::Nil;
+        for list in lists {
+            value = self.eval_list(list)?;
+        }
+        Ok(value)
+    }
+
+    /// Evaluates a single list.
+    pub fn eval_list(&mut self, list: &List) -> Result<Value, Error> {
+        let env = self.global.clone();
+        eval_form(list.iter().as_slice(), &list.location(), &env)
+    }
+
+    /// Evaluates a single node.
+    pub fn eval_node(&mut self, node: &Node) -> Result<Value, Error> {
+        let env = self.global.clone();
+        eval(node, &env)
+    }
+
+    /// Calls the function with the given arguments.
+    pub fn call(&mut self, function: &Value, args: &[Value]) -> Result<Value, Error> {
+        apply(function, args, &Location::default())
+    }
+}
+
+/// Evaluates the node in the given environment.
+pub fn eval(node: &Node, env: &Env) -> Result<Value, Error> {
+    let location = node.first_location();
+    match &node.ast {
+        Ast::Bool(b) => Ok(Value::Bool(*b)),
+        Ast::Number(n) => Ok(Value::Number(*n)),
+        Ast::String(s) => Ok(Value::String(s.clone())),
+        Ast::Comment(_) => Ok(Value::Nil),
+        Ast::Identifier(id) => match env.get(id) {
+            Some(value) => Ok(value),
+            None if *id == Symbol::NIL => Ok(Value::Nil),
+            None => err(&format!("Unbound symbol `{id}`"), &location),
+        },
+        Ast::List(nodes) => eval_form(nodes, &location, env),
+    }
+}
+
+fn eval_form(nodes: &[Node], location: &Location, env: &Env) -> Result<Value, Error> {
+    let (head, args) = match nodes.split_first() {
+        Some(split) => split,
+        None => return Ok(Value::List(vec![])),
+    };
+
+    if let Ast::Identifier(id) = &head.ast {
+        match *id {
+            Symbol::DEFINE => return eval_define(args, location, env),
+            Symbol::LAMBDA => return eval_lambda(args, location, env),
+            Symbol::IF => return eval_if(args, location, env),
+            Symbol::LET => return eval_let(args, location, env),
+            Symbol::BEGIN => return eval_body(args, env),
+            _ => {}
+        }
+    }
+
+    let function = eval(head, env)?;
+    let args = args
+        .iter()
+        .map(|arg| eval(arg, env))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    apply(&function, &args, location)
+}
+
+fn apply(function: &Value, args: &[Value], location: &Location) -> Result<Value, Error> {
+    match function {
+        Value::Builtin(builtin) => (builtin.function)(args, location),
+        Value::Lambda(lambda) => {
+            if lambda.params.len() != args.len() {
+                let name = lambda.name.as_deref().unwrap_or("lambda");
+                return err(
+                    &format!(
+                        "`{name}` expected {} arguments but got {}",
+                        lambda.params.len(),
+                        args.len()
+                    ),
+                    location,
+                );
+            }
+
+            let runtime = lambda.env.runtime();
+            let _call = runtime.enter(location)?;
+            let env = lambda.env.child();
+            for (param, arg) in lambda.params.iter().zip(args) {
+                env.define(param, arg.clone());
+            }
+            eval_body(&lambda.body, &env)
+        }
+        value => err(
+            &format!("Expected a function but got {}", value.type_name()),
+            location,
+        ),
+    }
+}
+
+/// Evaluates each node in order, returning the value of the last.
+fn eval_body(body: &[Node], env: &Env) -> Result<Value, Error> {
+    let mut value = Value::Nil;
+    for node in body {
+        value = eval(node, env)?;
+    }
+    Ok(value)
+}
+
+/// `(define name value)` or `(define (name params...) body...)`
+fn eval_define(args: &[Node], location: &Location, env: &Env) -> Result<Value, Error> {
+    let target = match args.first() {
+        Some(target) => target,
+        None => return err("Expected a name for define", location),
+    };
+
+    match &target.ast {
+        Ast::Identifier(name) => {
+            if args.len() != 2 {
+                return err(&format!("Expected a single value for `{name}`"), location);
+            }
+            let value = eval(&args[1], env)?;
+            env.define(name, value);
+            Ok(Value::Nil)
+        }
+        Ast::List(signature) => {
+            let name = match signature.first().map(|n| &n.ast) {
+                Some(Ast::Identifier(name)) => *name,
+                _ => return err("Expected a function name", &target.first_location()),
+            };
+            let params = params(&signature[1..])?;
+            let lambda = make_lambda(Some(name.to_string()), params, &args[1..], location, env)?;
+            env.define(&name, lambda);
+            Ok(Value::Nil)
+        }
+        _ => err("Expected a name for define", &target.first_location()),
+    }
+}
+
+/// `(lambda (params...) body...)`
+fn eval_lambda(args: &[Node], location: &Location, env: &Env) -> Result<Value, Error> {
+    let names = match args.first().map(|n| &n.ast) {
+        Some(Ast::List(names)) => params(names)?,
+        _ => return err("Expected a parameter list for lambda", location),
+    };
+    make_lambda(None, names, &args[1..], location, env)
+}
+
+fn make_lambda(
+    name: Option<String>,
+    params: Vec<String>,
+    body: &[Node],
+    location: &Location,
+    env: &Env,
+) -> Result<Value, Error> {
+    if body.is_empty() {
+        return err("Expected a body for lambda", location);
+    }
+    env.runtime().track(env);
+
+    Ok(Value::Lambda(Rc::new(Lambda {
+        name,
+        params,
+        body: body.to_vec(),
+        location: location.clone(),
+        env: env.clone(),
+    })))
+}
+
+fn params(nodes: &[Node]) -> Result<Vec<String>, Error> {
+    nodes
+        .iter()
+        .map(|n| match &n.ast {
+            Ast::Identifier(id) => Ok(id.to_string()),
+            _ => err("Expected a parameter name", &n.first_location()),
+        })
+        .collect()
+}
+
+/// `(if condition then else?)`
+fn eval_if(args: &[Node], location: &Location, env: &Env) -> Result<Value, Error> {
+    if args.len() < 2 || args.len() > 3 {
+        return err("Expected (if condition then else?)", location);
+    }
+
+    if eval(&args[0], env)?.is_truthy() {
+        eval(&args[1], env)
+    } else if let Some(otherwise) = args.get(2) {
+        eval(otherwise, env)
+    } else {
+        Ok(Value::Nil)
+    }
+}
+
+/// `(let ((name value)...) body...)`
+fn eval_let(args: &[Node], location: &Location, env: &Env) -> Result<Value, Error> {
+    let bindings = match args.first().map(|n| &n.ast) {
+        Some(Ast::List(bindings)) => bindings,
+        _ => return err("Expected a binding list for let", location),
+    };
+
+    let scope = env.child();
+    for binding in bindings {
+        match &binding.ast {
+            Ast::List(pair) if pair.len() == 2 => match &pair[0].ast {
+                Ast::Identifier(name) => {
+                    let value = eval(&pair[1], env)?;
+                    scope.define(name, value);
+                }
+                _ => return err("Expected a binding name", &pair[0].first_location()),
+            },
+            _ => return err("Expected (name value)", &binding.first_location()),
+        }
+    }
+
+    eval_body(&args[1..], &scope)
+}
+
+fn define_standard_builtins(interpreter: &mut Interpreter) {
+    fn numbers(name: &str, args: &[Value], location: &Location) -> Result<Vec<f64>, Error> {
+        args.iter()
+            .map(|arg| match arg {
+                Value::Number(n) => Ok(*n),
+                value => err(
+                    &format!("`{name}` expected numbers but got {}", value.type_name()),
+                    location,
+                ),
+            })
+            .collect()
+    }
+
+    interpreter.define_builtin("+", |args, location| {
+        Ok(Value::Number(numbers("+", args, location)?.iter().sum()))
+    });
+    interpreter.define_builtin("*", |args, location| {
+        Ok(Value::Number(
+            numbers("*", args, location)?.iter().product(),
+        ))
+    });
+    interpreter.define_builtin("-", |args, location| {
+        let n = numbers("-", args, location)?;
+        match n.split_first() {
+            Some((first, [])) => Ok(Value::Number(-first)),
+            Some((first, rest)) => Ok(Value::Number(rest.iter().fold(*first, |a, b| a - b))),
+            None => err("`-` expected at least one argument", location),
+        }
+    });
+    interpreter.define_builtin("/", |args, location| {
+        let n = numbers("/", args, location)?;
+        match n.split_first() {
+            Some((first, rest)) if !rest.is_empty() => {
+                Ok(Value::Number(rest.iter().fold(*first, |a, b| a / b)))
+            }
+            _ => err("`/` expected at least two arguments", location),
+        }
+    });
+
+    type Comparison = fn(f64, f64) -> bool;
+    let comparisons: [(&str, Comparison); 5] = [
+        ("<", |a, b| a < b),
+        (">", |a, b| a > b),
+        ("<=", |a, b| a <= b),
+        (">=", |a, b| a >= b),
+        ("=", |a, b| a == b),
+    ];
+    for (name, compare) in comparisons {
+        interpreter.define_builtin(name, move |args, location| {
+            let n = numbers(name, args, location)?;
+            Ok(Value::Bool(n.windows(2).all(|w| compare(w[0], w[1]))))
+        });
+    }
+
+    interpreter.define_builtin("not", |args, location| match args {
+        [value] => Ok(Value::Bool(!value.is_truthy())),
+        _ => err("`not` expected 1 argument", location),
+    });
+    interpreter.define_builtin("list", |args, _| Ok(Value::List(args.to_vec())));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_str(contents: &str) -> Result<Value, Error> {
+        Interpreter::new().eval_str(contents)
+    }
+
+    #[test]
+    fn eval_arithmetic() {
+        assert_eq!(Value::Number(9.0), eval_str("(* 3 (+ 1 2))").unwrap());
+        assert_eq!(Value::Number(-4.0), eval_str("(- 4)").unwrap());
+        assert_eq!(Value::Number(2.5), eval_str("(/ 10 2 2)").unwrap());
+    }
+
+    #[test]
+    fn eval_define_and_lookup() {
+        assert_eq!(
+            Value::Number(3.0),
+            eval_str("(define x 1) (define y 2) (+ x y)").unwrap()
+        );
+    }
+
+    #[test]
+    fn eval_if() {
+        assert_eq!(
+            Value::String("yes".into()),
+            eval_str("(if (< 1 2) \"yes\" \"no\")").unwrap()
+        );
+        assert_eq!(Value::Nil, eval_str("(if false 1)").unwrap());
+    }
+
+    #[test]
+    fn eval_let_shadows_outer_scope() {
+        assert_eq!(
+            Value::List(vec![Value::Number(2.0), Value::Number(1.0)]),
+            eval_str("(define x 1) (list (let ((x 2)) x) x)").unwrap()
+        );
+    }
+
+    #[test]
+    fn eval_begin_returns_last_value() {
+        assert_eq!(Value::Number(2.0), eval_str("(begin 1 2)").unwrap());
+    }
+
+    #[test]
+    fn eval_closures_capture_environment() {
+        let contents = "
+            (define (make-adder n) (lambda (x) (+ x n)))
+            (define add-two (make-adder 2))
+            (add-two 5)";
+        assert_eq!(Value::Number(7.0), eval_str(contents).unwrap());
+    }
+
+    #[test]
+    fn eval_recursion() {
+        let contents = "
+            (define (fact n) (if (<= n 1) 1 (* n (fact (- n 1)))))
+            (fact 5)";
+        assert_eq!(Value::Number(120.0), eval_str(contents).unwrap());
+    }
+
+    #[test]
+    fn eval_recursive_functions_do_not_keep_their_scope_alive() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .eval_str(
+                "(define (fact n) (if (<= n 1) 1 (* n (fact (- n 1)))))
+                (define twice (lambda (n) (if (> n 0) (+ 2 (twice (- n 1))) 0)))",
+            )
+            .unwrap();
+        assert_eq!(
+            Value::Number(126.0),
+            interpreter.eval_str("(+ (fact 5) (twice 3))").unwrap()
+        );
+
+        let global = Rc::downgrade(&interpreter.global().0);
+        drop(interpreter);
+        assert!(global.upgrade().is_none());
+    }
+
+    #[test]
+    fn eval_returns_inner_defined_closures() {
+        let contents = "
+            (define (make) (define (inner) 1) inner)
+            (define (counter n)
+                (define (loop i acc) (if (> i n) acc (loop (+ i 1) (+ acc i))))
+                loop)
+            (list ((make)) ((counter 4) 1 0))";
+        assert_eq!(
+            Value::List(vec![Value::Number(1.0), Value::Number(10.0)]),
+            eval_str(contents).unwrap()
+        );
+    }
+
+    #[test]
+    fn eval_collects_scopes_kept_alive_by_cycles() {
+        let mut interpreter = Interpreter::new();
+        let inner = interpreter
+            .eval_str("(define (make) (define (inner) 1) inner) (make)")
+            .unwrap();
+        let scope = match &inner {
+            Value::Lambda(lambda) => Rc::downgrade(&lambda.env.0),
+            value => panic!("Expected a lambda but got {value}"),
+        };
+
+        interpreter.global().runtime().collect();
+        assert_eq!(Value::Number(1.0), interpreter.call(&inner, &[]).unwrap());
+
+        drop(inner);
+        interpreter.global().runtime().collect();
+        assert!(scope.upgrade().is_none());
+
+        for _ in 0..1000 {
+            interpreter.eval_str("((make))").unwrap();
+        }
+        assert!(interpreter.global().runtime().captured.borrow().len() <= MIN_COLLECT_THRESHOLD);
+    }
+
+    #[test]
+    fn eval_returns_err_on_exceeding_max_call_depth() {
+        let mut interpreter = Interpreter::new();
+        interpreter.max_call_depth(10);
+        interpreter
+            .eval_str("(define (count n) (if (> n 0) (+ 1 (count (- n 1))) 0))")
+            .unwrap();
+
+        assert_eq!(
+            Value::Number(9.0),
+            interpreter.eval_str("(count 9)").unwrap()
+        );
+        let result = interpreter.eval_str("\n(count 10)").unwrap_err();
+        assert_eq!("Calls exceed the maximum depth of 10", result.message);
+        assert_eq!(
+            Value::Number(9.0),
+            interpreter.eval_str("(count 9)").unwrap()
+        );
+    }
+
+    #[test]
+    fn eval_default_max_call_depth_does_not_overflow() {
+        let result = eval_str(
+            "(define (count n) (if (> n 0) (+ 1 (count (- n 1))) 0))
+            (count 100000)",
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            format!("Calls exceed the maximum depth of {DEFAULT_MAX_CALL_DEPTH}"),
+            result.message
+        );
+    }
+
+    #[test]
+    fn eval_host_builtin() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define_builtin("twice", |args, _| match args {
+            [Value::Number(n)] => Ok(Value::Number(n * 2.0)),
+            _ => Err("bad args".into()),
+        });
+
+        assert_eq!(
+            Value::Number(8.0),
+            interpreter.eval_str("(twice 4)").unwrap()
+        );
+    }
+
+    #[test]
+    fn eval_returns_err_with_location_on_unbound_symbol() {
+        let result = eval_str("(define x 1)\n(+ x\n   y)").unwrap_err();
+
+        assert_eq!("Unbound symbol `y`", result.message);
+        assert_eq!(3, result.location.line);
+        assert_eq!(3, result.location.column);
+    }
+
+    #[test]
+    fn eval_returns_err_with_location_on_arity_mismatch() {
+        let result = eval_str("(define (f a b) a)\n  (f 1)").unwrap_err();
+
+        assert_eq!("`f` expected 2 arguments but got 1", result.message);
+        assert_eq!(2, result.location.line);
+        assert_eq!(2, result.location.column);
+    }
+
+    #[test]
+    fn eval_returns_err_on_calling_non_function() {
+        let result = eval_str("(1 2)").unwrap_err();
+
+        assert_eq!("Expected a function but got number", result.message);
+    }
+}
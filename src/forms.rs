@@ -0,0 +1,197 @@
+use crate::{
+    err, list,
+    parser::Parser,
+    parser_error, strip_comments, token_error,
+    tokenizer::{Token, TokenKind, TokenStream},
+    Error, List, ParseOptions,
+};
+use std::{io::BufRead, path::PathBuf};
+
+/// Returns an iterator yielding each top-level list in the reader as soon as it is closed.
+pub fn forms<R: BufRead>(reader: R) -> Forms<R> {
+    Forms::new(reader, None)
+}
+
+/// Returns an iterator yielding each top-level list in the file as soon as it is closed.
+pub fn file_forms<R: BufRead>(reader: R, path: PathBuf) -> Forms<R> {
+    Forms::new(reader, Some(path))
+}
+
+/// Reads top-level lists one at a time. Comments are ignored.
+///
+/// An invalid form produces an error without affecting the forms before it.
+/// Parsing continues after the error unless the input itself could not be tokenized.
+pub struct Forms<R> {
+    tokens: TokenStream<R>,
+    pending: Vec<Token>,
+    depth: usize,
+}
+
+impl<R: BufRead> Forms<R> {
+    /// Creates an iterator over the forms in the reader.
+    pub fn new(reader: R, path: Option<PathBuf>) -> Self {
+        Self::with_options(reader, path, &ParseOptions::default())
+    }
+
+    /// Creates an iterator over the forms in the reader, stopping at the first limit in the
+    /// options that is exceeded.
+    pub fn with_options(reader: R, path: Option<PathBuf>, options: &ParseOptions) -> Self {
+        Self {
+            tokens: TokenStream::with_options(reader, path, options),
+            pending: vec![],
+            depth: 0,
+        }
+    }
+
+    /// Parses the tokens of the form that was just closed.
+    fn finish_form(&mut self) -> Result<List, Error> {
+        let tokens = std::mem::take(&mut self.pending);
+        let mut nodes = Parser::parse(tokens).map_err(parser_error)?;
+        strip_comments(&mut nodes);
+        list::into_list(nodes.remove(0), "list")
+    }
+}
+
+impl<R: BufRead> Iterator for Forms<R> {
+    type Item = Result<List, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let token = match self.tokens.next() {
+                Some(Ok(token)) => token,
+                Some(Err(e)) => {
+                    // The stream stops after an error, so drop the unfinished form
+                    self.pending.clear();
+                    self.depth = 0;
+                    return Some(Err(token_error(e)));
+                }
+                None if self.pending.is_empty() => return None,
+                None => {
+                    // Let the parser report the unclosed list
+                    self.depth = 0;
+                    return Some(self.finish_form());
+                }
+            };
+
+            match token.kind {
+                TokenKind::Comment(_) if self.depth == 0 => {}
+                TokenKind::Symbol('(') => {
+                    self.depth += 1;
+                    self.pending.push(token);
+                }
+                TokenKind::Symbol(')') if self.depth == 0 => {
+                    return Some(err("List not started", &token.location));
+                }
+                TokenKind::Symbol(')') => {
+                    self.depth -= 1;
+                    self.pending.push(token);
+                    if self.depth == 0 {
+                        return Some(self.finish_form());
+                    }
+                }
+                _ if self.depth == 0 => return Some(err("Expected list", &token.location)),
+                _ => self.pending.push(token),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Read};
+
+    /// A reader that fails, standing in for input that hasn't arrived yet.
+    struct Failing;
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("not ready"))
+        }
+    }
+
+    fn collect(contents: &str) -> Vec<Result<String, String>> {
+        forms(contents.as_bytes())
+            .map(|f| f.map(|l| l.to_string()).map_err(|e| e.message))
+            .collect()
+    }
+
+    #[test]
+    fn forms_yields_each_list() {
+        let actual = collect("; header\n(a 1) (b ; inner\n (c))\n; footer");
+
+        assert_eq!(vec![Ok("(a 1)".into()), Ok("(b (c))".into())], actual);
+    }
+
+    #[test]
+    fn forms_matches_parse_file() {
+        let contents = "(def (add a b)\n  (+ a b))\n(add 1 2) (\"str\" true)";
+        let path = PathBuf::from("forms");
+
+        let expected = crate::parse_file(contents, path.clone()).unwrap();
+        let actual = file_forms(contents.as_bytes(), path)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn forms_yields_form_before_rest_of_input_is_read() {
+        let reader = BufReader::with_capacity(1, b"(first form)".chain(Failing));
+        let mut forms = forms(reader);
+
+        assert_eq!("(first form)", forms.next().unwrap().unwrap().to_string());
+        assert_eq!(
+            "Read error: not ready",
+            forms.next().unwrap().unwrap_err().message
+        );
+    }
+
+    #[test]
+    fn forms_ends_after_error_within_form() {
+        let actual = collect("(a)\n(b (1c) d)");
+
+        assert_eq!(
+            vec![
+                Ok("(a)".into()),
+                Err("Identifier begins with number: 1c".into())
+            ],
+            actual
+        );
+
+        let reader = BufReader::with_capacity(1, b"(first (form".chain(Failing));
+        let mut forms = forms(reader);
+        assert_eq!(
+            "Read error: not ready",
+            forms.next().unwrap().unwrap_err().message
+        );
+        assert!(forms.next().is_none());
+    }
+
+    #[test]
+    fn forms_continues_after_invalid_form() {
+        let actual = collect("(a) 5 ) (b)");
+
+        assert_eq!(
+            vec![
+                Ok("(a)".into()),
+                Err("Expected list".into()),
+                Err("List not started".into()),
+                Ok("(b)".into())
+            ],
+            actual
+        );
+    }
+
+    #[test]
+    fn forms_returns_err_on_unclosed_list() {
+        let mut forms = forms("(a)\n(b (c)".as_bytes());
+
+        assert!(forms.next().unwrap().is_ok());
+        let result = forms.next().unwrap().unwrap_err();
+        assert_eq!("Unclosed list", result.message);
+        assert_eq!(2, result.location.line);
+        assert_eq!(0, result.location.column);
+        assert!(forms.next().is_none());
+    }
+}
@@ -0,0 +1,328 @@
+use crate::{
+    err, parse_file_with_options, Error, FileSystem, List, Location, ParseOptions, SourceProvider,
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// The forms resolved by default, each taking a single path string.
+pub const INCLUDE_FORMS: [&str; 2] = ["include", "import"];
+
+/// Options for resolving includes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncludeOptions {
+    /// The identifiers starting an include form.
+    pub forms: Vec<String>,
+    /// The limits used when parsing included files.
+    pub parse: ParseOptions,
+}
+
+impl Default for IncludeOptions {
+    fn default() -> Self {
+        Self {
+            forms: INCLUDE_FORMS.iter().map(|f| f.to_string()).collect(),
+            parse: ParseOptions::default(),
+        }
+    }
+}
+
+/// Replaces top-level `(include "path")` and `(import "path")` lists with the lists in the file.
+/// See [`resolve_includes_with_options`].
+pub fn resolve_includes(lists: Vec<List>) -> Result<Vec<List>, Error> {
+    resolve_includes_with_options(lists, &IncludeOptions::default())
+}
+
+/// Replaces top-level include forms with the lists in the file they name, resolving includes
+/// in those files too. Paths are relative to the directory of the including list's
+/// `Location::path`, or the current directory if it has none.
+///
+/// Each file is included once, so later includes of the same file, or of a file the lists
+/// came from, are removed. Included lists keep the path of the file they came from.
+pub fn resolve_includes_with_options(
+    lists: Vec<List>,
+    options: &IncludeOptions,
+) -> Result<Vec<List>, Error> {
+    resolve_includes_from(lists, options, &FileSystem::new())
+}
+
+/// Resolves includes like [`resolve_includes_with_options`], reading included files from the sources.
+pub fn resolve_includes_from(
+    lists: Vec<List>,
+    options: &IncludeOptions,
+    sources: &dyn SourceProvider,
+) -> Result<Vec<List>, Error> {
+    let mut resolver = Resolver {
+        options,
+        sources,
+        stack: vec![],
+        seen: HashSet::new(),
+    };
+    let roots: Vec<Option<PathBuf>> = lists
+        .iter()
+        .map(|l| {
+            l.location()
+                .path()
+                .and_then(|p| sources.canonicalize(&p).ok())
+        })
+        .collect();
+    resolver.seen.extend(roots.iter().flatten().cloned());
+
+    let mut resolved = vec![];
+    for (list, root) in lists.into_iter().zip(roots) {
+        resolver.stack = match (root, list.location().path()) {
+            (Some(canonical), Some(path)) => vec![(canonical, path.to_path_buf())],
+            _ => vec![],
+        };
+        resolver.resolve(vec![list], &mut resolved)?;
+    }
+    Ok(resolved)
+}
+
+struct Resolver<'a> {
+    options: &'a IncludeOptions,
+    sources: &'a dyn SourceProvider,
+    /// The files being included, as canonical and original paths.
+    stack: Vec<(PathBuf, PathBuf)>,
+    /// The canonical paths of every file already included.
+    seen: HashSet<PathBuf>,
+}
+
+impl Resolver<'_> {
+    fn resolve(&mut self, lists: Vec<List>, resolved: &mut Vec<List>) -> Result<(), Error> {
+        for list in lists {
+            let (path, location) = match self.include_path(&list)? {
+                Some(include) => include,
+                None => {
+                    resolved.push(list);
+                    continue;
+                }
+            };
+
+            let base = list.location().path();
+            let base = base.as_deref().and_then(Path::parent);
+            let file = base.unwrap_or(Path::new("")).join(path);
+            let canonical = match self.sources.canonicalize(&file) {
+                Ok(canonical) => canonical,
+                Err(e) => {
+                    return err(
+                        &format!("Error reading included file: {}", e.message),
+                        &location,
+                    )
+                }
+            };
+
+            if let Some(start) = self.stack.iter().position(|(c, _)| *c == canonical) {
+                let chain: Vec<String> = self.stack[start..]
+                    .iter()
+                    .map(|(_, path)| path.display().to_string())
+                    .chain([file.display().to_string()])
+                    .collect();
+                return err(&format!("Include cycle: {}", chain.join(" -> ")), &location);
+            }
+            if !self.seen.insert(canonical.clone()) {
+                continue;
+            }
+
+            let contents = match self.sources.read_file(&file) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    return err(
+                        &format!("Error reading included file: {}", e.message),
+                        &location,
+                    )
+                }
+            };
+            let lists = parse_file_with_options(&contents, file.clone(), &self.options.parse)?;
+
+            self.stack.push((canonical, file));
+            self.resolve(lists, resolved)?;
+            self.stack.pop();
+        }
+        Ok(())
+    }
+
+    /// Returns the path and its location if the list is an include form.
+    fn include_path(&self, list: &List) -> Result<Option<(String, Location)>, Error> {
+        let form = match list.peek_identifier() {
+            Some(id) if self.options.forms.iter().any(|f| *f == id) => id.to_string(),
+            _ => return Ok(None),
+        };
+
+        let mut list = list.clone();
+        list.pop_front(&form)?;
+        let msg = format!("a path string for `{form}`");
+        let include = list.pop_string(&msg)?;
+        list.assert_empty(&format!("`{form}`"))?;
+        Ok(Some(include))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_file, temp_dir::TempDir};
+
+    fn resolve(dir: &Path, file: &str) -> Result<Vec<List>, Error> {
+        let path = dir.join(file);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        resolve_includes(parse_file(&contents, path).unwrap())
+    }
+
+    #[test]
+    fn resolve_includes_replaces_forms_with_file_lists() {
+        let dir = TempDir::with_files(
+            "include-replace",
+            &[
+                ("main.lisp", "(a)\n(include \"lib/common.lisp\")\n(b)"),
+                ("lib/common.lisp", "(import \"more.lisp\") (common)"),
+                ("lib/more.lisp", "(more)"),
+            ],
+        );
+
+        let lists = resolve(dir.path(), "main.lisp").unwrap();
+
+        let actual: Vec<(String, Option<PathBuf>)> = lists
+            .iter()
+            .map(|l| {
+                (
+                    l.to_string(),
+                    l.location().path().as_deref().map(Path::to_path_buf),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("(a)".into(), Some(dir.join("main.lisp"))),
+                ("(more)".into(), Some(dir.join("lib/more.lisp"))),
+                ("(common)".into(), Some(dir.join("lib/common.lisp"))),
+                ("(b)".into(), Some(dir.join("main.lisp"))),
+            ],
+            actual
+        );
+    }
+
+    #[test]
+    fn resolve_includes_removes_repeated_includes() {
+        let dir = TempDir::with_files(
+            "include-dedupe",
+            &[
+                (
+                    "main.lisp",
+                    "(include \"a.lisp\") (include \"b.lisp\") (include \"./a.lisp\")",
+                ),
+                ("a.lisp", "(a)"),
+                ("b.lisp", "(include \"a.lisp\") (b)"),
+            ],
+        );
+
+        let lists = resolve(dir.path(), "main.lisp").unwrap();
+
+        let actual: Vec<String> = lists.iter().map(|l| l.to_string()).collect();
+        assert_eq!(vec!["(a)", "(b)"], actual);
+    }
+
+    #[test]
+    fn resolve_includes_returns_err_on_cycle() {
+        let dir = TempDir::with_files(
+            "include-cycle",
+            &[
+                ("a.lisp", "(include \"b.lisp\")"),
+                ("b.lisp", "(x)\n(include \"a.lisp\")"),
+            ],
+        );
+
+        let result = resolve(dir.path(), "a.lisp").unwrap_err();
+
+        let a = dir.join("a.lisp");
+        let b = dir.join("b.lisp");
+        assert_eq!(
+            format!(
+                "Include cycle: {} -> {} -> {}",
+                a.display(),
+                b.display(),
+                a.display()
+            ),
+            result.message
+        );
+        assert_eq!(Some(b.as_path()), result.location.path().as_deref());
+        assert_eq!(2, result.location.line);
+    }
+
+    #[test]
+    fn resolve_includes_returns_err_on_invalid_forms() {
+        let dir = TempDir::with_files(
+            "include-invalid",
+            &[
+                ("missing.lisp", "(include \"nope.lisp\")"),
+                ("symbol.lisp", "(include nope)"),
+                ("extra.lisp", "(include \"a.lisp\" \"b.lisp\")"),
+            ],
+        );
+
+        let missing = resolve(dir.path(), "missing.lisp").unwrap_err();
+        let symbol = resolve(dir.path(), "symbol.lisp").unwrap_err();
+        let extra = resolve(dir.path(), "extra.lisp").unwrap_err();
+
+        assert!(missing.message.starts_with("Error reading included file"));
+        assert_eq!(
+            Some(dir.join("missing.lisp").as_path()),
+            missing.location.path().as_deref()
+        );
+        assert_eq!("Expected a path string for `include`", symbol.message);
+        assert_eq!("Expected no more values for `include`", extra.message);
+    }
+
+    #[test]
+    fn resolve_includes_with_options_uses_custom_forms() {
+        let dir = TempDir::with_files(
+            "include-custom",
+            &[
+                ("main.lisp", "(use \"a.lisp\") (include \"b\")"),
+                ("a.lisp", "(a)"),
+            ],
+        );
+        let path = dir.join("main.lisp");
+        let lists = parse_file(&std::fs::read_to_string(&path).unwrap(), path).unwrap();
+        let options = IncludeOptions {
+            forms: vec!["use".into()],
+            ..Default::default()
+        };
+
+        let lists = resolve_includes_with_options(lists, &options).unwrap();
+
+        let actual: Vec<String> = lists.iter().map(|l| l.to_string()).collect();
+        assert_eq!(vec!["(a)", "(include \"b\")"], actual);
+    }
+
+    #[test]
+    fn resolve_includes_from_reads_memory_sources() {
+        let mut sources = crate::MemorySources::new();
+        sources
+            .insert("mods/main.lisp", "(include \"../shared/a.lisp\") (main)")
+            .insert("shared/a.lisp", "(include \"./b.lisp\") (a)")
+            .insert("shared/b.lisp", "(b)")
+            .insert("loop/x.lisp", "(include \"y.lisp\")")
+            .insert("loop/y.lisp", "(include \"../loop/x.lisp\")");
+        let parse = |contents, path: &str| parse_file(contents, path.into()).unwrap();
+        let options = IncludeOptions::default();
+
+        let result = resolve_includes_from(
+            parse("(include \"mods/main.lisp\")", "root.lisp"),
+            &options,
+            &sources,
+        );
+        let cycle = resolve_includes_from(
+            parse("(include \"x.lisp\")", "loop/root.lisp"),
+            &options,
+            &sources,
+        );
+
+        let actual: Vec<String> = result.unwrap().iter().map(|l| l.to_string()).collect();
+        assert_eq!(vec!["(b)", "(a)", "(main)"], actual);
+        assert_eq!(
+            "Include cycle: loop/x.lisp -> loop/y.lisp -> loop/../loop/x.lisp",
+            cycle.unwrap_err().message
+        );
+    }
+}
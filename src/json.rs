@@ -0,0 +1,357 @@
+//! Converts between nodes and JSON values.
+//!
+//! | JSON             | Lisper                                                   |
+//! |------------------|----------------------------------------------------------|
+//! | `null`           | the identifier `null`                                    |
+//! | `true`, `false`  | `true`, `false`                                          |
+//! | numbers          | numbers                                                  |
+//! | strings          | strings                                                  |
+//! | arrays           | lists                                                    |
+//! | objects          | keyword lists `(:key value ...)` or `(map ("key" value) ...)` |
+//!
+//! Converting to JSON accepts both object forms. Any other identifier becomes a string,
+//! and whole numbers become integers. An empty keyword list can't be told apart from an
+//! empty list, so `{}` only round trips as `(map)`.
+//!
+//! Lisper text is read and written with `ParseOptions::backslash_escapes`, so `\\` in a
+//! string stands for a single backslash and strings ending in one round trip.
+use crate::{
+    err,
+    lint::{is_keyword, KEYWORD_PREFIX},
+    parse_nodes, pretty,
+    tokenizer::{Token, TokenKind, ESCAPE_CHARACTER},
+    visit::{walk_mut, VisitMut},
+    Ast, Error, Location, Node, ParseOptions, Symbol,
+};
+use serde_json::{Map, Number, Value};
+
+/// The identifier representing `null`.
+pub const NULL: &str = "null";
+/// The identifier starting a list of key value pairs.
+pub const MAP: &str = "map";
+
+/// The largest integer an `f64` can hold exactly.
+const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// How objects are written as nodes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ObjectStyle {
+    /// `(:key value ...)`. Keys must be valid identifiers.
+    #[default]
+    KeywordList,
+    /// `(map ("key" value) ...)`. Any key is allowed.
+    Map,
+}
+
+/// Parses the contents and converts them to JSON.
+/// A single top-level form becomes a single value, otherwise the values are put in an array.
+pub fn to_json(contents: &str) -> Result<Value, Error> {
+    let options = ParseOptions {
+        backslash_escapes: true,
+        ..Default::default()
+    };
+    let nodes = parse_nodes(contents, None, &options)?;
+    let mut values = nodes.iter().map(to_value).collect::<Result<Vec<_>, _>>()?;
+    match values.len() {
+        1 => Ok(values.remove(0)),
+        _ => Ok(Value::Array(values)),
+    }
+}
+
+/// Converts the JSON to Lisper text, formatted to fit within the width where possible.
+/// The value is always written as a single form, so it round trips through [`to_json`].
+pub fn to_lisper(value: &Value, style: ObjectStyle, width: usize) -> Result<String, Error> {
+    let mut node = to_node(value, style)?;
+    walk_mut(&mut EscapeBackslashes, &mut node);
+
+    Ok(pretty(&node, width) + "\n")
+}
+
+/// Escapes the backslashes in strings, so they read back the same with `backslash_escapes`.
+struct EscapeBackslashes;
+
+impl VisitMut for EscapeBackslashes {
+    fn visit_string(&mut self, value: &mut String) {
+        if value.contains(ESCAPE_CHARACTER) {
+            *value = value.replace(ESCAPE_CHARACTER, "\\\\");
+        }
+    }
+}
+
+/// Converts a node to JSON.
+pub fn to_value(node: &Node) -> Result<Value, Error> {
+    let location = node.first_location();
+    match &node.ast {
+        Ast::Bool(b) => Ok(Value::Bool(*b)),
+        Ast::Identifier(Symbol::NULL) => Ok(Value::Null),
+        Ast::Identifier(id) => Ok(Value::String(id.to_string())),
+        Ast::Number(n) => number(*n, &location),
+        Ast::String(s) => Ok(Value::String(s.clone())),
+        Ast::Comment(_) => err("Expected a value but got a comment", &location),
+        Ast::List(nodes) => {
+            let nodes: Vec<&Node> = nodes.iter().filter(|n| !n.is_comment()).collect();
+            match nodes.first().map(|n| &n.ast) {
+                Some(Ast::Identifier(Symbol::MAP)) => map_object(&nodes[1..]),
+                Some(Ast::Identifier(id)) if is_keyword(id) => keyword_object(&nodes),
+                _ => nodes.into_iter().map(to_value).collect(),
+            }
+        }
+    }
+}
+
+/// Converts JSON to a node. The node has no locations.
+pub fn to_node(value: &Value, style: ObjectStyle) -> Result<Node, Error> {
+    Ok(match value {
+        Value::Null => atom(
+            Ast::Identifier(Symbol::NULL),
+            TokenKind::Identifier(Symbol::NULL),
+        ),
+        Value::Bool(b) => atom(Ast::Bool(*b), TokenKind::Bool(*b)),
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or(f64::NAN);
+            atom(Ast::Number(n), TokenKind::Number(n))
+        }
+        Value::String(s) => atom(Ast::String(s.clone()), TokenKind::String(s.clone())),
+        Value::Array(values) => list(
+            values
+                .iter()
+                .map(|v| to_node(v, style))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(object) => {
+            let mut nodes = vec![];
+            if style == ObjectStyle::Map {
+                nodes.push(atom(
+                    Ast::Identifier(Symbol::MAP),
+                    TokenKind::Identifier(Symbol::MAP),
+                ));
+            }
+            for (key, value) in object {
+                let value = to_node(value, style)?;
+                match style {
+                    ObjectStyle::KeywordList => {
+                        if !is_valid_keyword(key) {
+                            return Err(format!(
+                                "Key `{key}` can't be written as a keyword, try the map object style"
+                            )
+                            .into());
+                        }
+                        let keyword = Symbol::new(&format!("{KEYWORD_PREFIX}{key}"));
+                        nodes.push(atom(
+                            Ast::Identifier(keyword),
+                            TokenKind::Identifier(keyword),
+                        ));
+                        nodes.push(value);
+                    }
+                    ObjectStyle::Map => {
+                        let key = atom(Ast::String(key.clone()), TokenKind::String(key.clone()));
+                        nodes.push(list(vec![key, value]));
+                    }
+                }
+            }
+            list(nodes)
+        }
+    })
+}
+
+/// Returns whether the key can be read back as part of a keyword identifier.
+fn is_valid_keyword(key: &str) -> bool {
+    !key.is_empty()
+        && !key
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '(' | ')' | '"' | ';' | '\\'))
+}
+
+fn number(n: f64, location: &Location) -> Result<Value, Error> {
+    if n.fract() == 0.0 && n.abs() <= MAX_EXACT_INTEGER {
+        return Ok(Value::Number((n as i64).into()));
+    }
+    match Number::from_f64(n) {
+        Some(n) => Ok(Value::Number(n)),
+        None => err(
+            &format!("Number `{n}` can't be represented in JSON"),
+            location,
+        ),
+    }
+}
+
+fn insert(
+    object: &mut Map<String, Value>,
+    key: String,
+    value: Value,
+    location: &Location,
+) -> Result<(), Error> {
+    if object.contains_key(&key) {
+        return err(&format!("Duplicate key `{key}`"), location);
+    }
+    object.insert(key, value);
+    Ok(())
+}
+
+/// Converts `(:key value ...)` to an object.
+fn keyword_object(nodes: &[&Node]) -> Result<Value, Error> {
+    let mut object = Map::new();
+    for pair in nodes.chunks(2) {
+        let location = pair[0].first_location();
+        let key = match &pair[0].ast {
+            Ast::Identifier(id) if is_keyword(id) => &id[KEYWORD_PREFIX.len_utf8()..],
+            _ => return err("Expected a keyword", &location),
+        };
+        let value = match pair.get(1) {
+            Some(value) => to_value(value)?,
+            None => {
+                return err(
+                    &format!("Expected a value for `{KEYWORD_PREFIX}{key}`"),
+                    &location,
+                )
+            }
+        };
+        insert(&mut object, key.to_string(), value, &location)?;
+    }
+    Ok(Value::Object(object))
+}
+
+/// Converts the entries of `(map ("key" value) ...)` to an object.
+fn map_object(entries: &[&Node]) -> Result<Value, Error> {
+    let mut object = Map::new();
+    for entry in entries {
+        let location = entry.first_location();
+        let pair = match &entry.ast {
+            Ast::List(pair) if pair.len() == 2 => pair,
+            _ => return err("Expected a (key value) pair", &location),
+        };
+        let key = match &pair[0].ast {
+            Ast::String(key) => key.clone(),
+            Ast::Identifier(id) if is_keyword(id) => id[KEYWORD_PREFIX.len_utf8()..].to_string(),
+            Ast::Identifier(id) => id.to_string(),
+            _ => return err("Expected a string key", &pair[0].first_location()),
+        };
+        insert(&mut object, key, to_value(&pair[1])?, &location)?;
+    }
+    Ok(Value::Object(object))
+}
+
+/// Creates a node for a single token.
+fn atom(ast: Ast, kind: TokenKind) -> Node {
+    Node {
+        ast,
+        tokens: vec![Token {
+            kind,
+            location: Location::default(),
+        }],
+    }
+}
+
+fn list(nodes: Vec<Node>) -> Node {
+    let paren = |c| Token {
+        kind: TokenKind::Symbol(c),
+        location: Location::default(),
+    };
+    Node {
+        ast: Ast::List(nodes),
+        tokens: vec![paren('('), paren(')')],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn to_json_maps_each_type() {
+        let contents = "(:name \"box\" :size (10 2.5) :visible true :parent null :kind crate)";
+
+        assert_eq!(
+            json!({"name": "box", "size": [10, 2.5], "visible": true, "parent": null, "kind": "crate"}),
+            to_json(contents).unwrap()
+        );
+    }
+
+    #[test]
+    fn to_json_accepts_map_objects() {
+        let contents = "(map (\"first name\" \"a\") (:age 3) (id 7))";
+
+        assert_eq!(
+            json!({"first name": "a", "age": 3, "id": 7}),
+            to_json(contents).unwrap()
+        );
+    }
+
+    #[test]
+    fn to_json_puts_multiple_forms_in_an_array() {
+        assert_eq!(json!([[1], [2]]), to_json("(1) (2)").unwrap());
+    }
+
+    #[test]
+    fn to_json_returns_err_on_invalid_objects() {
+        let duplicate = to_json("(:a 1\n :a 2)").unwrap_err();
+        assert_eq!("Duplicate key `a`", duplicate.message);
+        assert_eq!(2, duplicate.location.line);
+
+        let missing = to_json("(:a 1 :b)").unwrap_err();
+        assert_eq!("Expected a value for `:b`", missing.message);
+
+        let not_a_key = to_json("(:a 1 2 3)").unwrap_err();
+        assert_eq!("Expected a keyword", not_a_key.message);
+    }
+
+    #[test]
+    fn to_lisper_writes_keyword_lists() {
+        let value =
+            json!({"name": "box", "size": [10, 2.5], "parent": null, "quote": "say \"hi\""});
+
+        let actual = to_lisper(&value, ObjectStyle::KeywordList, 80).unwrap();
+
+        assert_eq!(
+            "(:name \"box\" :size (10 2.5) :parent null :quote \"say \\\"hi\\\"\")\n",
+            actual
+        );
+        assert_eq!(value, to_json(&actual).unwrap());
+    }
+
+    #[test]
+    fn to_lisper_writes_maps() {
+        let value = json!([{"first name": "a"}, {}]);
+
+        let actual = to_lisper(&value, ObjectStyle::Map, 80).unwrap();
+
+        assert_eq!("((map (\"first name\" \"a\")) (map))\n", actual);
+        assert_eq!(value, to_json(&actual).unwrap());
+    }
+
+    #[test]
+    fn to_lisper_round_trips_single_element_arrays() {
+        for value in [
+            json!([[1]]),
+            json!([{"a": 1}]),
+            json!([[]]),
+            json!({"k": "a\\"}),
+            json!(["\\\"", "C:\\dir\\", "\\\\"]),
+        ] {
+            let actual = to_lisper(&value, ObjectStyle::KeywordList, 80).unwrap();
+
+            assert_eq!(value, to_json(&actual).unwrap(), "{actual}");
+        }
+    }
+
+    #[test]
+    fn to_lisper_keeps_keys_with_values() {
+        let value = json!({"name": "box", "size": {"width": 10, "height": 20}, "tags": ["a", "b"]});
+
+        assert_eq!(
+            "(:name \"box\"\n :size (:width 10\n        :height 20)\n :tags (\"a\" \"b\"))\n",
+            to_lisper(&value, ObjectStyle::KeywordList, 20).unwrap()
+        );
+    }
+
+    #[test]
+    fn to_lisper_returns_err_on_invalid_keyword() {
+        let result = to_lisper(&json!({"a b": 1}), ObjectStyle::KeywordList, 80).unwrap_err();
+
+        assert_eq!(
+            "Key `a b` can't be written as a keyword, try the map object style",
+            result.message
+        );
+    }
+}
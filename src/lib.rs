@@ -1,15 +1,57 @@
+mod arena;
+pub mod binary;
+#[cfg(feature = "load_directory")]
+mod cache;
+pub mod dispatcher;
+mod document;
 mod error;
+#[cfg(feature = "eval")]
+pub mod eval;
+mod forms;
+mod include;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod lint;
 mod list;
+#[cfg(feature = "load_directory")]
+mod load;
 mod location;
+pub mod macros;
 mod node;
+mod options;
 pub mod parser;
+mod pretty;
+mod source;
+mod source_map;
+mod symbol;
+#[cfg(test)]
+mod temp_dir;
+pub mod template;
 pub mod tokenizer;
+pub mod visit;
+#[cfg(feature = "watch")]
+mod watch;
 
+pub use arena::*;
+#[cfg(feature = "load_directory")]
+pub use cache::*;
+pub use document::*;
+pub use forms::*;
+pub use include::*;
 pub use list::*;
+#[cfg(feature = "load_directory")]
+pub use load::*;
 pub use location::*;
 pub use node::*;
+pub use options::*;
 use parser::{ListErr, ParserErr};
+pub use pretty::*;
+pub use source::*;
+pub use source_map::*;
+pub use symbol::*;
 use tokenizer::{IdentifierErr, TokenErr, TokenType, TypeErr};
+#[cfg(feature = "watch")]
+pub use watch::*;
 
 #[derive(Debug, Clone)]
 pub struct Error {
@@ -28,20 +70,7 @@ impl PartialEq for Error {
 }
 impl From<Error> for String {
     fn from(value: Error) -> Self {
-        if let Some(path) = value.location.path {
-            format!(
-                "{}:{}:{}: {}",
-                path.display(),
-                value.location.line,
-                value.location.column,
-                value.message
-            )
-        } else {
-            format!(
-                "{}:{}: {}",
-                value.location.line, value.location.column, value.message
-            )
-        }
+        format!("{}: {}", value.location, value.message)
     }
 }
 
@@ -62,118 +91,53 @@ impl From<&str> for Error {
     }
 }
 
-#[cfg(feature = "load_directory")]
-pub fn load_directory(extension: &str, location: std::path::PathBuf) -> Result<Vec<List>, Error> {
-    if !location.is_dir() {
-        return Err(format!("Location '{:?}' is not a directory", location).into());
-    }
-
-    let mut files = vec![];
-    // Use walkdir and traverse directory recursively, getting all files with the extension
-    for entry in walkdir::WalkDir::new(location) {
-        let entry = entry.unwrap();
-        if entry.path().is_file() {
-            if let Some(ext) = entry.path().extension() {
-                if ext == extension {
-                    files.push(entry.path().to_path_buf());
-                }
-            }
-        }
-    }
-
-    // Now read each file and parse it
-    let mut lists = vec![];
-    for file in files {
-        let contents = match std::fs::read_to_string(&file) {
-            Ok(contents) => contents,
-            Err(e) => return Err(format!("Error reading file '{:?}': {}", file, e).into()),
-        };
-
-        match parse_file(&contents, file) {
-            Ok(mut l) => lists.append(&mut l),
-            Err(e) => return Err(e),
-        }
-    }
+/// Parsed trees are shared between threads when loading files in parallel.
+const fn assert_send_sync<T: Send + Sync>() {}
+const _: () = {
+    assert_send_sync::<Node>();
+    assert_send_sync::<List>();
+    assert_send_sync::<tokenizer::Token>();
+    assert_send_sync::<Error>();
+};
 
-    Ok(lists)
+/// Parses the given contents into a vec of lists.
+/// Will ignore comments.
+pub fn parse_str(contents: &str) -> Result<Vec<List>, Error> {
+    parse_optional_path(contents, None, &ParseOptions::default())
 }
 
-/// Parses the given contents into a vec of lists.
+/// Parses the given contents into a vec of lists, enforcing the limits in the options.
 /// Will ignore comments.
-pub fn parse_str<'a>(contents: &'a str) -> Result<Vec<List>, Error> {
-    parse_optional_path(contents, None)
+pub fn parse_str_with_options(contents: &str, options: &ParseOptions) -> Result<Vec<List>, Error> {
+    parse_optional_path(contents, None, options)
 }
 
 /// Parse the given contents from a file into a vec of lists.
 /// Will ignore comments.
-pub fn parse_file<'a>(contents: &'a str, path: std::path::PathBuf) -> Result<Vec<List>, Error> {
-    parse_optional_path(contents, Some(path))
+pub fn parse_file(contents: &str, path: std::path::PathBuf) -> Result<Vec<List>, Error> {
+    parse_optional_path(contents, Some(path), &ParseOptions::default())
 }
 
-fn parse_optional_path<'a>(
-    contents: &'a str,
+/// Parse the given contents from a file into a vec of lists, enforcing the limits in the options.
+/// Will ignore comments.
+pub fn parse_file_with_options(
+    contents: &str,
+    path: std::path::PathBuf,
+    options: &ParseOptions,
+) -> Result<Vec<List>, Error> {
+    parse_optional_path(contents, Some(path), options)
+}
+
+fn parse_optional_path(
+    contents: &str,
     path: Option<std::path::PathBuf>,
+    options: &ParseOptions,
 ) -> Result<Vec<List>, Error> {
-    let tokens = match tokenizer::Tokenizer::tokenize(contents, path) {
-        Ok(tokens) => tokens,
-        Err(e) => {
-            let msg: String = match e.kind {
-                TokenErr::Comment(c) => match c {
-                    tokenizer::CommentErr::NotStarted => "Comment not started".into(),
-                },
-                TokenErr::String(s) => match s {
-                    tokenizer::StringErr::NotStarted => "String not started".into(),
-                    tokenizer::StringErr::Unclosed(e) => format!("Unclosed string: {}", e.contents),
-                },
-                TokenErr::Type(t) => match t {
-                    TypeErr::WrongType { got: _, expected } => {
-                        let ty = match expected {
-                            TokenType::Bool => "bool",
-                            TokenType::Comment => "comment",
-                            TokenType::Identifier => "identifier",
-                            TokenType::Number => "number",
-                            TokenType::String => "string",
-                            TokenType::Symbol => "symbol",
-                        };
-                        format!("Expected type {}", ty)
-                    }
-                },
-                TokenErr::Identifier(i) => match i {
-                    IdentifierErr::NotStarted => "Identifier not started".into(),
-                    IdentifierErr::BeginsWithNumber { got } => {
-                        format!("Identifier begins with number: {}", got)
-                    }
-                },
-                TokenErr::StackUnderflow => "Stack underflow".into(),
-            };
-            return err(&msg, &e.location);
-        }
-    };
-    let nodes = match parser::Parser::parse(tokens) {
-        Ok(nodes) => nodes,
-        Err(e) => {
-            let msg: String = match e.kind {
-                ParserErr::Invalid(e) => format!("Invalid: {}", e),
-                ParserErr::List(l) => match l {
-                    ListErr::UnclosedList => "Unclosed list".into(),
-                    ListErr::UnstartedList => "List not started".into(),
-                },
-                ParserErr::StackUnderflow => "Stack underflow".into(),
-            };
-
-            return err(&msg, &e.location);
-        }
-    };
-    let nodes = nodes
-        .iter()
-        .filter(|n| !n.is_comment())
-        .map(|n| strip_comments(n))
-        .filter_map(|n| n)
-        .collect::<Vec<_>>();
+    let nodes = parse_nodes(contents, path, options)?;
 
     let mut lists = vec![];
     for node in nodes {
-        match list::list(&node, "list") {
+        match list::into_list(node, "list") {
             Ok(l) => lists.push(l),
             Err(e) => return Err(e),
         }
@@ -182,19 +146,81 @@ fn parse_optional_path<'a>(
     Ok(lists)
 }
 
-fn strip_comments(node: &Node) -> Option<Node> {
-    let node = match &node.ast {
-        Ast::Comment(_) => return None,
-        Ast::List(nodes) => {
-            let nodes = nodes.iter().filter_map(|n| strip_comments(n)).collect();
+/// Parses the given contents into a vec of nodes with comments removed.
+pub(crate) fn parse_nodes(
+    contents: &str,
+    path: Option<std::path::PathBuf>,
+    options: &ParseOptions,
+) -> Result<Vec<Node>, Error> {
+    options.check_input(contents, &path)?;
+    let tokens = tokenizer::TokenStream::with_options(contents.as_bytes(), path, options)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(token_error)?;
+    let mut nodes = parser::Parser::parse(tokens).map_err(parser_error)?;
+    strip_comments(&mut nodes);
+
+    Ok(nodes)
+}
 
-            Node {
-                ast: Ast::List(nodes),
-                tokens: node.tokens.clone(),
+/// Converts a tokenizer error into an error.
+pub(crate) fn token_error(e: tokenizer::Err) -> Error {
+    let msg: String = match e.kind {
+        TokenErr::Comment(c) => match c {
+            tokenizer::CommentErr::NotStarted => "Comment not started".into(),
+        },
+        TokenErr::String(s) => match s {
+            tokenizer::StringErr::NotStarted => "String not started".into(),
+            tokenizer::StringErr::Unclosed(e) => format!("Unclosed string: {}", e.contents),
+        },
+        TokenErr::Type(t) => match t {
+            TypeErr::WrongType { got: _, expected } => {
+                let ty = match expected {
+                    TokenType::Bool => "bool",
+                    TokenType::Comment => "comment",
+                    TokenType::Identifier => "identifier",
+                    TokenType::Number => "number",
+                    TokenType::String => "string",
+                    TokenType::Symbol => "symbol",
+                };
+                format!("Expected type {}", ty)
             }
-        }
-        Ast::Identifier(_) | Ast::Number(_) | Ast::String(_) | Ast::Bool(_) => node.clone(),
+        },
+        TokenErr::Identifier(i) => match i {
+            IdentifierErr::NotStarted => "Identifier not started".into(),
+            IdentifierErr::BeginsWithNumber { got } => {
+                format!("Identifier begins with number: {}", got)
+            }
+        },
+        TokenErr::Read(e) => format!("Read error: {}", e),
+        TokenErr::Limit(e) => e,
+        TokenErr::StackUnderflow => "Stack underflow".into(),
     };
+    Error::new(msg, e.location)
+}
 
-    Some(node)
+/// Converts a parser error into an error.
+pub(crate) fn parser_error(e: parser::Err) -> Error {
+    let msg: String = match e.kind {
+        ParserErr::Invalid(e) => format!("Invalid: {}", e),
+        ParserErr::List(l) => match l {
+            ListErr::UnclosedList => "Unclosed list".into(),
+            ListErr::UnstartedList => "List not started".into(),
+        },
+        ParserErr::StackUnderflow => "Stack underflow".into(),
+    };
+    Error::new(msg, e.location)
+}
+
+/// Removes comments from the nodes and every list within them.
+pub(crate) fn strip_comments(nodes: &mut Vec<Node>) {
+    // Each list is filtered once, then its child lists are queued
+    let mut stack = vec![nodes];
+    while let Some(nodes) = stack.pop() {
+        nodes.retain(|n| !n.is_comment());
+        for node in nodes.iter_mut() {
+            if let Ast::List(children) = &mut node.ast {
+                stack.push(children);
+            }
+        }
+    }
 }
@@ -0,0 +1,458 @@
+use crate::{
+    parser::Parser,
+    parser_error, token_error,
+    tokenizer::Tokenizer,
+    visit::{walk, Visit},
+    Ast, Error, FileId, Location, Node,
+};
+use std::{collections::HashMap, path::PathBuf};
+
+/// Identifiers starting with this are keys in keyword lists, e.g. `(:width 10 :height 20)`.
+pub const KEYWORD_PREFIX: char = ':';
+
+/// Returns whether the identifier is a keyword list key.
+pub(crate) fn is_keyword(id: &str) -> bool {
+    id.len() > 1 && id.starts_with(KEYWORD_PREFIX)
+}
+
+/// A problem found by a rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub rule: &'static str,
+    pub message: String,
+    pub location: Location,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} [{}]", self.location, self.message, self.rule)
+    }
+}
+
+/// The file being checked.
+pub struct Source<'a> {
+    pub text: &'a str,
+    pub path: Option<&'a PathBuf>,
+    lines: Vec<&'a str>,
+}
+
+impl<'a> Source<'a> {
+    pub fn new(text: &'a str, path: Option<&'a PathBuf>) -> Self {
+        Self {
+            text,
+            path,
+            lines: text.lines().collect(),
+        }
+    }
+
+    /// Returns the text of the line, counting from 1 like `Location`.
+    pub fn line(&self, line: usize) -> &'a str {
+        line.checked_sub(1)
+            .and_then(|i| self.lines.get(i))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns whether only whitespace comes before the location on its line.
+    pub fn starts_line(&self, location: &Location) -> bool {
+        self.line(location.line)
+            .chars()
+            .take(location.column)
+            .all(char::is_whitespace)
+    }
+
+    /// Returns a location at the start of the line.
+    pub fn line_location(&self, line: usize) -> Location {
+        Location {
+            line,
+            column: 0,
+            file: self.path.map(FileId::new),
+            expanded_from: None,
+        }
+    }
+}
+
+/// A check run against parsed files. Implement this to add rules to a [`Linter`].
+pub trait Rule {
+    /// A short name identifying the rule in output, e.g. `empty-list`.
+    fn name(&self) -> &'static str;
+
+    /// Checks a single node, including comments. Called for every node, parents before children.
+    fn check_node(&self, _node: &Node, _source: &Source, _diagnostics: &mut Vec<Diagnostic>) {}
+
+    /// Checks the file as a whole.
+    fn check_source(&self, _source: &Source, _diagnostics: &mut Vec<Diagnostic>) {}
+}
+
+/// Runs rules over files.
+pub struct Linter {
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Linter {
+    /// Creates a linter with the built in rules.
+    pub fn new() -> Self {
+        let mut linter = Self::empty();
+        linter
+            .add(Indentation)
+            .add(DuplicateKey)
+            .add(EmptyList)
+            .add(TrailingComment)
+            .add(MixedIndentation);
+        linter
+    }
+
+    /// Creates a linter with no rules.
+    pub fn empty() -> Self {
+        Self { rules: vec![] }
+    }
+
+    /// Adds a rule.
+    pub fn add<R: Rule + 'static>(&mut self, rule: R) -> &mut Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Removes the rule with the given name. Returns whether it existed.
+    pub fn disable(&mut self, name: &str) -> bool {
+        let len = self.rules.len();
+        self.rules.retain(|r| r.name() != name);
+        self.rules.len() != len
+    }
+
+    /// Returns the names of the rules that will be run.
+    pub fn rules(&self) -> Vec<&'static str> {
+        self.rules.iter().map(|r| r.name()).collect()
+    }
+
+    /// Parses the text and runs every rule, returning diagnostics in the order they appear.
+    pub fn check(&self, text: &str, path: Option<PathBuf>) -> Result<Vec<Diagnostic>, Error> {
+        let tokens = Tokenizer::tokenize(text, path.clone()).map_err(token_error)?;
+        let nodes = Parser::parse(tokens).map_err(parser_error)?;
+        let source = Source::new(text, path.as_ref());
+
+        let mut diagnostics = vec![];
+        for rule in &self.rules {
+            rule.check_source(&source, &mut diagnostics);
+        }
+
+        let mut checker = NodeChecker {
+            rules: &self.rules,
+            source: &source,
+            diagnostics: &mut diagnostics,
+        };
+        for node in &nodes {
+            walk(&mut checker, node);
+        }
+
+        diagnostics.sort_by_key(|d| (d.location.line, d.location.column));
+        Ok(diagnostics)
+    }
+}
+
+/// Runs each rule's `check_node` on every node.
+struct NodeChecker<'a> {
+    rules: &'a [Box<dyn Rule>],
+    source: &'a Source<'a>,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl Visit for NodeChecker<'_> {
+    fn enter(&mut self, node: &Node) -> bool {
+        for rule in self.rules {
+            rule.check_node(node, self.source, self.diagnostics);
+        }
+        true
+    }
+}
+
+/// Nodes starting a line must be indented further than the paren of the list they're in.
+pub struct Indentation;
+impl Rule for Indentation {
+    fn name(&self) -> &'static str {
+        "indentation"
+    }
+
+    fn check_node(&self, node: &Node, source: &Source, diagnostics: &mut Vec<Diagnostic>) {
+        let Ast::List(children) = &node.ast else {
+            return;
+        };
+        let open = node.first_location();
+        for child in children {
+            let location = child.first_location();
+            if location.line > open.line
+                && location.column <= open.column
+                && source.starts_line(&location)
+            {
+                diagnostics.push(Diagnostic {
+                    rule: self.name(),
+                    message: format!(
+                        "Indentation doesn't match the list opened at {}:{}",
+                        open.line, open.column
+                    ),
+                    location,
+                });
+            }
+        }
+    }
+}
+
+/// Keys may only appear once in a keyword list.
+pub struct DuplicateKey;
+impl Rule for DuplicateKey {
+    fn name(&self) -> &'static str {
+        "duplicate-key"
+    }
+
+    fn check_node(&self, node: &Node, _source: &Source, diagnostics: &mut Vec<Diagnostic>) {
+        let Ast::List(children) = &node.ast else {
+            return;
+        };
+
+        let mut keys: HashMap<&str, Location> = HashMap::new();
+        let mut values = children.iter().filter(|n| !n.is_comment());
+        while let Some(child) = values.next() {
+            let key = match &child.ast {
+                Ast::Identifier(id) if is_keyword(id) => id,
+                _ => continue,
+            };
+
+            let location = child.first_location();
+            match keys.get(key.as_str()) {
+                Some(first) => diagnostics.push(Diagnostic {
+                    rule: self.name(),
+                    message: format!(
+                        "Duplicate key `{key}`, first used at {}:{}",
+                        first.line, first.column
+                    ),
+                    location,
+                }),
+                None => {
+                    keys.insert(key, location);
+                }
+            }
+
+            // Skip the value, which may itself be a keyword
+            values.next();
+        }
+    }
+}
+
+/// Lists should contain something.
+pub struct EmptyList;
+impl Rule for EmptyList {
+    fn name(&self) -> &'static str {
+        "empty-list"
+    }
+
+    fn check_node(&self, node: &Node, _source: &Source, diagnostics: &mut Vec<Diagnostic>) {
+        if matches!(&node.ast, Ast::List(children) if children.is_empty()) {
+            diagnostics.push(Diagnostic {
+                rule: self.name(),
+                message: "Empty list".into(),
+                location: node.first_location(),
+            });
+        }
+    }
+}
+
+/// Comments shouldn't sit between the last element of a list and its closing paren.
+pub struct TrailingComment;
+impl Rule for TrailingComment {
+    fn name(&self) -> &'static str {
+        "trailing-comment"
+    }
+
+    fn check_node(&self, node: &Node, _source: &Source, diagnostics: &mut Vec<Diagnostic>) {
+        let Ast::List(children) = &node.ast else {
+            return;
+        };
+        if let Some(comment) = children.last().filter(|n| n.is_comment()) {
+            let open = node.first_location();
+            diagnostics.push(Diagnostic {
+                rule: self.name(),
+                message: format!(
+                    "Comment before the closing paren of the list opened at {}:{}",
+                    open.line, open.column
+                ),
+                location: comment.first_location(),
+            });
+        }
+    }
+}
+
+/// Lines should be indented with either tabs or spaces, consistently across the file.
+pub struct MixedIndentation;
+impl Rule for MixedIndentation {
+    fn name(&self) -> &'static str {
+        "mixed-indentation"
+    }
+
+    fn check_source(&self, source: &Source, diagnostics: &mut Vec<Diagnostic>) {
+        let mut first_style: Option<(char, usize)> = None;
+        for (i, line) in source.text.lines().enumerate() {
+            let indent: String = line
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect();
+            let line_number = i + 1;
+            let message = match (indent.contains(' '), indent.contains('\t')) {
+                (true, true) => "Indentation mixes tabs and spaces".to_string(),
+                (false, false) => continue,
+                (spaces, _) => {
+                    let style = if spaces { ' ' } else { '\t' };
+                    match first_style {
+                        None => {
+                            first_style = Some((style, line_number));
+                            continue;
+                        }
+                        Some((first, _)) if first == style => continue,
+                        Some((_, first_line)) => format!(
+                            "Indentation uses {} but line {first_line} uses {}",
+                            style_name(style),
+                            style_name(if spaces { '\t' } else { ' ' })
+                        ),
+                    }
+                }
+            };
+
+            diagnostics.push(Diagnostic {
+                rule: self.name(),
+                message,
+                location: source.line_location(line_number),
+            });
+        }
+    }
+}
+
+fn style_name(style: char) -> &'static str {
+    match style {
+        '\t' => "tabs",
+        _ => "spaces",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check<R: Rule + 'static>(rule: R, text: &str) -> Vec<(usize, usize, String)> {
+        let mut linter = Linter::empty();
+        linter.add(rule);
+        linter
+            .check(text, None)
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.location.line, d.location.column, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn indentation_flags_children_left_of_their_list() {
+        let text = "(def x\n  (a\n  b)\n c)\n(d\ne)";
+
+        assert_eq!(
+            vec![
+                (
+                    3,
+                    2,
+                    "Indentation doesn't match the list opened at 2:2".into()
+                ),
+                (
+                    6,
+                    0,
+                    "Indentation doesn't match the list opened at 5:0".into()
+                ),
+            ],
+            check(Indentation, text)
+        );
+    }
+
+    #[test]
+    fn duplicate_key_flags_repeated_keys() {
+        let text = "(:name \"a\" :mode :name :age 3 :name \"b\")";
+
+        assert_eq!(
+            vec![(1, 30, "Duplicate key `:name`, first used at 1:1".into())],
+            check(DuplicateKey, text)
+        );
+    }
+
+    #[test]
+    fn empty_list_flags_empty_lists() {
+        assert_eq!(
+            vec![(1, 3, "Empty list".into())],
+            check(EmptyList, "(a () (b))")
+        );
+    }
+
+    #[test]
+    fn trailing_comment_flags_comments_before_closing_paren() {
+        let text = "(a ; fine\n b ; trailing\n)";
+
+        assert_eq!(
+            vec![(
+                2,
+                3,
+                "Comment before the closing paren of the list opened at 1:0".into()
+            )],
+            check(TrailingComment, text)
+        );
+    }
+
+    #[test]
+    fn mixed_indentation_flags_mixed_lines_and_files() {
+        let text = "(a\n  b\n\tc\n \td)";
+
+        assert_eq!(
+            vec![
+                (3, 0, "Indentation uses tabs but line 2 uses spaces".into()),
+                (4, 0, "Indentation mixes tabs and spaces".into()),
+            ],
+            check(MixedIndentation, text)
+        );
+    }
+
+    #[test]
+    fn linter_runs_custom_rules_and_can_disable_rules() {
+        struct NoFoo;
+        impl Rule for NoFoo {
+            fn name(&self) -> &'static str {
+                "no-foo"
+            }
+
+            fn check_node(&self, node: &Node, _: &Source, diagnostics: &mut Vec<Diagnostic>) {
+                if matches!(&node.ast, Ast::Identifier(id) if id == "foo") {
+                    diagnostics.push(Diagnostic {
+                        rule: self.name(),
+                        message: "No foo".into(),
+                        location: node.first_location(),
+                    });
+                }
+            }
+        }
+
+        let mut linter = Linter::new();
+        linter.add(NoFoo);
+        assert!(linter.disable("empty-list"));
+        assert!(!linter.disable("missing"));
+
+        let diagnostics = linter.check("(a (foo) ())", Some("x.lisp".into())).unwrap();
+
+        assert_eq!(1, diagnostics.len());
+        assert_eq!("x.lisp:1:4: No foo [no-foo]", diagnostics[0].to_string());
+    }
+
+    #[test]
+    fn linter_returns_err_on_invalid_input() {
+        let result = Linter::new().check("(a", None).unwrap_err();
+
+        assert_eq!("Unclosed list", result.message);
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{Ast, Error, Location, Node};
+use crate::{
+    tokenizer::{Token, TokenKind},
+    Ast, Error, Location, Node, Symbol,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct List {
@@ -24,6 +27,16 @@ impl List {
         self.nodes.len()
     }
 
+    /// Returns an iterator over the remaining nodes.
+    pub fn iter(&self) -> std::slice::Iter<'_, Node> {
+        self.nodes.iter()
+    }
+
+    /// Returns the node at the index, if there is one.
+    pub fn get(&self, index: usize) -> Option<&Node> {
+        self.nodes.get(index)
+    }
+
     pub fn location(&self) -> Location {
         self.location.clone()
     }
@@ -36,10 +49,10 @@ impl List {
         }
     }
 
-    pub fn peek_identifier(&self) -> Option<&str> {
+    pub fn peek_identifier(&self) -> Option<Symbol> {
         if let Some(n) = self.peek_front() {
             match &n.ast {
-                Ast::Identifier(id) => Some(id),
+                Ast::Identifier(id) => Some(*id),
                 _ => None,
             }
         } else {
@@ -94,7 +107,7 @@ impl List {
     pub fn front_is_float(&self) -> bool {
         if let Some(n) = self.peek_front() {
             match n.ast {
-                Ast::Number(n) => true,
+                Ast::Number(_) => true,
                 _ => false,
             }
         } else {
@@ -143,24 +156,24 @@ impl List {
     }
 
     pub fn pop_comment(&mut self, msg: &str) -> Result<String, Error> {
-        let node = self.pop_front(&msg)?;
-        match &node.ast {
-            Ast::Comment(s) => Ok(s.clone()),
+        let mut node = self.pop_front(&msg)?;
+        match &mut node.ast {
+            Ast::Comment(s) => Ok(std::mem::take(s)),
             _ => err(&format!("Expected {msg}"), &node.first_location()),
         }
     }
 
-    pub fn pop_identifier(&mut self, msg: &str) -> Result<(String, Location), Error> {
+    pub fn pop_identifier(&mut self, msg: &str) -> Result<(Symbol, Location), Error> {
         let node = self.pop_front(&msg)?;
         match &node.ast {
-            Ast::Identifier(s) => Ok((s.clone(), node.first_location())),
+            Ast::Identifier(id) => Ok((*id, node.first_location())),
             _ => err(&format!("Expected {msg}"), &node.first_location()),
         }
     }
 
     pub fn pop_list(&mut self, msg: &str) -> Result<List, Error> {
         let node = self.pop_front(&msg)?;
-        list(&node, msg)
+        into_list(node, msg)
     }
 
     pub fn pop_float(&mut self, msg: &str) -> Result<(f64, Location), Error> {
@@ -189,10 +202,11 @@ impl List {
     }
 
     pub fn pop_string(&mut self, msg: &str) -> Result<(String, Location), Error> {
-        let node = self.pop_front(&msg)?;
-        match &node.ast {
-            Ast::String(s) => Ok((s.clone(), node.first_location())),
-            _ => err(&format!("Expected {msg}"), &node.first_location()),
+        let mut node = self.pop_front(&msg)?;
+        let location = node.first_location();
+        match &mut node.ast {
+            Ast::String(s) => Ok((std::mem::take(s), location)),
+            _ => err(&format!("Expected {msg}"), &location),
         }
     }
 
@@ -228,7 +242,7 @@ impl List {
         }
     }
 
-    pub fn maybe_pop_identifier(&mut self, msg: &str) -> Result<Option<(String, Location)>, Error> {
+    pub fn maybe_pop_identifier(&mut self, msg: &str) -> Result<Option<(Symbol, Location)>, Error> {
         let is_identifier = if let Some(n) = self.peek_front() {
             match n.ast {
                 Ast::Identifier(_) => true,
@@ -309,13 +323,51 @@ impl List {
     }
 
     /// Asserts that the next node is an identifier with the given value.
-    pub fn assert_identifier(&mut self, id: &str, msg: &str) -> Result<(), Error> {
+    pub fn assert_identifier(&mut self, id: impl Into<Symbol>, msg: &str) -> Result<(), Error> {
+        let id = id.into();
         let (identifer_value, loc) = self.pop_identifier(msg)?;
         if identifer_value != id {
             return err(&format!("Expected {id}"), &loc);
         }
         Ok(())
     }
+
+    /// Converts the list back into a node. Only the location of the opening paren is kept.
+    pub fn into_node(self) -> Node {
+        Node {
+            ast: Ast::List(self.nodes),
+            tokens: vec![Token {
+                kind: TokenKind::Symbol('('),
+                location: self.location,
+            }],
+        }
+    }
+}
+
+impl std::ops::Index<usize> for List {
+    type Output = Node;
+
+    fn index(&self, index: usize) -> &Node {
+        &self.nodes[index]
+    }
+}
+
+impl IntoIterator for List {
+    type Item = Node;
+    type IntoIter = std::vec::IntoIter<Node>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.nodes.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a List {
+    type Item = &'a Node;
+    type IntoIter = std::slice::Iter<'a, Node>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.nodes.iter()
+    }
 }
 
 pub fn list(node: &Node, msg: &str) -> Result<List, Error> {
@@ -329,6 +381,18 @@ pub fn list(node: &Node, msg: &str) -> Result<List, Error> {
     })
 }
 
+/// Converts the node into a list without cloning its children.
+pub(crate) fn into_list(mut node: Node, msg: &str) -> Result<List, Error> {
+    let location = node.first_location();
+    match &mut node.ast {
+        Ast::List(nodes) => Ok(List {
+            nodes: std::mem::take(nodes),
+            location,
+        }),
+        _ => err(&format!("Expected {msg}"), &location),
+    }
+}
+
 /// Create an error message with a location.
 pub fn err<T>(contents: &str, l: &Location) -> Result<T, Error> {
     Err(Error {
@@ -371,6 +435,14 @@ mod tests {
         assert_eq!(format!("{}", list), "(+ 1 (* 2 3))");
     }
 
+    #[test]
+    fn display_list_escapes_quotes() {
+        let contents = "(say \"\\\"hi\\\"\")";
+        let list = crate::parse_str(contents).unwrap().first().unwrap().clone();
+
+        assert_eq!(format!("{}", list), contents);
+    }
+
     #[test]
     fn assert_identifier_returns_err_on_mismatch() {
         let contents = "(1 (* 2 3))";
@@ -408,4 +480,18 @@ mod tests {
         assert!(result.is_some());
         assert_eq!(result.unwrap(), "+");
     }
+
+    #[test]
+    fn list_indexes_and_iterates_without_cloning() {
+        let list = crate::parse_str("(a 1 (b))").unwrap().remove(0);
+
+        assert_eq!("1", list[1].to_string());
+        assert_eq!(Some("(b)".to_string()), list.get(2).map(|n| n.to_string()));
+        assert!(list.get(3).is_none());
+
+        let borrowed: Vec<String> = (&list).into_iter().map(|n| n.to_string()).collect();
+        let owned: Vec<String> = list.into_iter().map(|n| n.to_string()).collect();
+        assert_eq!(vec!["a", "1", "(b)"], borrowed);
+        assert_eq!(borrowed, owned);
+    }
 }
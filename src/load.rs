@@ -0,0 +1,872 @@
+use crate::{Error, FileSystem, List, ParseCache, SourceProvider, Symlinks, WalkFilter};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Options for loading a directory.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadOptions {
+    /// Keep loading after a file fails, returning every error rather than just the first.
+    pub collect_errors: bool,
+    /// The cache used to skip parsing unchanged files.
+    pub cache: ParseCache,
+}
+
+/// The lists parsed from a single file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedFile {
+    pub path: PathBuf,
+    pub lists: Vec<List>,
+}
+
+/// The files loaded from a directory, in path order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Loaded {
+    pub files: Vec<LoadedFile>,
+    /// The errors for files that couldn't be read or parsed, when collecting errors.
+    pub errors: Vec<Error>,
+}
+
+impl Loaded {
+    /// Returns each list along with the file it came from.
+    pub fn lists(&self) -> impl Iterator<Item = (&Path, &List)> {
+        self.files
+            .iter()
+            .flat_map(|f| f.lists.iter().map(|l| (f.path.as_path(), l)))
+    }
+
+    /// Returns every list, in path order.
+    pub fn into_lists(self) -> Vec<List> {
+        self.files.into_iter().flat_map(|f| f.lists).collect()
+    }
+}
+
+/// The name of the ignore file read alongside `.gitignore`, using the same syntax.
+pub const IGNORE_FILE: &str = ".lisperignore";
+/// Ignore files in the order they're checked, so patterns in the first take precedence.
+const IGNORE_FILES: [&str; 2] = [IGNORE_FILE, ".gitignore"];
+
+/// Finds and parses files in a directory.
+///
+/// By default every file is loaded except hidden ones and those ignored by
+/// `.gitignore` or [`IGNORE_FILE`] files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loader {
+    extensions: Vec<String>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    ignore_files: bool,
+    hidden: bool,
+    symlinks: Symlinks,
+    max_depth: Option<usize>,
+    parallel: bool,
+    pub(crate) options: LoadOptions,
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        Self {
+            extensions: vec![],
+            include: vec![],
+            exclude: vec![],
+            ignore_files: true,
+            hidden: false,
+            symlinks: Symlinks::Skip,
+            max_depth: None,
+            parallel: false,
+            options: LoadOptions::default(),
+        }
+    }
+
+    /// Adds an extension, without the dot, to load. Files with any extension are loaded if none are added.
+    pub fn extension(&mut self, extension: &str) -> &mut Self {
+        self.extensions.push(extension.to_string());
+        self
+    }
+
+    /// Adds a glob, relative to the directory, that files must match. Files matching any include are loaded.
+    pub fn include(&mut self, glob: &str) -> &mut Self {
+        self.include.push(glob.to_string());
+        self
+    }
+
+    /// Adds a glob, relative to the directory, for files and directories to skip.
+    pub fn exclude(&mut self, glob: &str) -> &mut Self {
+        self.exclude.push(glob.to_string());
+        self
+    }
+
+    /// Sets whether `.gitignore` and [`IGNORE_FILE`] files are respected.
+    pub fn ignore_files(&mut self, ignore_files: bool) -> &mut Self {
+        self.ignore_files = ignore_files;
+        self
+    }
+
+    /// Sets whether hidden files and directories are loaded.
+    pub fn hidden(&mut self, hidden: bool) -> &mut Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Sets whether symlinks are followed when loading from the file system.
+    pub fn symlinks(&mut self, symlinks: Symlinks) -> &mut Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// Sets how deep to walk. A depth of 1 only loads files directly in the directory.
+    pub fn max_depth(&mut self, depth: usize) -> &mut Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Sets whether files are parsed across rayon's thread pool. Without the `parallel` feature
+    /// files are parsed sequentially regardless. Results and errors are in path order either way.
+    pub fn parallel(&mut self, parallel: bool) -> &mut Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Sets whether to keep loading after a file fails. See [`LoadOptions::collect_errors`].
+    pub fn collect_errors(&mut self, collect_errors: bool) -> &mut Self {
+        self.options.collect_errors = collect_errors;
+        self
+    }
+
+    pub fn cache(&mut self, cache: ParseCache) -> &mut Self {
+        self.options.cache = cache;
+        self
+    }
+
+    /// Returns the files that would be loaded from the directory, sorted by path.
+    pub fn files(&self, dir: &Path) -> Result<Vec<PathBuf>, Error> {
+        self.files_from(&self.file_system(), dir)
+    }
+
+    /// Returns the files that would be loaded from the directory in the sources, sorted by path.
+    pub fn files_from(
+        &self,
+        sources: &dyn SourceProvider,
+        dir: &Path,
+    ) -> Result<Vec<PathBuf>, Error> {
+        let mut errors = vec![];
+        let files = self.walk(sources, dir, &mut errors)?;
+        match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(files),
+        }
+    }
+
+    /// Loads the files in the directory.
+    /// When collecting errors, this only fails if the directory can't be listed or a glob is invalid.
+    pub fn load(&self, dir: &Path) -> Result<Loaded, Error> {
+        self.load_from(&self.file_system(), dir)
+    }
+
+    /// Loads the files in the directory from the sources.
+    pub fn load_from(&self, sources: &dyn SourceProvider, dir: &Path) -> Result<Loaded, Error> {
+        let mut loaded = Loaded::default();
+        let files = self.walk(sources, dir, &mut loaded.errors)?;
+        if !self.options.collect_errors {
+            if let Some(e) = loaded.errors.drain(..).next() {
+                return Err(e);
+            }
+        }
+
+        for result in self.parse(sources, files) {
+            match result {
+                Ok(file) => loaded.files.push(file),
+                Err(e) if self.options.collect_errors => loaded.errors.push(e),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Finds the files to load, adding errors reading within the directory to the errors.
+    fn walk(
+        &self,
+        sources: &dyn SourceProvider,
+        dir: &Path,
+        errors: &mut Vec<Error>,
+    ) -> Result<Vec<PathBuf>, Error> {
+        let include = glob_set(&self.include)?;
+        let filter = WalkFilter {
+            hidden: self.hidden,
+            ignore_files: self.ignore_files,
+            max_depth: self.max_depth,
+            exclude: glob_set(&self.exclude)?,
+        };
+
+        let mut files = sources.walk(dir, &filter, errors)?;
+        files.retain(|path| {
+            let relative = path.strip_prefix(dir).unwrap_or(path);
+            let has_extension = self.extensions.is_empty()
+                || path
+                    .extension()
+                    .is_some_and(|ext| self.extensions.iter().any(|e| ext == e.as_str()));
+            has_extension && (self.include.is_empty() || include.is_match(relative))
+        });
+        Ok(files)
+    }
+
+    pub(crate) fn file_system(&self) -> FileSystem {
+        let mut file_system = FileSystem::new();
+        file_system.symlinks(self.symlinks);
+        file_system
+    }
+
+    /// Parses each file, keeping the results in the same order as the files.
+    fn parse(
+        &self,
+        sources: &dyn SourceProvider,
+        files: Vec<PathBuf>,
+    ) -> Vec<Result<LoadedFile, Error>> {
+        let cache = &self.options.cache;
+        let collect_errors = self.options.collect_errors;
+        if self.parallel && files.len() > 1 {
+            let failed = FirstFailure::new(collect_errors);
+            let results = parse_parallel(sources, files, cache, &failed);
+            return until_failure(results, collect_errors);
+        }
+
+        let results = files
+            .into_iter()
+            .map(|file| Some(load_file(sources, file, cache)));
+        until_failure(results, collect_errors)
+    }
+}
+
+/// The index of the first file that failed, so parallel parsing can skip the files after it
+/// when their results would be thrown away.
+struct FirstFailure {
+    index: AtomicUsize,
+    collect_errors: bool,
+}
+
+impl FirstFailure {
+    fn new(collect_errors: bool) -> Self {
+        Self {
+            index: AtomicUsize::new(usize::MAX),
+            collect_errors,
+        }
+    }
+
+    /// Returns whether the file at the index comes after a failure, so needn't be parsed.
+    fn is_after(&self, i: usize) -> bool {
+        i > self.index.load(Ordering::Relaxed)
+    }
+
+    /// Parses the file at the index, recording it if it fails.
+    fn load(
+        &self,
+        i: usize,
+        sources: &dyn SourceProvider,
+        file: PathBuf,
+        cache: &ParseCache,
+    ) -> Option<Result<LoadedFile, Error>> {
+        if self.is_after(i) {
+            return None;
+        }
+        let result = load_file(sources, file, cache);
+        if result.is_err() && !self.collect_errors {
+            self.index.fetch_min(i, Ordering::Relaxed);
+        }
+        Some(result)
+    }
+}
+
+/// Keeps the results in order up to the first error, unless collecting errors. Files are only
+/// skipped after a failure, so every file before the first error has a result.
+fn until_failure(
+    results: impl IntoIterator<Item = Option<Result<LoadedFile, Error>>>,
+    collect_errors: bool,
+) -> Vec<Result<LoadedFile, Error>> {
+    let mut kept = vec![];
+    for result in results.into_iter().flatten() {
+        let failed = result.is_err();
+        kept.push(result);
+        if failed && !collect_errors {
+            break;
+        }
+    }
+    kept
+}
+
+/// Parses the files on rayon's thread pool, keeping the results in the same order as the files.
+#[cfg(feature = "parallel")]
+fn parse_parallel(
+    sources: &dyn SourceProvider,
+    files: Vec<PathBuf>,
+    cache: &ParseCache,
+    failed: &FirstFailure,
+) -> Vec<Option<Result<LoadedFile, Error>>> {
+    use rayon::prelude::*;
+    files
+        .into_par_iter()
+        .enumerate()
+        .map(|(i, file)| failed.load(i, sources, file, cache))
+        .collect()
+}
+
+/// Without the `parallel` feature, parses the files in order on the calling thread.
+#[cfg(not(feature = "parallel"))]
+fn parse_parallel(
+    sources: &dyn SourceProvider,
+    files: Vec<PathBuf>,
+    cache: &ParseCache,
+    failed: &FirstFailure,
+) -> Vec<Option<Result<LoadedFile, Error>>> {
+    files
+        .into_iter()
+        .enumerate()
+        .map(|(i, file)| failed.load(i, sources, file, cache))
+        .collect()
+}
+
+/// Loads all files with the extension in the directory and its subdirectories, sorted by path.
+/// Hidden and ignored files are included. Returns the first error found.
+/// With the `parallel` feature, files are parsed across a thread pool.
+pub fn load_directory(extension: &str, location: PathBuf) -> Result<Vec<List>, Error> {
+    load_directory_with_options(extension, location, &LoadOptions::default())
+        .map(Loaded::into_lists)
+}
+
+/// Loads the directory like `load_directory`, reusing cached results for unchanged files.
+pub fn load_directory_with_cache(
+    extension: &str,
+    location: PathBuf,
+    cache: &ParseCache,
+) -> Result<Vec<List>, Error> {
+    let options = LoadOptions {
+        cache: cache.clone(),
+        ..Default::default()
+    };
+    load_directory_with_options(extension, location, &options).map(Loaded::into_lists)
+}
+
+/// Loads the directory like `load_directory`, keeping track of which file each list came from.
+/// When collecting errors, this only fails if the location isn't a directory.
+pub fn load_directory_with_options(
+    extension: &str,
+    location: PathBuf,
+    options: &LoadOptions,
+) -> Result<Loaded, Error> {
+    let mut loader = Loader::new();
+    loader
+        .extension(extension)
+        .ignore_files(false)
+        .hidden(true)
+        .symlinks(Symlinks::Follow)
+        .parallel(cfg!(feature = "parallel"))
+        .collect_errors(options.collect_errors)
+        .cache(options.cache.clone());
+    loader.load(&location)
+}
+
+/// Reads and parses a single file.
+pub(crate) fn load_file(
+    sources: &dyn SourceProvider,
+    path: PathBuf,
+    cache: &ParseCache,
+) -> Result<LoadedFile, Error> {
+    let contents = sources.read_file(&path)?;
+    let lists = cache.parse_file(&contents, path.clone())?;
+    Ok(LoadedFile { path, lists })
+}
+
+/// Filters every file listed in the directory, for sources that can't skip directories while
+/// walking. Ignore files that can't be read are added to the errors.
+pub(crate) fn filter_files<S: SourceProvider + ?Sized>(
+    sources: &S,
+    dir: &Path,
+    all: Vec<PathBuf>,
+    filter: &WalkFilter,
+    errors: &mut Vec<Error>,
+) -> Result<Vec<PathBuf>, Error> {
+    let ignored = match filter.ignore_files {
+        true => IgnoreFiles::read(sources, &all, errors),
+        false => IgnoreFiles::default(),
+    };
+
+    let mut files = vec![];
+    for path in all {
+        let relative = path.strip_prefix(dir).unwrap_or(&path);
+        let hidden = relative.components().any(|c| match c {
+            Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+            _ => false,
+        });
+        let excluded = relative
+            .ancestors()
+            .any(|p| !p.as_os_str().is_empty() && filter.exclude.is_match(p));
+
+        if (filter.hidden || !hidden)
+            && !excluded
+            && filter
+                .max_depth
+                .is_none_or(|max| relative.components().count() <= max)
+            && !ignored.is_ignored(dir, &path)
+        {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// The patterns in the ignore files found while listing a directory, by the directory they're in.
+#[derive(Default)]
+struct IgnoreFiles {
+    dirs: HashMap<PathBuf, Vec<Gitignore>>,
+}
+
+impl IgnoreFiles {
+    fn read<S: SourceProvider + ?Sized>(
+        sources: &S,
+        files: &[PathBuf],
+        errors: &mut Vec<Error>,
+    ) -> Self {
+        let mut ignore_files = Self::default();
+        for name in IGNORE_FILES {
+            for path in files
+                .iter()
+                .filter(|p| p.file_name().is_some_and(|n| n == name))
+            {
+                match Self::parse(sources, path) {
+                    Ok((dir, gitignore)) => {
+                        ignore_files.dirs.entry(dir).or_default().push(gitignore)
+                    }
+                    Err(e) => errors.push(e),
+                }
+            }
+        }
+        ignore_files
+    }
+
+    /// Reads an ignore file, returning the directory it applies to and its patterns.
+    fn parse<S: SourceProvider + ?Sized>(
+        sources: &S,
+        path: &Path,
+    ) -> Result<(PathBuf, Gitignore), Error> {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut builder = GitignoreBuilder::new(dir);
+        for line in sources.read_file(path)?.lines() {
+            if let Err(e) = builder.add_line(Some(path.to_path_buf()), line) {
+                return Err(format!("Invalid pattern in '{:?}': {e}", path).into());
+            }
+        }
+        let gitignore = builder
+            .build()
+            .map_err(|e| Error::from(format!("Invalid pattern in '{:?}': {e}", path)))?;
+        Ok((dir.to_path_buf(), gitignore))
+    }
+
+    /// Returns whether the file or a directory above it is ignored. Like a walk that skips
+    /// ignored directories, files in an ignored directory can't be whitelisted again.
+    fn is_ignored(&self, root: &Path, path: &Path) -> bool {
+        let mut dirs: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| *dir != root)
+            .collect();
+        dirs.reverse();
+        dirs.into_iter().any(|dir| self.matches(root, dir, true)) || self.matches(root, path, false)
+    }
+
+    /// Returns whether the path is ignored, checking the closest ignore files first.
+    fn matches(&self, root: &Path, path: &Path, is_dir: bool) -> bool {
+        for dir in path.ancestors().skip(1) {
+            for gitignore in self.dirs.get(dir).into_iter().flatten() {
+                let matched = gitignore.matched(path, is_dir);
+                if matched.is_ignore() {
+                    return true;
+                }
+                if matched.is_whitelist() {
+                    return false;
+                }
+            }
+            if dir == root {
+                break;
+            }
+        }
+        false
+    }
+}
+
+fn glob_set(globs: &[String]) -> Result<globset::GlobSet, Error> {
+    let mut set = globset::GlobSetBuilder::new();
+    for glob in globs {
+        match globset::Glob::new(glob) {
+            Ok(glob) => set.add(glob),
+            Err(e) => return Err(format!("Invalid glob `{glob}`: {e}").into()),
+        };
+    }
+    set.build()
+        .map_err(|e| format!("Invalid globs: {e}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn load_directory_sorts_by_path() {
+        let dir = TempDir::with_files(
+            "load-sorted",
+            &[
+                ("c.lisp", "(c)"),
+                ("a/z.lisp", "(z)"),
+                ("b.lisp", "(b)"),
+                ("a/y.lisp", "(y)"),
+                ("notes.txt", "(ignored)"),
+            ],
+        );
+
+        let lists = load_directory("lisp", dir.to_path_buf()).unwrap();
+
+        let names: Vec<String> = lists.iter().map(|l| l.to_string()).collect();
+        assert_eq!(vec!["(y)", "(z)", "(b)", "(c)"], names);
+    }
+
+    #[test]
+    fn load_directory_returns_first_err() {
+        let dir = TempDir::with_files("load-first", &[("a.lisp", "(a"), ("b.lisp", "(b")]);
+
+        let result = load_directory("lisp", dir.to_path_buf()).unwrap_err();
+
+        assert_eq!("Unclosed list", result.message);
+        assert_eq!(
+            Some(dir.join("a.lisp").as_path()),
+            result.location.path().as_deref()
+        );
+    }
+
+    #[test]
+    fn load_directory_returns_err_on_missing_directory() {
+        let result = load_directory("lisp", "/does/not/exist".into());
+
+        assert!(result.unwrap_err().message.ends_with("is not a directory"));
+    }
+
+    #[test]
+    fn load_directory_with_options_collects_errors() {
+        let dir = TempDir::with_files(
+            "load-collect",
+            &[("a.lisp", "(a"), ("b.lisp", "(b) (c)"), ("c.lisp", "\"c")],
+        );
+        let options = LoadOptions {
+            collect_errors: true,
+            ..Default::default()
+        };
+
+        let loaded = load_directory_with_options("lisp", dir.to_path_buf(), &options).unwrap();
+
+        let errors: Vec<Option<PathBuf>> = loaded
+            .errors
+            .iter()
+            .map(|e| e.location.path().as_deref().map(Path::to_path_buf))
+            .collect();
+        assert_eq!(
+            vec![Some(dir.join("a.lisp")), Some(dir.join("c.lisp"))],
+            errors
+        );
+        let lists: Vec<(&Path, String)> = loaded
+            .lists()
+            .map(|(path, list)| (path, list.to_string()))
+            .collect();
+        let b = dir.join("b.lisp");
+        assert_eq!(
+            vec![(b.as_path(), "(b)".into()), (b.as_path(), "(c)".into())],
+            lists
+        );
+    }
+
+    #[test]
+    fn loader_skips_files_in_ignored_directories() {
+        let dir = TempDir::with_files(
+            "load-pruned",
+            &[
+                (".gitignore", "drafts/\n"),
+                ("a.lisp", "(a)"),
+                ("drafts/.gitignore", "!b.lisp\n"),
+                ("drafts/b.lisp", "(b)"),
+            ],
+        );
+        let mut sources = crate::MemorySources::new();
+        sources
+            .insert("content/.gitignore", "drafts/\n")
+            .insert("content/a.lisp", "(a)")
+            .insert("content/drafts/.gitignore", "!b.lisp\n")
+            .insert("content/drafts/b.lisp", "(b)");
+
+        let loaded = Loader::new().extension("lisp").load(&dir).unwrap();
+        let from_memory = Loader::new()
+            .extension("lisp")
+            .load_from(&sources, Path::new("content"))
+            .unwrap();
+
+        assert_eq!(vec!["(a)"], lists(loaded));
+        assert_eq!(vec!["(a)"], lists(from_memory));
+    }
+
+    #[test]
+    fn loader_collects_errors_reading_ignore_files() {
+        let mut sources = crate::MemorySources::new();
+        sources
+            .insert("content/a.lisp", "(a)")
+            .insert("content/mods/.lisperignore", "a{b\n")
+            .insert("content/mods/b.lisp", "(b)");
+        let mut loader = Loader::new();
+        loader.extension("lisp");
+
+        let first = loader
+            .load_from(&sources, Path::new("content"))
+            .unwrap_err();
+        let loaded = loader
+            .collect_errors(true)
+            .load_from(&sources, Path::new("content"))
+            .unwrap();
+
+        assert!(first.message.starts_with("Invalid pattern"));
+        assert_eq!(1, loaded.errors.len());
+        assert_eq!(first, loaded.errors[0]);
+        assert_eq!(vec!["(a)", "(b)"], lists(loaded));
+    }
+
+    fn lists(loaded: Loaded) -> Vec<String> {
+        loaded.lists().map(|(_, l)| l.to_string()).collect()
+    }
+
+    fn relative(dir: &Path, files: Vec<PathBuf>) -> Vec<String> {
+        files
+            .iter()
+            .map(|f| {
+                f.strip_prefix(dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loader_filters_by_extension_and_globs() {
+        let dir = TempDir::with_files(
+            "load-globs",
+            &[
+                ("a.lisp", ""),
+                ("b.lsp", ""),
+                ("c.txt", ""),
+                ("mods/d.lisp", ""),
+                ("mods/test/e.lisp", ""),
+                ("target/f.lisp", ""),
+            ],
+        );
+
+        let files = Loader::new()
+            .extension("lisp")
+            .extension("lsp")
+            .exclude("target")
+            .exclude("**/test/**")
+            .files(&dir)
+            .unwrap();
+        assert_eq!(
+            vec!["a.lisp", "b.lsp", "mods/d.lisp"],
+            relative(&dir, files)
+        );
+
+        let files = Loader::new().include("mods/**").files(&dir).unwrap();
+        assert_eq!(
+            vec!["mods/d.lisp", "mods/test/e.lisp"],
+            relative(&dir, files)
+        );
+    }
+
+    #[test]
+    fn loader_respects_ignore_files_and_hidden() {
+        let dir = TempDir::with_files(
+            "load-ignore",
+            &[
+                (".gitignore", "target/\n"),
+                (".lisperignore", "*.gen.lisp\n"),
+                (".hidden/a.lisp", ""),
+                ("b.lisp", ""),
+                ("b.gen.lisp", ""),
+                ("target/c.lisp", ""),
+            ],
+        );
+
+        let files = Loader::new().extension("lisp").files(&dir).unwrap();
+        assert_eq!(vec!["b.lisp"], relative(&dir, files));
+
+        let files = Loader::new()
+            .extension("lisp")
+            .ignore_files(false)
+            .hidden(true)
+            .files(&dir)
+            .unwrap();
+        assert_eq!(
+            vec![".hidden/a.lisp", "b.gen.lisp", "b.lisp", "target/c.lisp"],
+            relative(&dir, files)
+        );
+    }
+
+    #[test]
+    fn loader_limits_depth() {
+        let dir = TempDir::with_files("load-depth", &[("a.lisp", ""), ("b/c.lisp", "")]);
+
+        let files = Loader::new().max_depth(1).files(&dir).unwrap();
+
+        assert_eq!(vec!["a.lisp"], relative(&dir, files));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn loader_follows_symlinks_when_asked() {
+        let dir = TempDir::with_files("load-symlinks", &[("real/a.lisp", "(a)")]);
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+
+        let skipped = Loader::new().files(&dir).unwrap();
+        let followed = Loader::new()
+            .symlinks(Symlinks::Follow)
+            .files(&dir)
+            .unwrap();
+
+        assert_eq!(vec!["real/a.lisp"], relative(&dir, skipped));
+        assert_eq!(vec!["link/a.lisp", "real/a.lisp"], relative(&dir, followed));
+    }
+
+    #[test]
+    fn loader_parses_in_parallel_in_path_order() {
+        let files: Vec<(String, String)> = (0..50)
+            .map(|i| (format!("{i:02}.lisp"), format!("(file {i})")))
+            .collect();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(f, c)| (f.as_str(), c.as_str()))
+            .collect();
+        let dir = TempDir::with_files("load-parallel", &files);
+
+        let sequential = Loader::new().load(&dir).unwrap();
+        let parallel = Loader::new().parallel(true).load(&dir).unwrap();
+
+        assert_eq!(sequential, parallel);
+        assert_eq!("(file 49)", parallel.into_lists()[49].to_string());
+    }
+
+    #[test]
+    fn loader_returns_err_on_invalid_glob() {
+        let dir = TempDir::new("load-invalid");
+
+        let result = Loader::new().include("a[").load(&dir).unwrap_err();
+
+        assert!(result.message.starts_with("Invalid glob `a[`"));
+    }
+
+    #[test]
+    fn loader_aggregates_parallel_errors_in_path_order() {
+        let files: Vec<(String, &str)> = (0..20)
+            .map(|i| {
+                (
+                    format!("{i:02}.lisp"),
+                    if i % 3 == 0 { "(bad" } else { "(good)" },
+                )
+            })
+            .collect();
+        let files: Vec<(&str, &str)> = files.iter().map(|(f, c)| (f.as_str(), *c)).collect();
+        let dir = TempDir::with_files("load-parallel-errors", &files);
+
+        let loaded = Loader::new()
+            .parallel(true)
+            .collect_errors(true)
+            .load(&dir)
+            .unwrap();
+        let first = Loader::new().parallel(true).load(&dir).unwrap_err();
+
+        let errors: Vec<PathBuf> = loaded
+            .errors
+            .iter()
+            .map(|e| e.location.path().unwrap().to_path_buf())
+            .collect();
+        let expected: Vec<PathBuf> = (0..20)
+            .step_by(3)
+            .map(|i| dir.join(format!("{i:02}.lisp")))
+            .collect();
+        assert_eq!(expected, errors);
+        assert_eq!(13, loaded.files.len());
+        assert_eq!(
+            Some(dir.join("00.lisp").as_path()),
+            first.location.path().as_deref()
+        );
+    }
+
+    #[test]
+    fn loader_stops_parallel_parsing_at_first_error() {
+        struct CountReads(crate::MemorySources, AtomicUsize);
+        impl SourceProvider for CountReads {
+            fn list_files(&self, dir: &Path) -> Result<Vec<PathBuf>, Error> {
+                self.0.list_files(dir)
+            }
+
+            fn read_file(&self, path: &Path) -> Result<String, Error> {
+                self.1.fetch_add(1, Ordering::Relaxed);
+                self.0.read_file(path)
+            }
+        }
+        let mut sources = crate::MemorySources::new();
+        sources.insert("content/0000.lisp", "(bad");
+        for i in 1..1000 {
+            sources.insert(format!("content/{i:04}.lisp"), "(good)");
+        }
+        let sources = CountReads(sources, AtomicUsize::new(0));
+
+        let first = Loader::new()
+            .parallel(true)
+            .load_from(&sources, Path::new("content"))
+            .unwrap_err();
+
+        assert_eq!(
+            Some(Path::new("content/0000.lisp")),
+            first.location.path().as_deref()
+        );
+        assert!(sources.1.load(Ordering::Relaxed) < 1000);
+    }
+
+    #[test]
+    fn loader_loads_from_memory_sources() {
+        let mut sources = crate::MemorySources::new();
+        sources
+            .insert("content/.lisperignore", "draft.lisp\n")
+            .insert("content/a.lisp", "(a)")
+            .insert("content/draft.lisp", "(draft)")
+            .insert("content/mods/b.lisp", "(b) (c)")
+            .insert("other/d.lisp", "(d)");
+
+        let loaded = Loader::new()
+            .extension("lisp")
+            .load_from(&sources, Path::new("content"))
+            .unwrap();
+
+        let lists: Vec<(&Path, String)> = loaded
+            .lists()
+            .map(|(path, list)| (path, list.to_string()))
+            .collect();
+        assert_eq!(
+            vec![
+                (Path::new("content/a.lisp"), "(a)".into()),
+                (Path::new("content/mods/b.lisp"), "(b)".into()),
+                (Path::new("content/mods/b.lisp"), "(c)".into()),
+            ],
+            lists
+        );
+    }
+}
@@ -1,19 +1,18 @@
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock, RwLock},
+};
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Location {
     pub line: usize,
     pub column: usize,
-    pub path: Option<PathBuf>,
-}
-impl Default for Location {
-    fn default() -> Self {
-        Self {
-            line: 0,
-            column: 0,
-            path: None,
-        }
-    }
+    /// The file the location is in, if parsed from a file.
+    /// See [`FileId`] for how long its path is kept.
+    pub file: Option<FileId>,
+    /// The location this one was generated from, such as the use site of a template.
+    pub expanded_from: Option<Box<Location>>,
 }
 
 impl Location {
@@ -21,12 +20,135 @@ impl Location {
         Self {
             line: 0,
             column: 0,
-            path,
+            file: path.map(FileId::new),
+            expanded_from: None,
         }
     }
 
+    /// Returns the path of the file the location is in.
+    pub fn path(&self) -> Option<Arc<Path>> {
+        self.file.map(FileId::path)
+    }
+
     pub fn increment_line(mut self) -> Self {
         self.line += 1;
         self
     }
+
+    /// Appends the given location to the end of the expansion chain.
+    pub fn with_expansion(mut self, from: Location) -> Self {
+        let mut current = &mut self;
+        while current.expanded_from.is_some() {
+            current = current.expanded_from.as_mut().unwrap();
+        }
+        current.expanded_from = Some(Box::new(from));
+        self
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path() {
+            Some(path) => write!(f, "{}:{}:{}", path.display(), self.line, self.column)?,
+            None => write!(f, "{}:{}", self.line, self.column)?,
+        }
+
+        if let Some(from) = &self.expanded_from {
+            write!(f, " (expanded from {})", from)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A small handle to a file path, so locations don't each hold a copy of it.
+/// Ids are equal when their paths are.
+///
+/// Ids index a process-wide table rather than one owned by a [`SourceMap`](crate::SourceMap),
+/// so any location can show its path without a map at hand and ids stay valid across
+/// threads and maps. The cost is that the table is never cleared: each distinct path
+/// stays in memory, once, until the process exits. A long-running process that parses
+/// an unbounded set of paths, such as generated temporary files, grows this table.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(u32);
+
+/// The interned paths, indexed by id.
+#[derive(Default)]
+struct Paths {
+    ids: HashMap<Arc<Path>, FileId>,
+    paths: Vec<Arc<Path>>,
+}
+
+fn paths() -> &'static RwLock<Paths> {
+    static PATHS: OnceLock<RwLock<Paths>> = OnceLock::new();
+    PATHS.get_or_init(Default::default)
+}
+
+impl FileId {
+    /// Returns the id for the path, interning it if it hasn't been seen before.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        if let Some(id) = paths().read().unwrap().ids.get(path) {
+            return *id;
+        }
+
+        let mut paths = paths().write().unwrap();
+        if let Some(id) = paths.ids.get(path) {
+            return *id;
+        }
+        let id = FileId(paths.paths.len() as u32);
+        let path: Arc<Path> = path.into();
+        paths.paths.push(path.clone());
+        paths.ids.insert(path, id);
+        id
+    }
+
+    pub fn path(self) -> Arc<Path> {
+        paths().read().unwrap().paths[self.0 as usize].clone()
+    }
+}
+
+impl std::fmt::Debug for FileId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FileId({:?})", self.path())
+    }
+}
+
+impl From<PathBuf> for FileId {
+    fn from(path: PathBuf) -> Self {
+        Self::new(path)
+    }
+}
+
+impl From<&Path> for FileId {
+    fn from(path: &Path) -> Self {
+        Self::new(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_ids_are_equal_for_equal_paths() {
+        let a = FileId::new("location/a.lisp");
+        let b = FileId::new(PathBuf::from("location/b.lisp"));
+
+        assert_eq!(a, FileId::new(Path::new("location/a.lisp")));
+        assert_ne!(a, b);
+        assert_eq!(Path::new("location/b.lisp"), &*b.path());
+    }
+
+    #[test]
+    fn display_location_includes_path() {
+        let location = Location {
+            line: 2,
+            column: 4,
+            file: Some(FileId::new("a.lisp")),
+            expanded_from: Some(Box::new(Location::new(None).increment_line())),
+        };
+
+        assert_eq!("a.lisp:2:4 (expanded from 1:0)", location.to_string());
+    }
 }
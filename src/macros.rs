@@ -0,0 +1,454 @@
+use crate::{
+    err,
+    list::{into_list, list},
+    template::{Bindings, Template, ELLIPSIS},
+    tokenizer::Token,
+    Ast, Error, List, Location, Node, Symbol,
+};
+use std::collections::HashMap;
+
+/// The identifier that starts a macro definition.
+pub const DEFMACRO: &str = "defmacro";
+/// The default number of nested expansions allowed before giving up.
+pub const DEFAULT_RECURSION_LIMIT: usize = 64;
+
+/// A macro implemented by the host. Receives the whole use, including the macro name.
+pub type NativeMacro = dyn Fn(List) -> Result<Node, Error>;
+
+enum Macro {
+    Template {
+        params: Vec<Symbol>,
+        variadic: bool,
+        template: Template,
+    },
+    Native(Box<NativeMacro>),
+}
+
+/// Expands macro uses in parsed lists before the host sees them.
+///
+/// Macros are defined with `(defmacro name (params...) body)`. The body is a
+/// [`Template`] where each parameter is substituted wherever its name appears; a final
+/// parameter followed by `...` collects the remaining arguments.
+///
+/// ```
+/// let lists = lisper::parse_str("
+///     (defmacro unless (cond body ...) (if cond () (begin body ...)))
+///     (unless done (step) (step))").unwrap();
+///
+/// let expanded = lisper::macros::expand(lists).unwrap();
+/// assert_eq!("(if done () (begin (step) (step)))", expanded[0].to_string());
+/// ```
+///
+/// Expansion is not hygienic; identifiers in the body are inserted as written.
+pub struct Expander {
+    macros: HashMap<Symbol, Macro>,
+    recursion_limit: usize,
+}
+
+impl Default for Expander {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Expands the lists using only the macros they define.
+pub fn expand(lists: Vec<List>) -> Result<Vec<List>, Error> {
+    Expander::new().expand(lists)
+}
+
+impl Expander {
+    /// Creates an expander with no macros.
+    pub fn new() -> Self {
+        Self {
+            macros: HashMap::new(),
+            recursion_limit: DEFAULT_RECURSION_LIMIT,
+        }
+    }
+
+    /// Sets the number of nested expansions allowed before returning an error.
+    pub fn set_recursion_limit(&mut self, limit: usize) -> &mut Self {
+        self.recursion_limit = limit;
+        self
+    }
+
+    /// Registers a macro implemented in Rust.
+    pub fn register<F>(&mut self, name: &str, expand: F) -> &mut Self
+    where
+        F: Fn(List) -> Result<Node, Error> + 'static,
+    {
+        self.macros
+            .insert(Symbol::new(name), Macro::Native(Box::new(expand)));
+        self
+    }
+
+    /// Returns whether the macro is defined.
+    pub fn contains(&self, name: &str) -> bool {
+        Symbol::get(name).is_some_and(|name| self.macros.contains_key(&name))
+    }
+
+    /// Defines a macro from a `(defmacro name (params...) body)` list.
+    pub fn define(&mut self, mut list: List) -> Result<(), Error> {
+        list.assert_identifier(Symbol::DEFMACRO, DEFMACRO)?;
+        let (name, _) = list.pop_identifier("a macro name")?;
+        let mut param_list = list.pop_list("a parameter list")?;
+        let body = list.pop_front("a macro body")?;
+        list.assert_empty(&format!("macro `{name}`"))?;
+
+        let mut params = vec![];
+        let mut variadic = false;
+        while !param_list.is_empty() {
+            if variadic {
+                return err(
+                    &format!("Expected `{ELLIPSIS}` to follow the last parameter"),
+                    &param_list.location(),
+                );
+            }
+
+            let (param, param_location) = param_list.pop_identifier("a parameter name")?;
+            if param == Symbol::ELLIPSIS {
+                return err("Expected a parameter before `...`", &param_location);
+            }
+            params.push(param);
+
+            if param_list.peek_identifier() == Some(Symbol::ELLIPSIS) {
+                param_list.pop_front(ELLIPSIS)?;
+                variadic = true;
+            }
+        }
+
+        let template = Template::with_parameters(vec![body], params.clone());
+        self.macros.insert(
+            name,
+            Macro::Template {
+                params,
+                variadic,
+                template,
+            },
+        );
+        Ok(())
+    }
+
+    /// Collects the macro definitions in the lists, then expands every use.
+    /// Definitions are removed from the result.
+    pub fn expand(&mut self, lists: Vec<List>) -> Result<Vec<List>, Error> {
+        let mut forms = vec![];
+        for l in lists {
+            if l.peek_identifier() == Some(Symbol::DEFMACRO) {
+                self.define(l)?;
+            } else {
+                forms.push(l);
+            }
+        }
+
+        forms
+            .into_iter()
+            .map(|l| {
+                let node = self.expand_node(&l.into_node())?;
+                into_list(node, "a list after macro expansion")
+            })
+            .collect()
+    }
+
+    /// Expands every macro use within the node.
+    pub fn expand_node(&self, node: &Node) -> Result<Node, Error> {
+        /// A list whose children are being expanded.
+        struct Frame {
+            children: std::vec::IntoIter<Node>,
+            expanded: Vec<Node>,
+            tokens: Vec<Token>,
+            /// The number of expansions the list is nested in.
+            depth: usize,
+            /// The length of `uses` before the list was expanded from a macro.
+            uses: usize,
+        }
+
+        // Lists are walked with a stack since both the input and each expansion can nest
+        // arbitrarily deep. `uses` holds the macro uses being expanded, outermost first, so
+        // errors can be traced back through them.
+        let mut uses: Vec<(Symbol, Location)> = vec![];
+        let mut stack: Vec<Frame> = vec![];
+        let mut next = Some((node.clone(), 0));
+        loop {
+            if let Some((mut node, mut depth)) = next.take() {
+                let base = uses.len();
+                while let Some(name) = self.macro_name(&node) {
+                    let location = node.first_location();
+                    if depth >= self.recursion_limit {
+                        let e = Error::new(
+                            format!(
+                                "Macro expansion exceeded the recursion limit of {}",
+                                self.recursion_limit
+                            ),
+                            location,
+                        );
+                        return Err(trace_uses(e, &uses));
+                    }
+
+                    node = match self.apply(name, &node) {
+                        Ok(expanded) => expanded,
+                        Err(e) => return Err(trace_uses(trace(e, &name, &location), &uses)),
+                    };
+                    uses.push((name, location));
+                    depth += 1;
+                }
+
+                let (ast, tokens) = node.into_parts();
+                match ast {
+                    Ast::List(children) => stack.push(Frame {
+                        children: children.into_iter(),
+                        expanded: vec![],
+                        tokens,
+                        depth,
+                        uses: base,
+                    }),
+                    ast => {
+                        uses.truncate(base);
+                        match stack.last_mut() {
+                            Some(frame) => frame.expanded.push(Node { ast, tokens }),
+                            None => return Ok(Node { ast, tokens }),
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let frame = stack.last_mut().expect("a list is being expanded");
+            if let Some(child) = frame.children.next() {
+                next = Some((child, frame.depth));
+                continue;
+            }
+
+            let frame = stack.pop().unwrap();
+            uses.truncate(frame.uses);
+            let node = Node {
+                ast: Ast::List(frame.expanded),
+                tokens: frame.tokens,
+            };
+            match stack.last_mut() {
+                Some(parent) => parent.expanded.push(node),
+                None => return Ok(node),
+            }
+        }
+    }
+
+    /// Returns the macro the node uses, if it is a list starting with one.
+    fn macro_name(&self, node: &Node) -> Option<Symbol> {
+        match node.as_list_ref().ok()?.first().map(|n| &n.ast) {
+            Some(Ast::Identifier(name)) if self.macros.contains_key(name) => Some(*name),
+            _ => None,
+        }
+    }
+
+    /// Expands a single use of the macro.
+    fn apply(&self, name: Symbol, node: &Node) -> Result<Node, Error> {
+        let location = node.first_location();
+        let mut args = list(node, "a macro use")?;
+
+        match &self.macros[&name] {
+            Macro::Native(expand) => expand(args),
+            Macro::Template {
+                params,
+                variadic,
+                template,
+            } => {
+                args.pop_front("a macro name")?;
+                let required = params.len() - usize::from(*variadic);
+                if args.len() < required || (!variadic && args.len() > required) {
+                    let expected = match variadic {
+                        true => format!("at least {required}"),
+                        false => required.to_string(),
+                    };
+                    return err(
+                        &format!(
+                            "`{name}` expects {expected} arguments but got {}",
+                            args.len()
+                        ),
+                        &location,
+                    );
+                }
+
+                let mut bindings = Bindings::new(location.clone());
+                for param in &params[..required] {
+                    bindings.bind(param, args.pop_front(param)?);
+                }
+                if *variadic {
+                    bindings.bind_sequence(&params[required], args.iter().cloned().collect());
+                }
+
+                let mut nodes = template.instantiate(&bindings)?;
+                match nodes.len() {
+                    1 => Ok(nodes.remove(0)),
+                    _ => err("Expected a single form from macro", &location),
+                }
+            }
+        }
+    }
+}
+
+/// Adds a line to the error noting which expansion it happened in.
+fn trace(mut e: Error, name: &str, location: &Location) -> Error {
+    let location = Location {
+        expanded_from: None,
+        ..location.clone()
+    };
+    e.message = format!("{}\n  in expansion of `{name}` at {location}", e.message);
+    e
+}
+
+/// Traces the error through the enclosing uses, innermost first.
+fn trace_uses(e: Error, uses: &[(Symbol, Location)]) -> Error {
+    uses.iter()
+        .rev()
+        .fold(e, |e, (name, location)| trace(e, name, location))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    fn expand_str(contents: &str) -> Result<Vec<String>, Error> {
+        let lists = expand(parse_str(contents)?)?;
+        Ok(lists.iter().map(|l| l.to_string()).collect())
+    }
+
+    #[test]
+    fn expand_removes_definitions_and_expands_uses() {
+        let contents = "
+            (defmacro when (cond body) (if cond body ()))
+            (when ready (go))";
+
+        assert_eq!(vec!["(if ready (go) ())"], expand_str(contents).unwrap());
+    }
+
+    #[test]
+    fn expand_collects_variadic_arguments() {
+        let contents = "
+            (defmacro when (cond body ...) (if cond (begin body ...) ()))
+            (when ready (a) (b))
+            (when ready)";
+
+        assert_eq!(
+            vec!["(if ready (begin (a) (b)) ())", "(if ready (begin) ())"],
+            expand_str(contents).unwrap()
+        );
+    }
+
+    #[test]
+    fn expand_nested_uses() {
+        let contents = "
+            (defmacro inc (x) (set! x (+ x 1)))
+            (defmacro twice (body) (begin body body))
+            (twice (inc counter))";
+
+        assert_eq!(
+            vec!["(begin (set! counter (+ counter 1)) (set! counter (+ counter 1)))"],
+            expand_str(contents).unwrap()
+        );
+    }
+
+    #[test]
+    fn expand_leaves_other_identifiers_as_written() {
+        let contents = "
+            (defmacro swap (a b) (list b a ?a))
+            (swap x y)";
+
+        assert_eq!(vec!["(list y x ?a)"], expand_str(contents).unwrap());
+    }
+
+    #[test]
+    fn expand_native_macro() {
+        let mut expander = Expander::new();
+        expander.register("answer", |l| {
+            let mut node = crate::parse_nodes("(quote 42)", None, &Default::default())?.remove(0);
+            node.tokens[0].location = l.location();
+            Ok(node)
+        });
+
+        let lists = expander
+            .expand(parse_str("(print (answer))").unwrap())
+            .unwrap();
+
+        assert_eq!("(print (quote 42))", lists[0].to_string());
+    }
+
+    #[test]
+    fn expand_returns_err_on_wrong_arity() {
+        let contents = "
+            (defmacro when (cond body) (if cond body ()))
+            (when ready)";
+
+        let result = expand_str(contents).unwrap_err();
+
+        assert_eq!(
+            "`when` expects 2 arguments but got 1\n  in expansion of `when` at 3:12",
+            result.message
+        );
+    }
+
+    #[test]
+    fn expand_returns_err_with_trace() {
+        let contents = "(defmacro bad (xs ...) (oops xs))
+(defmacro outer (x) (begin x))
+(outer\n  (bad))";
+
+        let result = expand_str(contents).unwrap_err();
+
+        assert_eq!(
+            "Template variable `xs` is a sequence and must be followed by `...`
+  in expansion of `bad` at 4:2
+  in expansion of `outer` at 3:0",
+            result.message
+        );
+    }
+
+    #[test]
+    fn expand_returns_err_on_recursion_limit() {
+        let mut expander = Expander::new();
+        expander.set_recursion_limit(3);
+
+        let lists = parse_str("(defmacro forever () (forever)) (forever)").unwrap();
+        let result = expander.expand(lists).unwrap_err();
+
+        assert!(result
+            .message
+            .starts_with("Macro expansion exceeded the recursion limit of 3"));
+    }
+
+    #[test]
+    fn define_returns_err_on_misplaced_ellipsis() {
+        let lists = parse_str("(defmacro m (a ... b) a)").unwrap();
+
+        let result = Expander::new().expand(lists).unwrap_err();
+
+        assert_eq!(
+            "Expected `...` to follow the last parameter",
+            result.message
+        );
+    }
+
+    #[test]
+    fn define_returns_err_at_ellipsis_without_parameter() {
+        let lists = parse_str("(defmacro m\n  (... a) a)").unwrap();
+
+        let result = Expander::new().expand(lists).unwrap_err();
+
+        assert_eq!("Expected a parameter before `...`", result.message);
+        assert_eq!(2, result.location.line);
+        assert_eq!(3, result.location.column);
+    }
+
+    #[test]
+    fn expand_deeply_nested_uses_do_not_overflow_the_stack() {
+        let depth = 100_000;
+        let nested = |inner: &str| format!("{}{inner}{}", "(".repeat(depth), ")".repeat(depth));
+        let contents = format!(
+            "(defmacro twice (x) (begin x x)) {}",
+            nested(&nested("twice (a)"))
+        );
+
+        let actual = expand_str(&contents).unwrap();
+
+        assert_eq!(vec![nested(&nested("begin (a) (a)"))], actual);
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{tokenizer::Token, Location};
+use crate::{
+    tokenizer::{Token, QUOTE},
+    Location, Symbol,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum NodeError {
@@ -18,7 +21,7 @@ pub enum NodeError {
 pub enum Ast {
     Bool(bool),
     Comment(String),
-    Identifier(String),
+    Identifier(Symbol),
     List(Vec<Node>),
     Number(f64),
     String(String),
@@ -46,47 +49,138 @@ pub enum AstType {
     String,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// A parsed node and the tokens it was read from.
+///
+/// `Clone`, `PartialEq`, `Display` and `Drop` keep pending lists on the heap, so they handle
+/// any depth the parser accepts. Since `Node` implements `Drop`, its fields can't be moved out
+/// by destructuring; use [`Node::into_parts`] or the `into_*` accessors instead.
+#[derive(Debug)]
 pub struct Node {
     pub ast: Ast,
     pub tokens: Vec<Token>,
 }
-impl std::fmt::Display for Node {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut buff = String::new();
-        match &self.ast {
-            Ast::Bool(value) => {
-                if *value {
-                    buff.push_str("true");
-                } else {
-                    buff.push_str("false");
+
+impl Clone for Node {
+    fn clone(&self) -> Self {
+        struct Frame<'a> {
+            node: &'a Node,
+            children: std::slice::Iter<'a, Node>,
+            cloned: Vec<Node>,
+        }
+
+        // Clones each list's children before the list itself
+        let mut stack: Vec<Frame> = vec![];
+        let mut next = self;
+        loop {
+            let mut finished = match &next.ast {
+                Ast::List(children) => {
+                    stack.push(Frame {
+                        node: next,
+                        children: children.iter(),
+                        cloned: Vec::with_capacity(children.len()),
+                    });
+                    None
+                }
+                ast => Some(Node {
+                    ast: ast.clone(),
+                    tokens: next.tokens.clone(),
+                }),
+            };
+
+            loop {
+                let frame = match stack.last_mut() {
+                    Some(frame) => frame,
+                    None => return finished.expect("the root has been cloned"),
+                };
+                frame.cloned.extend(finished.take());
+                match frame.children.next() {
+                    Some(child) => {
+                        next = child;
+                        break;
+                    }
+                    None => {
+                        let frame = stack.pop().unwrap();
+                        finished = Some(Node {
+                            ast: Ast::List(frame.cloned),
+                            tokens: frame.node.tokens.clone(),
+                        });
+                    }
                 }
             }
-            Ast::Comment(comment) => {
-                buff.push_str(";");
-                buff.push_str(comment);
-                buff.push_str("\n");
+        }
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack = vec![(self, other)];
+        while let Some((a, b)) = stack.pop() {
+            if a.tokens != b.tokens {
+                return false;
             }
-            Ast::Identifier(id) => {
-                buff.push_str(id);
+            match (&a.ast, &b.ast) {
+                (Ast::List(a), Ast::List(b)) if a.len() == b.len() => stack.extend(a.iter().zip(b)),
+                (a, b) if a != b => return false,
+                _ => {}
             }
-            Ast::List(vec) => {
-                buff.push_str("(");
-                for (i, node) in vec.iter().enumerate() {
-                    if i != 0 {
-                        buff.push_str(" ");
+        }
+        true
+    }
+}
+
+impl std::fmt::Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        enum Item<'a> {
+            Node(&'a Node),
+            Str(&'static str),
+        }
+
+        // Closing parens and separators are pushed between children so they're written in order
+        let mut buff = String::new();
+        let mut stack = vec![Item::Node(self)];
+        while let Some(item) = stack.pop() {
+            let node = match item {
+                Item::Node(node) => node,
+                Item::Str(s) => {
+                    buff.push_str(s);
+                    continue;
+                }
+            };
+
+            match &node.ast {
+                Ast::Bool(value) => {
+                    if *value {
+                        buff.push_str("true");
+                    } else {
+                        buff.push_str("false");
                     }
-                    buff.push_str(&node.to_string());
                 }
-                buff.push_str(")");
-            }
-            Ast::Number(n) => {
-                buff.push_str(&n.to_string());
-            }
-            Ast::String(str) => {
-                buff.push_str("\"");
-                buff.push_str(str);
-                buff.push_str("\"");
+                Ast::Comment(comment) => {
+                    buff.push(';');
+                    buff.push_str(comment);
+                    buff.push('\n');
+                }
+                Ast::Identifier(id) => {
+                    buff.push_str(id);
+                }
+                Ast::List(vec) => {
+                    buff.push('(');
+                    stack.push(Item::Str(")"));
+                    for (i, node) in vec.iter().enumerate().rev() {
+                        stack.push(Item::Node(node));
+                        if i != 0 {
+                            stack.push(Item::Str(" "));
+                        }
+                    }
+                }
+                Ast::Number(n) => {
+                    buff.push_str(&n.to_string());
+                }
+                Ast::String(str) => {
+                    buff.push(QUOTE);
+                    buff.push_str(&str.replace(QUOTE, "\\\""));
+                    buff.push(QUOTE);
+                }
             }
         }
 
@@ -94,7 +188,31 @@ impl std::fmt::Display for Node {
     }
 }
 
+impl Drop for Node {
+    fn drop(&mut self) {
+        // Flattens nested lists onto a stack so deeply nested lists can't overflow when dropped
+        let mut stack = match &mut self.ast {
+            Ast::List(nodes) if nodes.iter().any(|n| matches!(n.ast, Ast::List(_))) => {
+                std::mem::take(nodes)
+            }
+            _ => return,
+        };
+
+        while let Some(mut node) = stack.pop() {
+            if let Ast::List(nodes) = &mut node.ast {
+                stack.append(nodes);
+            }
+        }
+    }
+}
+
 impl Node {
+    /// Moves the ast and tokens out of the node.
+    pub fn into_parts(mut self) -> (Ast, Vec<Token>) {
+        let ast = std::mem::replace(&mut self.ast, Ast::List(vec![]));
+        (ast, std::mem::take(&mut self.tokens))
+    }
+
     pub fn first_location(&self) -> Location {
         if self.tokens.is_empty() {
             Location::default()
@@ -111,7 +229,7 @@ impl Node {
     }
 
     pub fn assert_length(&self, expected: usize) -> Result<(), NodeError> {
-        let l = self.as_list()?;
+        let l = self.as_list_ref()?;
         if l.len() != expected {
             return Err(NodeError::InvalidLength {
                 expected,
@@ -147,7 +265,7 @@ impl Node {
 
     pub fn as_identifier(&self) -> Result<String, NodeError> {
         match &self.ast {
-            Ast::Identifier(value) => Ok(value.clone()),
+            Ast::Identifier(value) => Ok(value.to_string()),
             value => Err(NodeError::InvalidType {
                 expected: AstType::Identifier,
                 got: value.type_(),
@@ -188,4 +306,139 @@ impl Node {
             }),
         }
     }
+
+    /// Returns the comment without cloning it.
+    pub fn as_comment_ref(&self) -> Result<&str, NodeError> {
+        match &self.ast {
+            Ast::Comment(value) => Ok(value),
+            _ => Err(self.invalid_type(AstType::Comment)),
+        }
+    }
+
+    pub fn as_symbol(&self) -> Result<Symbol, NodeError> {
+        match &self.ast {
+            Ast::Identifier(value) => Ok(*value),
+            _ => Err(self.invalid_type(AstType::Identifier)),
+        }
+    }
+
+    /// Returns the children without cloning them.
+    pub fn as_list_ref(&self) -> Result<&[Node], NodeError> {
+        match &self.ast {
+            Ast::List(nodes) => Ok(nodes),
+            _ => Err(self.invalid_type(AstType::List)),
+        }
+    }
+
+    /// Returns the string without cloning it.
+    pub fn as_str(&self) -> Result<&str, NodeError> {
+        match &self.ast {
+            Ast::String(value) => Ok(value),
+            _ => Err(self.invalid_type(AstType::String)),
+        }
+    }
+
+    /// Alias of [`Node::as_str`], named to match `as_list_ref` and `as_comment_ref`.
+    pub fn as_string_ref(&self) -> Result<&str, NodeError> {
+        self.as_str()
+    }
+
+    pub fn into_comment(mut self) -> Result<String, NodeError> {
+        match &mut self.ast {
+            Ast::Comment(value) => Ok(std::mem::take(value)),
+            _ => Err(self.invalid_type(AstType::Comment)),
+        }
+    }
+
+    pub fn into_identifier(self) -> Result<Symbol, NodeError> {
+        self.as_symbol()
+    }
+
+    /// Returns the children, moving them out of the node.
+    pub fn into_list(mut self) -> Result<Vec<Node>, NodeError> {
+        match &mut self.ast {
+            Ast::List(nodes) => Ok(std::mem::take(nodes)),
+            _ => Err(self.invalid_type(AstType::List)),
+        }
+    }
+
+    pub fn into_string(mut self) -> Result<String, NodeError> {
+        match &mut self.ast {
+            Ast::String(value) => Ok(std::mem::take(value)),
+            _ => Err(self.invalid_type(AstType::String)),
+        }
+    }
+
+    fn invalid_type(&self, expected: AstType) -> NodeError {
+        NodeError::InvalidType {
+            expected,
+            got: self.ast.type_(),
+            location: self.first_location(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(contents: &str) -> Node {
+        crate::parse_str(contents).unwrap().remove(0).into_node()
+    }
+
+    #[test]
+    fn borrowing_accessors_return_references() {
+        let node = node("(a \"b\" (c))");
+        let children = node.as_list_ref().unwrap();
+
+        assert_eq!(Symbol::new("a"), children[0].as_symbol().unwrap());
+        assert_eq!("b", children[1].as_str().unwrap());
+        assert_eq!("b", children[1].as_string_ref().unwrap());
+        assert_eq!(1, children[2].as_list_ref().unwrap().len());
+        assert_eq!(
+            Err(NodeError::InvalidType {
+                expected: AstType::String,
+                got: AstType::Identifier,
+                location: children[0].first_location(),
+            }),
+            children[0].as_str()
+        );
+    }
+
+    #[test]
+    fn clone_and_eq_compare_whole_tree() {
+        let node = node("(a (b \"c\" (1 true)) ())");
+        let copy = node.clone();
+
+        assert_eq!(node, copy);
+        assert_eq!(node.to_string(), copy.to_string());
+        assert_ne!(node, self::node("(a (b \"c\" (1 false)) ())"));
+        assert_ne!(node, self::node("(a (b \"c\" (1 true)))"));
+        assert_ne!(node, self::node("(a (b \"c\" (1 true)) x)"));
+    }
+
+    #[test]
+    fn into_parts_moves_ast_and_tokens_out() {
+        let node = node("(a)");
+        let expected = node.tokens.clone();
+
+        let (ast, tokens) = node.into_parts();
+
+        assert!(matches!(ast, Ast::List(nodes) if nodes.len() == 1));
+        assert_eq!(expected, tokens);
+    }
+
+    #[test]
+    fn into_accessors_move_values_out() {
+        let mut children = node("(\"b\" (c d))").into_list().unwrap();
+
+        let nested = children.pop().unwrap().into_list().unwrap();
+        assert_eq!(2, nested.len());
+        assert_eq!("b", children.pop().unwrap().into_string().unwrap());
+        assert_eq!(
+            Symbol::new("c"),
+            nested[0].clone().into_identifier().unwrap()
+        );
+        assert!(nested[0].clone().into_string().is_err());
+    }
 }
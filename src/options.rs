@@ -0,0 +1,220 @@
+use crate::{err, Error, Location};
+use std::path::PathBuf;
+
+/// Options applied while parsing, including limits for use with untrusted input.
+/// Each limit is disabled when `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParseOptions {
+    /// The deepest lists may be nested.
+    pub max_depth: Option<usize>,
+    /// The most tokens the input may contain, including parens and comments.
+    pub max_tokens: Option<usize>,
+    /// The longest a single string may be, in bytes.
+    pub max_string_length: Option<usize>,
+    /// The largest the input may be, in bytes.
+    pub max_input_bytes: Option<usize>,
+    /// Whether `\\` in a string reads as a single backslash, so strings can end in one.
+    /// Otherwise only `\"` is an escape and other backslashes are kept as written.
+    pub backslash_escapes: bool,
+}
+
+impl ParseOptions {
+    /// Options with conservative limits for parsing user supplied files.
+    pub fn untrusted() -> Self {
+        Self {
+            max_depth: Some(128),
+            max_tokens: Some(1_000_000),
+            max_string_length: Some(64 * 1024),
+            max_input_bytes: Some(16 * 1024 * 1024),
+            backslash_escapes: false,
+        }
+    }
+
+    /// Checks the raw input before it is tokenized.
+    pub(crate) fn check_input(&self, contents: &str, path: &Option<PathBuf>) -> Result<(), Error> {
+        match self.max_input_bytes {
+            Some(max) if contents.len() > max => err(
+                &format!("Input exceeds the maximum size of {max} bytes"),
+                &location_of_byte(contents, max, path),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns the line and column of the character containing the given byte.
+fn location_of_byte(contents: &str, byte: usize, path: &Option<PathBuf>) -> Location {
+    let mut location = Location::new(path.clone()).increment_line();
+    for (i, c) in contents.char_indices() {
+        if i + c.len_utf8() > byte {
+            break;
+        }
+        if c == '\n' {
+            location.line += 1;
+            location.column = 0;
+        } else {
+            location.column += 1;
+        }
+    }
+    location
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_str, parse_str_with_options};
+
+    #[test]
+    fn default_has_no_limits() {
+        let contents = format!("{}{}", "(".repeat(1000), ")".repeat(1000));
+
+        assert!(parse_str_with_options(&contents, &ParseOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn max_depth_returns_err_at_list() {
+        let options = ParseOptions {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+
+        assert!(parse_str_with_options("(a (b))", &options).is_ok());
+        let result = parse_str_with_options("(a (b\n (c)))", &options).unwrap_err();
+
+        assert_eq!(
+            "List nesting exceeds the maximum depth of 2",
+            result.message
+        );
+        assert_eq!(2, result.location.line);
+        assert_eq!(1, result.location.column);
+    }
+
+    #[test]
+    fn max_tokens_returns_err_at_token() {
+        let options = ParseOptions {
+            max_tokens: Some(4),
+            ..Default::default()
+        };
+
+        assert!(parse_str_with_options("(a b)", &options).is_ok());
+        let result = parse_str_with_options("(a b c)", &options).unwrap_err();
+
+        assert_eq!("Input exceeds the maximum of 4 tokens", result.message);
+        assert_eq!(6, result.location.column);
+    }
+
+    #[test]
+    fn max_string_length_returns_err_at_string() {
+        let options = ParseOptions {
+            max_string_length: Some(3),
+            ..Default::default()
+        };
+
+        assert!(parse_str_with_options("(a \"abc\")", &options).is_ok());
+        let result = parse_str_with_options("(a \"abcd\")", &options).unwrap_err();
+
+        assert_eq!(
+            "String exceeds the maximum length of 3 bytes",
+            result.message
+        );
+        assert_eq!(3, result.location.column);
+    }
+
+    #[test]
+    fn max_input_bytes_returns_err_at_limit() {
+        let options = ParseOptions {
+            max_input_bytes: Some(8),
+            ..Default::default()
+        };
+
+        assert!(parse_str_with_options("(a b c)", &options).is_ok());
+        let result = parse_str_with_options("(a b)\n(c d)", &options).unwrap_err();
+
+        assert_eq!("Input exceeds the maximum size of 8 bytes", result.message);
+        assert_eq!(2, result.location.line);
+        assert_eq!(2, result.location.column);
+    }
+
+    #[test]
+    fn limits_stop_parsing_when_first_exceeded() {
+        let options = ParseOptions {
+            max_tokens: Some(3),
+            ..Default::default()
+        };
+
+        let result = parse_str_with_options("(a b c) )\n(1abc \"unclosed", &options).unwrap_err();
+
+        assert_eq!("Input exceeds the maximum of 3 tokens", result.message);
+        assert_eq!(5, result.location.column);
+    }
+
+    #[test]
+    fn forms_stop_at_first_limit() {
+        let options = ParseOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        let contents = "(a) (b (c)) (d)";
+
+        let actual = crate::Forms::with_options(contents.as_bytes(), None, &options)
+            .map(|f| f.map(|l| l.to_string()).map_err(|e| e.message))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                Ok("(a)".into()),
+                Err("List nesting exceeds the maximum depth of 1".into())
+            ],
+            actual
+        );
+    }
+
+    #[test]
+    fn backslash_escapes_are_opt_in() {
+        let contents = r#"("C:\\foo" "a\"b" "end\\")"#;
+        let options = ParseOptions {
+            backslash_escapes: true,
+            ..Default::default()
+        };
+        let strings = |list: &crate::List| {
+            list.iter()
+                .map(|n| n.as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        let expected = vec![r"C:\foo", r#"a"b"#, r"end\"];
+
+        let lists = parse_str_with_options(contents, &options).unwrap();
+        let forms = crate::Forms::with_options(contents.as_bytes(), None, &options)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(expected, strings(&lists[0]));
+        assert_eq!(expected, strings(&forms[0]));
+
+        let result = parse_str(contents).unwrap_err();
+        assert_eq!(r#"Unclosed string: end\\")"#, result.message);
+    }
+
+    #[test]
+    fn default_keeps_backslashes_as_written() {
+        let lists = parse_str(r#"("C:\\foo" "a\"b" "c\d")"#).unwrap();
+        let strings = lists[0]
+            .iter()
+            .map(|n| n.as_str().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![r"C:\\foo", r#"a"b"#, r"c\d"], strings);
+    }
+
+    #[test]
+    fn deeply_nested_lists_do_not_overflow_the_stack() {
+        let depth = 100_000;
+        let contents = format!("{}{}", "(".repeat(depth), ")".repeat(depth));
+
+        let lists = parse_str(&contents).unwrap();
+        let display = lists[0].to_string();
+        let copy = lists.clone();
+
+        assert_eq!(contents, display);
+        assert!(copy == lists);
+    }
+}
@@ -3,6 +3,7 @@ use crate::node::{Ast, Node};
 use super::error;
 use super::location::Location;
 use super::tokenizer::{Token, TokenKind};
+use std::collections::VecDeque;
 
 pub type Err = error::Error<ParserErr>;
 
@@ -39,7 +40,7 @@ impl ToString for ListErr {
 pub struct Parser {
     current_location: Location,
     nodes: Vec<Node>,
-    tokens: Vec<Token>,
+    tokens: VecDeque<Token>,
     state_stack: Vec<State>,
 }
 impl Parser {
@@ -65,7 +66,7 @@ impl Parser {
                 }
                 TokenKind::Identifier(id) => {
                     let node = Node {
-                        ast: Ast::Identifier(id.clone()),
+                        ast: Ast::Identifier(*id),
                         tokens: vec![token],
                     };
                     parser.add_node(node)?;
@@ -140,7 +141,6 @@ impl Parser {
                     err.location = state.start;
                     Some(err)
                 }
-                _ => None,
             };
 
             match err {
@@ -179,7 +179,7 @@ impl Parser {
 
         Self {
             current_location,
-            tokens,
+            tokens: tokens.into(),
             nodes: vec![],
             state_stack: vec![],
         }
@@ -187,14 +187,10 @@ impl Parser {
 
     /// Attempts to get the next token.
     fn next_token(&mut self) -> Option<Token> {
-        if self.tokens.is_empty() {
-            None
-        } else {
-            let token = self.tokens.remove(0);
-            self.current_location = token.location.clone();
+        let token = self.tokens.pop_front()?;
+        self.current_location = token.location.clone();
 
-            Some(token)
-        }
+        Some(token)
     }
 
     /// Peeks the top of the state stack.
@@ -0,0 +1,119 @@
+use crate::{lint::is_keyword, Ast, Node};
+
+/// How far nested values are indented when a list is broken across lines.
+const INDENT: usize = 2;
+
+/// Prints the node on one line if it fits in the width. Otherwise lists are broken after their
+/// first element with the rest indented, except keyword lists which keep each key with its value.
+pub fn pretty(node: &Node, width: usize) -> String {
+    enum Item<'a> {
+        Node(&'a Node, usize),
+        Str(&'a str),
+        /// A line break followed by the indent.
+        Break(usize),
+    }
+
+    // Items are pushed in reverse, so lists are laid out without recursing into children
+    let mut out = String::new();
+    let mut stack = vec![Item::Node(node, 0)];
+    while let Some(item) = stack.pop() {
+        let (node, indent) = match item {
+            Item::Node(node, indent) => (node, indent),
+            Item::Str(s) => {
+                out.push_str(s);
+                continue;
+            }
+            Item::Break(indent) => {
+                out.push('\n');
+                out.push_str(&" ".repeat(indent));
+                continue;
+            }
+        };
+
+        let flat = node.to_string();
+        let children = match &node.ast {
+            Ast::List(children)
+                if indent + flat.chars().count() > width && !children.is_empty() =>
+            {
+                children
+            }
+            _ => {
+                out.push_str(&flat);
+                continue;
+            }
+        };
+
+        out.push('(');
+        stack.push(Item::Str(")"));
+        if let Some(pairs) = keyword_pairs(children) {
+            let mut items = vec![];
+            for (i, (key, value)) in pairs.enumerate() {
+                if i > 0 {
+                    items.push(Item::Break(indent + 1));
+                }
+                items.push(Item::Str(key));
+                items.push(Item::Str(" "));
+                items.push(Item::Node(value, indent + 1 + key.chars().count() + 1));
+            }
+            stack.extend(items.into_iter().rev());
+        } else {
+            for child in children[1..].iter().rev() {
+                stack.push(Item::Node(child, indent + INDENT));
+                stack.push(Item::Break(indent + INDENT));
+            }
+            stack.push(Item::Node(&children[0], indent + 1));
+        }
+    }
+    out
+}
+
+/// Returns the keys and values if the nodes alternate between keywords and values.
+fn keyword_pairs(nodes: &[Node]) -> Option<impl Iterator<Item = (&str, &Node)>> {
+    let is_key = |n: &Node| matches!(&n.ast, Ast::Identifier(id) if is_keyword(id));
+    if !nodes.len().is_multiple_of(2) || !nodes.iter().step_by(2).all(is_key) {
+        return None;
+    }
+
+    Some(nodes.chunks(2).map(|pair| match &pair[0].ast {
+        Ast::Identifier(key) => (key.as_str(), &pair[1]),
+        _ => unreachable!("keys were checked to be keywords"),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(contents: &str) -> Node {
+        crate::parse_str(contents).unwrap().remove(0).into_node()
+    }
+
+    #[test]
+    fn pretty_keeps_short_lists_on_one_line() {
+        assert_eq!("(a (b c) 1)", pretty(&node("(a\n (b c)\n 1)"), 20));
+    }
+
+    #[test]
+    fn pretty_breaks_long_lists() {
+        let node = node("(def (add a b) (+ a b) (print \"adding\"))");
+
+        assert_eq!(
+            "(def\n  (add a b)\n  (+ a b)\n  (print \"adding\"))",
+            pretty(&node, 20)
+        );
+        assert_eq!(
+            "(def\n  (add\n    a\n    b)\n  (+\n    a\n    b)\n  (print\n    \"adding\"))",
+            pretty(&node, 8)
+        );
+    }
+
+    #[test]
+    fn pretty_keeps_keys_with_their_values() {
+        let node = node("(:name \"lisper\" :tags (parser lists))");
+
+        assert_eq!(
+            "(:name \"lisper\"\n :tags (parser\n         lists))",
+            pretty(&node, 14)
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use crate::{Error, Location};
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+};
+
+/// Where source files are listed and read from.
+pub trait SourceProvider: Send + Sync {
+    /// Returns every file in the directory and its subdirectories, sorted by path.
+    fn list_files(&self, dir: &Path) -> Result<Vec<PathBuf>, Error>;
+
+    /// Returns the contents of the file.
+    fn read_file(&self, path: &Path) -> Result<String, Error>;
+
+    /// Returns the files in the directory that pass the filter, sorted by path. Only the
+    /// directory not being readable is returned as an error; errors reading anything within it
+    /// are added to the errors so the walk can go on.
+    ///
+    /// By default every file is listed and then filtered. Providers that can skip directories
+    /// while walking should override this so filtered directories aren't read.
+    #[cfg(feature = "load_directory")]
+    fn walk(
+        &self,
+        dir: &Path,
+        filter: &WalkFilter,
+        errors: &mut Vec<Error>,
+    ) -> Result<Vec<PathBuf>, Error> {
+        crate::load::filter_files(self, dir, self.list_files(dir)?, filter, errors)
+    }
+
+    /// Returns the same path for every path naming the file, used to tell if two paths are
+    /// the same file. Removes `.` and `..` components by default.
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, Error> {
+        Ok(normalize(path))
+    }
+}
+
+/// Whether symlinks are followed while walking a directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Symlinks {
+    /// Skip symlinked files and directories.
+    #[default]
+    Skip,
+    /// List symlinked files and walk symlinked directories, skipping links back to a parent.
+    Follow,
+}
+
+/// The files and directories skipped by [`SourceProvider::walk`].
+#[cfg(feature = "load_directory")]
+#[derive(Debug, Clone)]
+pub struct WalkFilter {
+    /// Whether hidden files and directories are walked.
+    pub hidden: bool,
+    /// Whether `.gitignore` and [`crate::IGNORE_FILE`] files are respected.
+    pub ignore_files: bool,
+    /// How deep to walk. A depth of 1 only walks files directly in the directory.
+    pub max_depth: Option<usize>,
+    /// Globs, relative to the directory, for files and directories to skip.
+    pub exclude: globset::GlobSet,
+}
+
+/// Sources read from the file system with `std::fs`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FileSystem {
+    symlinks: Symlinks,
+}
+
+impl FileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn symlinks(&mut self, symlinks: Symlinks) -> &mut Self {
+        self.symlinks = symlinks;
+        self
+    }
+}
+
+impl SourceProvider for FileSystem {
+    fn list_files(&self, dir: &Path) -> Result<Vec<PathBuf>, Error> {
+        if !dir.is_dir() {
+            return Err(format!("Location '{:?}' is not a directory", dir).into());
+        }
+
+        let read_error = |dir: &Path, e: std::io::Error| {
+            Error::new(
+                format!("Error reading directory '{:?}': {e}", dir),
+                Location::new(Some(dir.to_path_buf())),
+            )
+        };
+
+        // Each directory is walked with the canonical paths of the directories above it, so
+        // links back up the tree are skipped
+        let mut files = vec![];
+        let mut dirs = vec![(dir.to_path_buf(), vec![])];
+        while let Some((dir, mut parents)) = dirs.pop() {
+            if let Ok(canonical) = dir.canonicalize() {
+                if parents.contains(&canonical) {
+                    continue;
+                }
+                parents.push(canonical);
+            }
+
+            for entry in std::fs::read_dir(&dir).map_err(|e| read_error(&dir, e))? {
+                let entry = entry.map_err(|e| read_error(&dir, e))?;
+                let mut file_type = entry.file_type().map_err(|e| read_error(&dir, e))?;
+                if file_type.is_symlink() {
+                    if self.symlinks == Symlinks::Skip {
+                        continue;
+                    }
+                    match std::fs::metadata(entry.path()) {
+                        Ok(metadata) => file_type = metadata.file_type(),
+                        // Skip broken links
+                        Err(_) => continue,
+                    }
+                }
+
+                if file_type.is_dir() {
+                    dirs.push((entry.path(), parents.clone()));
+                } else if file_type.is_file() {
+                    files.push(entry.path());
+                }
+            }
+        }
+
+        files.sort();
+        Ok(files)
+    }
+
+    /// Walks with `ignore`, so hidden, excluded and ignored directories aren't entered.
+    #[cfg(feature = "load_directory")]
+    fn walk(
+        &self,
+        dir: &Path,
+        filter: &WalkFilter,
+        errors: &mut Vec<Error>,
+    ) -> Result<Vec<PathBuf>, Error> {
+        if !dir.is_dir() {
+            return Err(format!("Location '{:?}' is not a directory", dir).into());
+        }
+
+        let mut walk = ignore::WalkBuilder::new(dir);
+        walk.standard_filters(false)
+            .hidden(!filter.hidden)
+            .git_ignore(filter.ignore_files)
+            .require_git(false)
+            .parents(false)
+            .follow_links(self.symlinks == Symlinks::Follow)
+            .max_depth(filter.max_depth)
+            .sort_by_file_name(|a, b| a.cmp(b));
+        if filter.ignore_files {
+            walk.add_custom_ignore_filename(crate::IGNORE_FILE);
+        }
+        let root = dir.to_path_buf();
+        let exclude = filter.exclude.clone();
+        walk.filter_entry(move |entry| {
+            let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+            entry.depth() == 0 || !exclude.is_match(relative)
+        });
+
+        let mut files = vec![];
+        for entry in walk.build() {
+            match entry {
+                Ok(entry) if entry.file_type().is_some_and(|t| t.is_file()) => {
+                    files.push(entry.into_path())
+                }
+                Ok(_) => {}
+                // Skip links back to a parent and broken links
+                Err(e)
+                    if is_loop(&e)
+                        || e.io_error()
+                            .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) => {}
+                Err(e) => errors.push(walk_error(e)),
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    fn read_file(&self, path: &Path) -> Result<String, Error> {
+        std::fs::read_to_string(path).map_err(|e| {
+            Error::new(
+                format!("Error reading file '{:?}': {}", path, e),
+                Location::new(Some(path.to_path_buf())),
+            )
+        })
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, Error> {
+        path.canonicalize().map_err(|e| {
+            Error::new(
+                format!("Error reading file '{:?}': {}", path, e),
+                Location::new(Some(path.to_path_buf())),
+            )
+        })
+    }
+}
+
+/// Sources held in memory, for tests or files read from an archive.
+/// Directories exist as long as a file is in them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemorySources {
+    files: BTreeMap<PathBuf, String>,
+}
+
+impl MemorySources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces the file.
+    pub fn insert(&mut self, path: impl AsRef<Path>, contents: impl Into<String>) -> &mut Self {
+        self.files.insert(normalize(path.as_ref()), contents.into());
+        self
+    }
+
+    /// Removes the file, returning its contents.
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> Option<String> {
+        self.files.remove(&normalize(path.as_ref()))
+    }
+}
+
+impl SourceProvider for MemorySources {
+    fn list_files(&self, dir: &Path) -> Result<Vec<PathBuf>, Error> {
+        // Paths are returned under the directory as given, like the file system does
+        let normalized = normalize(dir);
+        let files: Vec<PathBuf> = self
+            .files
+            .keys()
+            .filter_map(|path| path.strip_prefix(&normalized).ok())
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .map(|relative| dir.join(relative))
+            .collect();
+        match files.is_empty() {
+            true => Err(format!("Location '{:?}' is not a directory", dir).into()),
+            false => Ok(files),
+        }
+    }
+
+    fn read_file(&self, path: &Path) -> Result<String, Error> {
+        match self.files.get(&normalize(path)) {
+            Some(contents) => Ok(contents.clone()),
+            None => Err(Error::new(
+                format!("Error reading file '{:?}': not found", path),
+                Location::new(Some(path.to_path_buf())),
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "load_directory")]
+fn is_loop(e: &ignore::Error) -> bool {
+    match e {
+        ignore::Error::Loop { .. } => true,
+        ignore::Error::WithPath { err, .. } | ignore::Error::WithDepth { err, .. } => is_loop(err),
+        _ => false,
+    }
+}
+
+/// Converts an error walking a directory, keeping the path it happened at.
+#[cfg(feature = "load_directory")]
+fn walk_error(e: ignore::Error) -> Error {
+    let mut path = None;
+    let mut inner = &e;
+    loop {
+        match inner {
+            ignore::Error::WithPath { path: p, err } => {
+                path = Some(p.clone());
+                inner = err;
+            }
+            ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+                inner = err
+            }
+            _ => break,
+        }
+    }
+    Error::new(format!("Error reading directory: {e}"), Location::new(path))
+}
+
+/// Removes `.` components and `..` components along with the directory before them.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn memory_sources_list_and_read_files() {
+        let mut sources = MemorySources::new();
+        sources
+            .insert("mods/b.lisp", "(b)")
+            .insert("./mods/a.lisp", "(a)")
+            .insert("other/c.lisp", "(c)");
+
+        assert_eq!(
+            vec![PathBuf::from("mods/a.lisp"), PathBuf::from("mods/b.lisp")],
+            sources.list_files(Path::new("mods")).unwrap()
+        );
+        assert_eq!(
+            "(a)",
+            sources
+                .read_file(Path::new("other/../mods/a.lisp"))
+                .unwrap()
+        );
+        assert_eq!(
+            "Error reading file '\"mods/d.lisp\"': not found",
+            sources
+                .read_file(Path::new("mods/d.lisp"))
+                .unwrap_err()
+                .message
+        );
+        assert!(sources.list_files(Path::new("missing")).is_err());
+    }
+
+    #[test]
+    fn normalize_removes_dot_components() {
+        assert_eq!(PathBuf::from("a/c"), normalize(Path::new("./a/b/../c")));
+        assert_eq!(PathBuf::from("../a"), normalize(Path::new("../a")));
+        assert_eq!(PathBuf::from("/a"), normalize(Path::new("/a/./b/..")));
+    }
+
+    #[test]
+    fn file_system_lists_files_in_path_order() {
+        let dir = TempDir::with_files(
+            "source",
+            &[("b.lisp", ""), ("a/z.lisp", ""), ("a/y.lisp", "")],
+        );
+
+        let files = FileSystem::new().list_files(dir.path()).unwrap();
+
+        let expected: Vec<PathBuf> = ["a/y.lisp", "a/z.lisp", "b.lisp"]
+            .iter()
+            .map(|f| dir.join(f))
+            .collect();
+        assert_eq!(expected, files);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{parse_file_with_options, Error, FileId, List, Location, ParseOptions};
+use std::{collections::HashMap, path::PathBuf};
+
+/// The contents of parsed files, so locations can be turned back into source lines.
+///
+/// The map only owns contents. File ids come from the global path table described on
+/// [`FileId`], so dropping the map frees the contents but not the paths, and ids from
+/// one map can be looked up in another that added the same path.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: HashMap<FileId, SourceFile>,
+}
+
+#[derive(Debug, Clone)]
+struct SourceFile {
+    contents: String,
+    /// The byte index each line starts at.
+    line_starts: Vec<usize>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces the contents of the file, returning its id.
+    pub fn add(&mut self, path: impl Into<PathBuf>, contents: impl Into<String>) -> FileId {
+        let id = FileId::new(path.into());
+        let contents = contents.into();
+        let line_starts = std::iter::once(0)
+            .chain(contents.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        self.files.insert(
+            id,
+            SourceFile {
+                contents,
+                line_starts,
+            },
+        );
+        id
+    }
+
+    /// Adds the file and parses it.
+    pub fn parse(
+        &mut self,
+        path: impl Into<PathBuf>,
+        contents: impl Into<String>,
+        options: &ParseOptions,
+    ) -> Result<Vec<List>, Error> {
+        let id = self.add(path, contents);
+        parse_file_with_options(self.contents(id).unwrap(), id.path().to_path_buf(), options)
+    }
+
+    /// Returns the contents of the file, if added.
+    pub fn contents(&self, file: FileId) -> Option<&str> {
+        self.files.get(&file).map(|f| f.contents.as_str())
+    }
+
+    /// Returns the line the location is on, without its line ending.
+    pub fn line(&self, location: &Location) -> Option<&str> {
+        let file = self.files.get(&location.file?)?;
+        let index = location.line.checked_sub(1)?;
+        let start = *file.line_starts.get(index)?;
+        let end = file
+            .line_starts
+            .get(index + 1)
+            .map_or(file.contents.len(), |end| end - 1);
+        Some(file.contents[start..end].trim_end_matches('\r'))
+    }
+
+    /// Returns the line the location is on with a caret under its column, for error messages.
+    pub fn snippet(&self, location: &Location) -> Option<String> {
+        let line = self.line(location)?;
+        let number = location.line.to_string();
+        let indent = " ".repeat(number.len());
+        let column: String = line
+            .chars()
+            .take(location.column)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        Some(format!(
+            "{indent} |\n{number} | {line}\n{indent} | {column}^"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_returns_source_line_for_location() {
+        let mut sources = SourceMap::new();
+        let lists = sources
+            .parse(
+                "source_map/a.lisp",
+                "(a)\r\n  (b\n\t(c))",
+                &ParseOptions::default(),
+            )
+            .unwrap();
+
+        let b = lists[1].location();
+        let mut b_list = lists[1].clone();
+        b_list.pop_identifier("b").unwrap();
+        let c = b_list.pop_list("c").unwrap().location();
+        assert_eq!(Some("  (b"), sources.line(&b));
+        assert_eq!(Some("(a)"), sources.line(&lists[0].location()));
+        assert_eq!(Some("\t(c))"), sources.line(&c));
+        assert_eq!(None, sources.line(&Location::new(None)));
+        assert_eq!(
+            None,
+            sources.line(&Location::new(Some("source_map/missing.lisp".into())))
+        );
+    }
+
+    #[test]
+    fn snippet_points_at_column() {
+        let mut sources = SourceMap::new();
+        let lists = sources
+            .parse("source_map/b.lisp", "\n  (b 1)", &ParseOptions::default())
+            .unwrap();
+
+        assert_eq!(
+            Some("  |\n2 |   (b 1)\n  |   ^".to_string()),
+            sources.snippet(&lists[0].location())
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{OnceLock, RwLock},
+};
+
+/// An interned identifier. Symbols are equal when their text is, so comparing them is a
+/// single integer compare.
+///
+/// Interned text lives in a process-wide table and is never freed, so interning
+/// unbounded input (rather than identifiers from source) leaks memory.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+/// Symbols interned before any others, in the order of the constants below.
+const KNOWN: [&str; 13] = [
+    "begin", "define", "defmacro", "...", "if", "import", "include", "lambda", "let", "map", "nil",
+    "null", "quote",
+];
+
+impl Symbol {
+    pub const BEGIN: Symbol = Symbol(0);
+    pub const DEFINE: Symbol = Symbol(1);
+    pub const DEFMACRO: Symbol = Symbol(2);
+    pub const ELLIPSIS: Symbol = Symbol(3);
+    pub const IF: Symbol = Symbol(4);
+    pub const IMPORT: Symbol = Symbol(5);
+    pub const INCLUDE: Symbol = Symbol(6);
+    pub const LAMBDA: Symbol = Symbol(7);
+    pub const LET: Symbol = Symbol(8);
+    pub const MAP: Symbol = Symbol(9);
+    pub const NIL: Symbol = Symbol(10);
+    pub const NULL: Symbol = Symbol(11);
+    pub const QUOTE: Symbol = Symbol(12);
+}
+
+/// The interned text, indexed by symbol.
+struct Symbols {
+    ids: HashMap<&'static str, Symbol>,
+    text: Vec<&'static str>,
+}
+
+fn symbols() -> &'static RwLock<Symbols> {
+    static SYMBOLS: OnceLock<RwLock<Symbols>> = OnceLock::new();
+    SYMBOLS.get_or_init(|| {
+        let text = KNOWN.to_vec();
+        let ids = (0..).zip(&text).map(|(i, t)| (*t, Symbol(i))).collect();
+        RwLock::new(Symbols { ids, text })
+    })
+}
+
+thread_local! {
+    /// A per-thread copy of the interned text so looking up a symbol's text doesn't take
+    /// the table's lock. Text is only ever appended, so the copy is refreshed on a miss.
+    static TEXT: RefCell<Vec<&'static str>> = RefCell::new(KNOWN.to_vec());
+}
+
+impl Symbol {
+    /// Returns the symbol for the text, interning it if it hasn't been seen before.
+    pub fn new(text: &str) -> Self {
+        if let Some(symbol) = symbols().read().unwrap().ids.get(text) {
+            return *symbol;
+        }
+
+        let mut symbols = symbols().write().unwrap();
+        if let Some(symbol) = symbols.ids.get(text) {
+            return *symbol;
+        }
+        let symbol = Symbol(symbols.text.len() as u32);
+        let text: &'static str = Box::leak(text.into());
+        symbols.text.push(text);
+        symbols.ids.insert(text, symbol);
+        symbol
+    }
+
+    /// Returns the symbol for the text if it has already been interned.
+    pub fn get(text: &str) -> Option<Self> {
+        symbols().read().unwrap().ids.get(text).copied()
+    }
+
+    pub fn as_str(self) -> &'static str {
+        let index = self.0 as usize;
+        TEXT.with(|cache| {
+            let cached = cache.borrow().get(index).copied();
+            if let Some(text) = cached {
+                return text;
+            }
+
+            let mut cache = cache.borrow_mut();
+            let symbols = symbols().read().unwrap();
+            let len = cache.len();
+            cache.extend_from_slice(&symbols.text[len..]);
+            cache[index]
+        })
+    }
+}
+
+impl std::ops::Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Symbol({:?})", self.as_str())
+    }
+}
+
+/// Symbols are ordered by their text rather than when they were interned.
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        match self == other {
+            true => std::cmp::Ordering::Equal,
+            false => self.as_str().cmp(other.as_str()),
+        }
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<Symbol> for str {
+    fn eq(&self, other: &Symbol) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialEq<Symbol> for &str {
+    fn eq(&self, other: &Symbol) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl PartialEq<Symbol> for String {
+    fn eq(&self, other: &Symbol) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(text: String) -> Self {
+        Self::new(&text)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(text: &String) -> Self {
+        Self::new(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_symbols_match_their_constants() {
+        for (i, text) in KNOWN.iter().enumerate() {
+            assert_eq!(Symbol(i as u32), Symbol::new(text));
+        }
+        assert_eq!("...", Symbol::ELLIPSIS.as_str());
+    }
+
+    #[test]
+    fn symbols_are_equal_for_equal_text() {
+        let a = Symbol::new("symbol-test-a");
+        let b = Symbol::from(String::from("symbol-test-b"));
+
+        assert_eq!(a, Symbol::new("symbol-test-a"));
+        assert_ne!(a, b);
+        assert_eq!(a, "symbol-test-a");
+        assert_eq!(Some(a), Symbol::get("symbol-test-a"));
+        assert_eq!(None, Symbol::get("symbol-test-never-interned"));
+        assert_eq!("symbol-test-b", b);
+        assert!(a < b);
+        assert_eq!("Symbol(\"symbol-test-a\")", format!("{a:?}"));
+    }
+
+    #[test]
+    fn symbols_interned_on_other_threads_have_text() {
+        let before = Symbol::new("symbol-test-before").as_str();
+        let symbol = std::thread::spawn(|| Symbol::new("symbol-test-other-thread"))
+            .join()
+            .unwrap();
+
+        assert_eq!("symbol-test-before", before);
+        assert_eq!("symbol-test-other-thread", symbol.as_str());
+        assert_eq!(symbol.to_string(), symbol.as_str());
+    }
+}
@@ -0,0 +1,405 @@
+use crate::{
+    err, parse_nodes,
+    tokenizer::Token,
+    visit::{walk_mut, VisitMut},
+    Ast, Error, Location, Node, ParseOptions, Symbol,
+};
+use std::{collections::HashMap, path::PathBuf};
+
+/// Marks an identifier as a template variable, e.g. `?name`.
+pub const VARIABLE_PREFIX: char = '?';
+/// Following a variable, splices in every node of a sequence binding.
+pub const ELLIPSIS: &str = "...";
+
+/// A value bound to a template variable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Binding {
+    Node(Node),
+    Sequence(Vec<Node>),
+}
+
+/// The values substituted into a template when it is instantiated.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bindings {
+    origin: Option<Location>,
+    values: HashMap<String, Binding>,
+}
+
+impl Bindings {
+    /// Creates an empty set of bindings that originated at the given location.
+    pub fn new(origin: Location) -> Self {
+        Self {
+            origin: Some(origin),
+            values: HashMap::new(),
+        }
+    }
+
+    /// Binds a single node to the variable.
+    pub fn bind(&mut self, name: &str, node: Node) -> &mut Self {
+        self.values
+            .insert(variable_key(name).to_string(), Binding::Node(node));
+        self
+    }
+
+    /// Binds a sequence of nodes to the variable. Must be used with an ellipsis.
+    pub fn bind_sequence(&mut self, name: &str, nodes: Vec<Node>) -> &mut Self {
+        self.values
+            .insert(variable_key(name).to_string(), Binding::Sequence(nodes));
+        self
+    }
+
+    /// Returns the binding for the variable.
+    pub fn get(&self, name: &str) -> Option<&Binding> {
+        self.values.get(variable_key(name))
+    }
+
+    /// Returns the location the bindings originated from.
+    pub fn origin(&self) -> Option<&Location> {
+        self.origin.as_ref()
+    }
+}
+
+/// A tree of nodes containing variables to substitute.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    nodes: Vec<Node>,
+    /// The identifiers to substitute, if they aren't marked with the prefix.
+    parameters: Option<Vec<Symbol>>,
+}
+
+impl Template {
+    /// Parses the given contents into a template.
+    pub fn parse(contents: &str) -> Result<Self, Error> {
+        Ok(Self::from_nodes(parse_nodes(
+            contents,
+            None,
+            &ParseOptions::default(),
+        )?))
+    }
+
+    /// Parses the given contents from a file into a template.
+    pub fn parse_file(contents: &str, path: PathBuf) -> Result<Self, Error> {
+        Ok(Self::from_nodes(parse_nodes(
+            contents,
+            Some(path),
+            &ParseOptions::default(),
+        )?))
+    }
+
+    /// Creates a template from already parsed nodes.
+    pub fn from_nodes(nodes: Vec<Node>) -> Self {
+        Self {
+            nodes,
+            parameters: None,
+        }
+    }
+
+    /// Creates a template whose variables are the given identifiers, written without a prefix.
+    pub fn with_parameters(nodes: Vec<Node>, parameters: Vec<Symbol>) -> Self {
+        Self {
+            nodes,
+            parameters: Some(parameters),
+        }
+    }
+
+    /// Returns the nodes making up the template.
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Substitutes the bindings into the template, producing new nodes.
+    /// Substituted nodes keep their own location and are marked as expanded from the template,
+    /// while the rest of the template is marked as expanded from the bindings' origin.
+    pub fn instantiate(&self, bindings: &Bindings) -> Result<Vec<Node>, Error> {
+        instantiate_nodes(&self.nodes, bindings, self.parameters.as_deref())
+    }
+}
+
+/// Returns the variable name without its prefix.
+fn variable_key(name: &str) -> &str {
+    name.strip_prefix(VARIABLE_PREFIX).unwrap_or(name)
+}
+
+/// Returns the variable as written if the node is one.
+fn variable(node: &Node, parameters: Option<&[Symbol]>) -> Option<Symbol> {
+    match (&node.ast, parameters) {
+        (Ast::Identifier(id), Some(parameters)) => parameters.contains(id).then_some(*id),
+        (Ast::Identifier(id), None) if id.len() > 1 && id.starts_with(VARIABLE_PREFIX) => Some(*id),
+        _ => None,
+    }
+}
+
+/// Returns whether the node is an ellipsis.
+fn is_ellipsis(node: Option<&Node>) -> bool {
+    matches!(node.map(|n| &n.ast), Some(Ast::Identifier(id)) if id == ELLIPSIS)
+}
+
+fn instantiate_nodes(
+    nodes: &[Node],
+    bindings: &Bindings,
+    parameters: Option<&[Symbol]>,
+) -> Result<Vec<Node>, Error> {
+    /// A list of the template whose children are being instantiated.
+    struct Frame<'a> {
+        nodes: &'a [Node],
+        next: usize,
+        instantiated: Vec<Node>,
+        tokens: Vec<Token>,
+    }
+
+    // Templates are parsed from input, so nested lists are walked with a stack
+    let mut stack = vec![Frame {
+        nodes,
+        next: 0,
+        instantiated: vec![],
+        tokens: vec![],
+    }];
+    loop {
+        let frame = stack.last_mut().expect("a list is being instantiated");
+        let i = frame.next;
+        let Some(node) = frame.nodes.get(i) else {
+            let frame = stack.pop().unwrap();
+            let node = Node {
+                ast: Ast::List(frame.instantiated),
+                tokens: frame.tokens,
+            };
+            match stack.last_mut() {
+                Some(parent) => parent.instantiated.push(node),
+                None => return Ok(node.into_list().unwrap()),
+            }
+            continue;
+        };
+        frame.next += 1;
+
+        let location = node.first_location();
+        let spliced = is_ellipsis(frame.nodes.get(i + 1));
+
+        match variable(node, parameters) {
+            Some(name) => match (bindings.get(&name), spliced) {
+                (Some(Binding::Node(value)), false) => {
+                    frame
+                        .instantiated
+                        .push(relocate(value, &|l| l.with_expansion(location.clone())));
+                }
+                (Some(Binding::Sequence(values)), true) => {
+                    for value in values {
+                        frame
+                            .instantiated
+                            .push(relocate(value, &|l| l.with_expansion(location.clone())));
+                    }
+                    // Skip the ellipsis
+                    frame.next += 1;
+                }
+                (Some(Binding::Node(_)), true) => {
+                    return err(
+                        &format!("Template variable `{name}` is not a sequence and cannot be followed by `{ELLIPSIS}`"),
+                        &location,
+                    );
+                }
+                (Some(Binding::Sequence(_)), false) => {
+                    return err(
+                        &format!("Template variable `{name}` is a sequence and must be followed by `{ELLIPSIS}`"),
+                        &location,
+                    );
+                }
+                (None, _) => {
+                    return err(&format!("Unbound template variable `{name}`"), &location);
+                }
+            },
+            None if is_ellipsis(Some(node)) => {
+                return err(
+                    &format!("Expected a template variable before `{ELLIPSIS}`"),
+                    &location,
+                );
+            }
+            None => {
+                let tokens = expanded_tokens(node, bindings);
+                match &node.ast {
+                    Ast::List(nodes) => stack.push(Frame {
+                        nodes,
+                        next: 0,
+                        instantiated: vec![],
+                        tokens,
+                    }),
+                    ast => frame.instantiated.push(Node {
+                        ast: ast.clone(),
+                        tokens,
+                    }),
+                }
+            }
+        }
+    }
+}
+
+/// Clones the node's tokens, marking them as expanded from the bindings' origin.
+fn expanded_tokens(node: &Node, bindings: &Bindings) -> Vec<Token> {
+    node.tokens
+        .iter()
+        .map(|t| {
+            let mut token = t.clone();
+            if let Some(origin) = bindings.origin() {
+                token.location = token.location.with_expansion(origin.clone());
+            }
+            token
+        })
+        .collect()
+}
+
+/// Clones the node, updating the location of every token.
+fn relocate(node: &Node, f: &dyn Fn(Location) -> Location) -> Node {
+    struct Relocate<'a>(&'a dyn Fn(Location) -> Location);
+
+    impl VisitMut for Relocate<'_> {
+        fn enter(&mut self, node: &mut Node) -> bool {
+            for token in &mut node.tokens {
+                token.location = (self.0)(std::mem::take(&mut token.location));
+            }
+            true
+        }
+    }
+
+    let mut node = node.clone();
+    walk_mut(&mut Relocate(f), &mut node);
+    node
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileId;
+    use std::path::Path;
+
+    fn node(contents: &str) -> Node {
+        parse_nodes(contents, Some("bindings".into()), &ParseOptions::default()).unwrap()[0].clone()
+    }
+
+    fn display(nodes: &[Node]) -> String {
+        nodes
+            .iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn instantiate_substitutes_variables() {
+        let template = Template::parse("(set! ?name (+ ?name 1))").unwrap();
+        let mut bindings = Bindings::default();
+        bindings.bind("name", node("counter"));
+
+        let actual = template.instantiate(&bindings).unwrap();
+
+        assert_eq!("(set! counter (+ counter 1))", display(&actual));
+    }
+
+    #[test]
+    fn instantiate_splices_sequences() {
+        let template = Template::parse("(begin ?body ... (done))").unwrap();
+        let mut bindings = Bindings::default();
+        bindings.bind_sequence("?body", vec![node("(a 1)"), node("(b 2)")]);
+
+        let actual = template.instantiate(&bindings).unwrap();
+
+        assert_eq!("(begin (a 1) (b 2) (done))", display(&actual));
+    }
+
+    #[test]
+    fn instantiate_splices_empty_sequences() {
+        let template = Template::parse("(list ?items ...)").unwrap();
+        let mut bindings = Bindings::default();
+        bindings.bind_sequence("items", vec![]);
+
+        let actual = template.instantiate(&bindings).unwrap();
+
+        assert_eq!("(list)", display(&actual));
+    }
+
+    #[test]
+    fn instantiate_returns_err_on_unbound_variable() {
+        let template = Template::parse("(set! ?name 1)").unwrap();
+
+        let result = template.instantiate(&Bindings::default()).unwrap_err();
+
+        assert_eq!("Unbound template variable `?name`", result.message);
+        assert_eq!(1, result.location.line);
+        assert_eq!(6, result.location.column);
+    }
+
+    #[test]
+    fn instantiate_returns_err_on_sequence_without_ellipsis() {
+        let template = Template::parse("(list ?items)").unwrap();
+        let mut bindings = Bindings::default();
+        bindings.bind_sequence("items", vec![node("1")]);
+
+        let result = template.instantiate(&bindings).unwrap_err();
+
+        assert_eq!(
+            "Template variable `?items` is a sequence and must be followed by `...`",
+            result.message
+        );
+    }
+
+    #[test]
+    fn instantiate_returns_err_on_node_with_ellipsis() {
+        let template = Template::parse("(list ?item ...)").unwrap();
+        let mut bindings = Bindings::default();
+        bindings.bind("item", node("1"));
+
+        let result = template.instantiate(&bindings).unwrap_err();
+
+        assert_eq!(
+            "Template variable `?item` is not a sequence and cannot be followed by `...`",
+            result.message
+        );
+    }
+
+    #[test]
+    fn instantiate_returns_err_on_stray_ellipsis() {
+        let template = Template::parse("(list 1 ...)").unwrap();
+
+        let result = template.instantiate(&Bindings::default()).unwrap_err();
+
+        assert_eq!("Expected a template variable before `...`", result.message);
+    }
+
+    #[test]
+    fn instantiate_locations_point_at_template_and_bindings() {
+        let template = Template::parse_file("(inc ?x)", "template".into()).unwrap();
+        let origin = Location {
+            line: 7,
+            column: 2,
+            file: Some(FileId::new("use")),
+            expanded_from: None,
+        };
+        let mut bindings = Bindings::new(origin.clone());
+        bindings.bind("x", node("counter"));
+
+        let actual = template.instantiate(&bindings).unwrap();
+        let list = actual[0].as_list().unwrap();
+
+        // Template nodes keep their location and point back at the bindings' origin
+        let head = list[0].first_location();
+        assert_eq!(Some(Path::new("template")), head.path().as_deref());
+        assert_eq!(Some(Box::new(origin)), head.expanded_from);
+
+        // Substituted nodes keep their location and point back at the template variable
+        let x = list[1].first_location();
+        assert_eq!(Some(Path::new("bindings")), x.path().as_deref());
+        let from = x.expanded_from.unwrap();
+        assert_eq!(Some(Path::new("template")), from.path().as_deref());
+        assert_eq!(5, from.column);
+    }
+
+    #[test]
+    fn deeply_nested_templates_do_not_overflow_the_stack() {
+        let depth = 100_000;
+        let nested = |inner: &str| format!("{}{inner}{}", "(".repeat(depth), ")".repeat(depth));
+        let template = Template::parse(&nested("?x")).unwrap();
+        let mut bindings = Bindings::default();
+        bindings.bind("x", node(&nested("a")));
+
+        let actual = template.instantiate(&bindings).unwrap();
+
+        assert_eq!(nested(&nested("a")), display(&actual));
+    }
+}
@@ -1,5 +1,5 @@
-use super::{error, location::Location};
-use std::path::PathBuf;
+use super::{error, location::Location, ParseOptions, Symbol};
+use std::{io::BufRead, path::PathBuf};
 
 pub type Err = error::Error<TokenErr>;
 pub type Success = Vec<Token>;
@@ -23,7 +23,7 @@ pub struct Token {
 pub enum TokenKind {
     Bool(bool),
     Comment(String),
-    Identifier(String),
+    Identifier(Symbol),
     Number(f64),
     String(String),
     Symbol(char),
@@ -58,6 +58,9 @@ pub enum TokenErr {
     String(StringErr),
     Type(TypeErr),
     Identifier(IdentifierErr),
+    Read(String),
+    /// A limit in the `ParseOptions` was exceeded.
+    Limit(String),
     StackUnderflow,
 }
 impl ToString for TokenErr {
@@ -72,6 +75,8 @@ impl ToString for TokenErr {
             },
             TokenErr::Type(err) => format!("Type error: {:?}", err),
             TokenErr::Identifier(err) => format!("Identifier error: {:?}", err),
+            TokenErr::Read(err) => format!("Read error: {}", err),
+            TokenErr::Limit(err) => err.clone(),
             TokenErr::StackUnderflow => "Stack underflow".into(),
         }
     }
@@ -110,6 +115,8 @@ pub struct Tokenizer {
     next_char_index: usize,
     original_contents: String,
     state_stack: Vec<State>,
+    /// Whether `\\` in a string is an escaped escape character, see `ParseOptions`.
+    backslash_escapes: bool,
 }
 
 fn is_symbol(c: char) -> bool {
@@ -129,92 +136,128 @@ impl Tokenizer {
 
         let mut prev_char = None;
         while let Some(c) = tokenizer.next_character() {
-            let is_comment = c == COMMENT;
-            let is_quote = c == QUOTE;
-            let is_whitespace = c.is_whitespace();
-            let prev_char_is_escape = Some(ESCAPE_CHARACTER) == prev_char;
-            let is_newline = c == NEW_LINE;
-            let is_symbol = is_symbol(c);
-            let is_making_comment = tokenizer.is_making_comment();
-            let is_terminal_character = is_symbol | is_whitespace || is_comment || is_newline;
-
-            // Handle making a string
-            if tokenizer.is_making_string() {
-                if is_quote && !prev_char_is_escape {
-                    tokenizer.make_string()?;
-                } else {
-                    let mut state = tokenizer.pop_string_state()?;
-                    state.contents.push(c);
-                    tokenizer.state_stack.push(State::String(state));
-                }
-            }
-            // End the string
-            else if is_quote && !is_making_comment {
-                if tokenizer.is_making_identifier() {
-                    tokenizer.make_identifier()?;
-                }
+            tokenizer.push_character(c, prev_char)?;
+            prev_char = Some(c);
+        }
 
-                tokenizer.state_stack.push(State::String(StringState {
-                    start: tokenizer.location.clone(),
-                    contents: String::new(),
-                }));
-            } else if is_terminal_character {
-                if is_whitespace && tokenizer.state_stack.is_empty() {
+        tokenizer.finalize()
+    }
 
-                    // do nothing
-                } else {
-                    let mut skip_symbol = false;
+    /// Creates a tokenizer that resumes part way through a file at the given location.
+    pub(crate) fn starting_at(location: Location) -> Self {
+        let mut tokenizer = Self::load("", None);
+        tokenizer.location = location;
+        tokenizer
+    }
 
-                    if tokenizer.is_making_identifier() {
-                        tokenizer.make_identifier()?;
-                    }
+    /// Processes more contents, which must already have normalized line endings.
+    /// The previous character is needed when resuming inside a string.
+    pub(crate) fn push_str(
+        &mut self,
+        contents: &str,
+        mut prev_char: Option<char>,
+    ) -> Result<(), Err> {
+        for c in contents.chars() {
+            self.push_character(c, prev_char)?;
+            prev_char = Some(c);
+        }
+        Ok(())
+    }
 
-                    if is_comment && !is_making_comment {
-                        tokenizer.state_stack.push(State::Comment(CommentState {
-                            start: tokenizer.location.clone(),
-                            contents: String::new(),
-                        }));
-                    } else if is_making_comment {
-                        if !is_comment {
-                            tokenizer.push_char_on_comment(c)?;
-                            skip_symbol = true;
-                        }
-                    }
+    /// Returns whether every character processed so far has been turned into a token.
+    pub(crate) fn is_idle(&self) -> bool {
+        self.state_stack.is_empty()
+    }
 
-                    if is_newline && tokenizer.is_making_comment() {
-                        tokenizer.make_comment()?;
-                    }
+    /// Removes the tokens completed so far.
+    pub(crate) fn take_tokens(&mut self) -> Vec<Token> {
+        std::mem::take(&mut self.tokens)
+    }
 
-                    if is_symbol && !skip_symbol {
-                        tokenizer.tokens.push(Token {
-                            kind: TokenKind::Symbol(c),
-                            location: tokenizer.location.clone(),
-                        });
-                    }
-                }
-            } else if tokenizer.is_making_identifier() {
-                let mut state = tokenizer.pop_identifier_state()?;
+    /// Processes a single character, adding any tokens it completes.
+    fn push_character(&mut self, c: char, prev_char: Option<char>) -> Result<(), Err> {
+        let is_comment = c == COMMENT;
+        let is_quote = c == QUOTE;
+        let is_whitespace = c.is_whitespace();
+        let prev_char_is_escape = Some(ESCAPE_CHARACTER) == prev_char;
+        let is_newline = c == NEW_LINE;
+        let is_symbol = is_symbol(c);
+        let is_making_comment = self.is_making_comment();
+        let is_terminal_character = is_symbol | is_whitespace || is_comment || is_newline;
+
+        // Handle making a string
+        if self.is_making_string() {
+            let is_escaped = prev_char_is_escape
+                && (!self.backslash_escapes
+                    || matches!(self.state_stack.last(), Some(State::String(s)) if ends_in_escape(&s.contents)));
+            if is_quote && !is_escaped {
+                self.make_string()?;
+            } else {
+                let mut state = self.pop_string_state()?;
                 state.contents.push(c);
-                tokenizer.state_stack.push(State::Identifier(state));
-            } else if tokenizer.is_making_comment() {
-                tokenizer.push_char_on_comment(c)?;
+                self.state_stack.push(State::String(state));
+            }
+        }
+        // End the string
+        else if is_quote && !is_making_comment {
+            if self.is_making_identifier() {
+                self.make_identifier()?;
+            }
+
+            self.state_stack.push(State::String(StringState {
+                start: self.location.clone(),
+                contents: String::new(),
+            }));
+        } else if is_terminal_character {
+            if is_whitespace && self.state_stack.is_empty() {
+
+                // do nothing
             } else {
-                // Start identifier
-                tokenizer
-                    .state_stack
-                    .push(State::Identifier(IdentifierState {
-                        start: tokenizer.location.clone(),
-                        contents: c.to_string(),
+                let mut skip_symbol = false;
+
+                if self.is_making_identifier() {
+                    self.make_identifier()?;
+                }
+
+                if is_comment && !is_making_comment {
+                    self.state_stack.push(State::Comment(CommentState {
+                        start: self.location.clone(),
+                        contents: String::new(),
                     }));
-            }
+                } else if is_making_comment && !is_comment {
+                    self.push_char_on_comment(c)?;
+                    skip_symbol = true;
+                }
 
-            // TODO: terminations of special characters
+                if is_newline && self.is_making_comment() {
+                    self.make_comment()?;
+                }
 
-            prev_char = Some(c);
-            tokenizer.increment_location(c);
+                if is_symbol && !skip_symbol {
+                    self.tokens.push(Token {
+                        kind: TokenKind::Symbol(c),
+                        location: self.location.clone(),
+                    });
+                }
+            }
+        } else if self.is_making_identifier() {
+            let mut state = self.pop_identifier_state()?;
+            state.contents.push(c);
+            self.state_stack.push(State::Identifier(state));
+        } else if self.is_making_comment() {
+            self.push_char_on_comment(c)?;
+        } else {
+            // Start identifier
+            self.state_stack.push(State::Identifier(IdentifierState {
+                start: self.location.clone(),
+                contents: c.to_string(),
+            }));
         }
 
-        tokenizer.finalize()
+        // TODO: terminations of special characters
+
+        self.increment_location(c);
+        Ok(())
     }
 
     fn push_char_on_comment(&mut self, c: char) -> Result<(), Err> {
@@ -262,6 +305,7 @@ impl Tokenizer {
             next_char_index: 0,
             original_contents: contents,
             state_stack: vec![],
+            backslash_escapes: false,
         }
     }
 
@@ -281,7 +325,7 @@ impl Tokenizer {
                 }
 
                 state => Err(self.make_err(TokenErr::Type(TypeErr::WrongType {
-                    got: { state },
+                    got: state,
                     expected: TokenType::Comment,
                 }))),
             },
@@ -336,7 +380,7 @@ impl Tokenizer {
                     }
 
                     self.tokens.push(Token {
-                        kind: TokenKind::Identifier(contents.into()),
+                        kind: TokenKind::Identifier(Symbol::new(&contents)),
                         location: start,
                     });
 
@@ -344,7 +388,7 @@ impl Tokenizer {
                 }
 
                 state => Err(self.make_err(TokenErr::Type(TypeErr::WrongType {
-                    got: { state },
+                    got: state,
                     expected: TokenType::Identifier,
                 }))),
             },
@@ -360,10 +404,13 @@ impl Tokenizer {
         match self.state_stack.pop() {
             Some(state) => match state {
                 State::String(StringState { start, contents }) => {
-                    let contents = contents.replace("\\\"", "\"");
+                    let contents = match self.backslash_escapes {
+                        true => unescape(&contents),
+                        false => contents.replace("\\\"", "\""),
+                    };
 
                     self.tokens.push(Token {
-                        kind: TokenKind::String(contents.into()),
+                        kind: TokenKind::String(contents),
                         location: start,
                     });
 
@@ -371,7 +418,7 @@ impl Tokenizer {
                 }
 
                 state => Err(self.make_err(TokenErr::Type(TypeErr::WrongType {
-                    got: { state },
+                    got: state,
                     expected: TokenType::String,
                 }))),
             },
@@ -384,11 +431,19 @@ impl Tokenizer {
 
     /// Returns the next character in the contents.
     fn next_character(&mut self) -> Option<char> {
-        self.original_contents.chars().nth(self.next_char_index)
+        self.original_contents[self.next_char_index..]
+            .chars()
+            .next()
     }
 
     /// Convert to the final form.
     fn finalize(mut self) -> Result<Success, Err> {
+        self.flush()?;
+        Ok(self.tokens)
+    }
+
+    /// Completes any token still being made at the end of the input.
+    pub(crate) fn flush(&mut self) -> Result<(), Err> {
         while let Ok(state) = self.pop_state() {
             match state {
                 State::String(state) => {
@@ -405,13 +460,13 @@ impl Tokenizer {
             }
         }
 
-        Ok(self.tokens)
+        Ok(())
     }
 
     /// Increments the location for the given character.
     fn increment_location(&mut self, c: char) {
-        // Increment if next character exists
-        self.next_char_index += 1;
+        // Increment if next character exists. This is a byte index so lookups stay constant time.
+        self.next_char_index += c.len_utf8();
 
         // Move location if it's a new line.
         if c == '\n' {
@@ -472,6 +527,265 @@ impl Tokenizer {
     }
 }
 
+/// Returns whether the string ends in an escape character that isn't itself escaped.
+pub(crate) fn ends_in_escape(contents: &str) -> bool {
+    contents
+        .chars()
+        .rev()
+        .take_while(|c| *c == ESCAPE_CHARACTER)
+        .count()
+        % 2
+        == 1
+}
+
+/// Replaces escaped quotes and escape characters with the characters themselves.
+pub(crate) fn unescape(contents: &str) -> String {
+    let mut unescaped = String::with_capacity(contents.len());
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(&next @ (QUOTE | ESCAPE_CHARACTER)) if c == ESCAPE_CHARACTER => {
+                unescaped.push(next);
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Tokenizes a reader one token at a time, holding only the token being made in memory.
+/// Stops at the first limit in its `ParseOptions` that is exceeded.
+pub struct TokenStream<R> {
+    reader: R,
+    tokenizer: Tokenizer,
+    prev_char: Option<char>,
+    prev_char_was_carriage_return: bool,
+    finished: bool,
+    options: ParseOptions,
+    bytes: usize,
+    tokens: usize,
+    depth: usize,
+    /// The escapes in the string being made, which shrink to one byte each.
+    escapes: usize,
+    /// Whether the last character added to the string was an unescaped escape character.
+    escaping: bool,
+}
+
+impl<R: BufRead> TokenStream<R> {
+    /// Creates a stream over the reader.
+    pub fn new(reader: R, path: Option<PathBuf>) -> Self {
+        Self::with_options(reader, path, &ParseOptions::default())
+    }
+
+    /// Creates a stream over the reader, enforcing the limits in the options.
+    pub fn with_options(reader: R, path: Option<PathBuf>, options: &ParseOptions) -> Self {
+        let mut tokenizer = Tokenizer::load("", path);
+        tokenizer.backslash_escapes = options.backslash_escapes;
+        Self {
+            reader,
+            tokenizer,
+            prev_char: None,
+            prev_char_was_carriage_return: false,
+            finished: false,
+            options: options.clone(),
+            bytes: 0,
+            tokens: 0,
+            depth: 0,
+            escapes: 0,
+            escaping: false,
+        }
+    }
+
+    /// Returns the location of the next character to be read.
+    pub fn location(&self) -> &Location {
+        &self.tokenizer.location
+    }
+
+    /// Reads the next character, decoding UTF-8 that may span multiple reads.
+    fn read_char(&mut self) -> Result<Option<char>, Err> {
+        let mut bytes = [0; 4];
+        let mut len = 0;
+        loop {
+            let buffer = match self.reader.fill_buf() {
+                Ok(buffer) => buffer,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(self.tokenizer.make_err(TokenErr::Read(e.to_string()))),
+            };
+
+            let byte = match buffer.first() {
+                Some(byte) => *byte,
+                None if len == 0 => return Ok(None),
+                None => {
+                    return Err(self.tokenizer.make_err(TokenErr::Read(
+                        "Unexpected end of input in UTF-8 character".into(),
+                    )))
+                }
+            };
+            self.reader.consume(1);
+            bytes[len] = byte;
+            len += 1;
+
+            self.bytes += 1;
+            if let Some(max) = self.options.max_input_bytes {
+                if self.bytes > max {
+                    let msg = format!("Input exceeds the maximum size of {max} bytes");
+                    return Err(self.tokenizer.make_err(TokenErr::Limit(msg)));
+                }
+            }
+
+            let expected = match bytes[0] {
+                0x00..=0x7F => 1,
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF7 => 4,
+                _ => 0,
+            };
+            if len >= expected {
+                return match std::str::from_utf8(&bytes[..len]) {
+                    Ok(s) => Ok(s.chars().next()),
+                    Err(_) => Err(self
+                        .tokenizer
+                        .make_err(TokenErr::Read("Invalid UTF-8".into()))),
+                };
+            }
+        }
+    }
+
+    /// Reads the next character with line endings normalized the same way as `Tokenizer::load`.
+    fn read_normalized_char(&mut self) -> Result<Option<char>, Err> {
+        loop {
+            let c = self.read_char()?;
+            let was_carriage_return = self.prev_char_was_carriage_return;
+            self.prev_char_was_carriage_return = c == Some('\r');
+
+            match c {
+                Some('\r') => return Ok(Some(NEW_LINE)),
+                Some(NEW_LINE) if was_carriage_return => continue,
+                c => return Ok(c),
+            }
+        }
+    }
+
+    /// Counts the token about to be returned against the limits.
+    fn check_token(&mut self, token: &Token) -> Result<(), Err> {
+        let limit = |msg| {
+            Err(error::Error {
+                kind: TokenErr::Limit(msg),
+                location: token.location.clone(),
+            })
+        };
+
+        if self.options.max_tokens == Some(self.tokens) {
+            return limit(format!(
+                "Input exceeds the maximum of {} tokens",
+                self.tokens
+            ));
+        }
+        self.tokens += 1;
+
+        match token.kind {
+            TokenKind::Symbol('(') => {
+                self.depth += 1;
+                match self.options.max_depth {
+                    Some(max) if self.depth > max => {
+                        limit(format!("List nesting exceeds the maximum depth of {max}"))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            TokenKind::Symbol(')') => {
+                self.depth = self.depth.saturating_sub(1);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the string being made after the character was added, so long strings are
+    /// rejected before they are read in full.
+    fn check_string(&mut self, c: char, was_making_string: bool) -> Result<(), Err> {
+        let max = match self.options.max_string_length {
+            Some(max) => max,
+            None => return Ok(()),
+        };
+        if !was_making_string {
+            self.escapes = 0;
+            self.escaping = false;
+        } else if !self.options.backslash_escapes {
+            if c == QUOTE {
+                self.escapes += 1;
+            }
+        } else if self.escaping {
+            if c == QUOTE || c == ESCAPE_CHARACTER {
+                self.escapes += 1;
+            }
+            self.escaping = false;
+        } else {
+            self.escaping = c == ESCAPE_CHARACTER;
+        }
+
+        match self.tokenizer.state_stack.last() {
+            Some(State::String(state)) if state.contents.len() - self.escapes > max => {
+                Err(error::Error {
+                    kind: TokenErr::Limit(format!(
+                        "String exceeds the maximum length of {max} bytes"
+                    )),
+                    location: state.start.clone(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Stops the stream after an error.
+    fn fail(&mut self, e: Err) -> Option<Result<Token, Err>> {
+        self.finished = true;
+        self.tokenizer.tokens.clear();
+        Some(Err(e))
+    }
+}
+
+impl<R: BufRead> Iterator for TokenStream<R> {
+    type Item = Result<Token, Err>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if !self.tokenizer.tokens.is_empty() {
+                let token = self.tokenizer.tokens.remove(0);
+                return match self.check_token(&token) {
+                    Ok(()) => Some(Ok(token)),
+                    Err(e) => self.fail(e),
+                };
+            }
+            if self.finished {
+                return None;
+            }
+
+            let result = match self.read_normalized_char() {
+                Ok(Some(c)) => {
+                    let was_making_string = self.tokenizer.is_making_string();
+                    let result = self
+                        .tokenizer
+                        .push_character(c, self.prev_char)
+                        .and_then(|()| self.check_string(c, was_making_string));
+                    self.prev_char = Some(c);
+                    result
+                }
+                Ok(None) => {
+                    self.finished = true;
+                    self.tokenizer.flush()
+                }
+                Err(e) => Err(e),
+            };
+
+            if let Err(e) = result {
+                return self.fail(e);
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum State {
     Comment(CommentState),
@@ -510,6 +824,7 @@ impl State {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::FileId;
     use std::path::PathBuf;
 
     #[test]
@@ -613,6 +928,7 @@ mod tests {
             tokens: vec![],
             original_contents: "\n \n \n \n \n \n".into(),
             next_char_index: 0,
+            backslash_escapes: false,
         };
         assert_eq!(expected, actual);
     }
@@ -628,6 +944,7 @@ mod tests {
             tokens: vec![],
             original_contents: "\n \n \n \n".into(),
             next_char_index: 0,
+            backslash_escapes: false,
         };
         assert_eq!(expected, actual);
     }
@@ -667,7 +984,8 @@ mod tests {
             location: Location {
                 line: 1,
                 column: 0,
-                path: Some(path),
+                file: Some(FileId::new(&path)),
+                expanded_from: None,
             },
         }];
 
@@ -706,7 +1024,8 @@ mod tests {
             location: Location {
                 line: 1,
                 column: 0,
-                path: Some(path),
+                file: Some(FileId::new(&path)),
+                expanded_from: None,
             },
         }];
 
@@ -745,7 +1064,8 @@ mod tests {
             location: Location {
                 line: 1,
                 column: 0,
-                path: Some(path),
+                file: Some(FileId::new(&path)),
+                expanded_from: None,
             },
         }];
 
@@ -771,7 +1091,8 @@ mod tests {
             location: Location {
                 line: 1,
                 column: 0,
-                path: Some(path),
+                file: Some(FileId::new(&path)),
+                expanded_from: None,
             },
         }];
 
@@ -857,7 +1178,8 @@ mod tests {
             location: Location {
                 line: 1,
                 column: 0,
-                path: Some(path),
+                file: Some(FileId::new(&path)),
+                expanded_from: None,
             },
         }]);
         assert_eq!(expected, actual);
@@ -874,7 +1196,8 @@ mod tests {
             location: Location {
                 line: 1,
                 column: 0,
-                path: Some(path),
+                file: Some(FileId::new(&path)),
+                expanded_from: None,
             },
         }]);
         assert_eq!(expected, actual);
@@ -892,7 +1215,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 0,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -900,7 +1224,8 @@ mod tests {
                 location: Location {
                     line: 2,
                     column: 0,
-                    path: Some(path),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
         ]);
@@ -924,7 +1249,8 @@ mod tests {
                 location: Location {
                     line: 2,
                     column: 0,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -932,7 +1258,8 @@ mod tests {
                 location: Location {
                     line: 3,
                     column: 0,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -940,7 +1267,8 @@ mod tests {
                 location: Location {
                     line: 4,
                     column: 0,
-                    path: Some(path),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
         ]);
@@ -964,7 +1292,8 @@ mod tests {
                 location: Location {
                     line: 2,
                     column: 8,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -972,7 +1301,8 @@ mod tests {
                 location: Location {
                     line: 3,
                     column: 8,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -980,7 +1310,8 @@ mod tests {
                 location: Location {
                     line: 4,
                     column: 8,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -988,7 +1319,8 @@ mod tests {
                 location: Location {
                     line: 4,
                     column: 14,
-                    path: Some(path),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
         ]);
@@ -1011,7 +1343,8 @@ mod tests {
                 location: Location {
                     line: 2,
                     column: 0,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -1021,7 +1354,8 @@ mod tests {
                 location: Location {
                     line: 3,
                     column: 0,
-                    path: Some(path),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
         ]);
@@ -1040,7 +1374,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 0,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -1048,7 +1383,8 @@ mod tests {
                 location: Location {
                     line: 2,
                     column: 0,
-                    path: Some(path),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
         ]);
@@ -1077,7 +1413,8 @@ mod tests {
             location: Location {
                 line: 1,
                 column: 0,
-                path: Some(path),
+                file: Some(FileId::new(&path)),
+                expanded_from: None,
             },
         }]);
         assert_eq!(expected, actual);
@@ -1091,11 +1428,12 @@ mod tests {
         let actual = Tokenizer::tokenize(&contents, Some(path.clone()));
         let expected = Ok(vec![
             Token {
-                kind: TokenKind::Identifier('h'.to_string()),
+                kind: TokenKind::Identifier('h'.to_string().into()),
                 location: Location {
                     line: 1,
                     column: 0,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -1103,7 +1441,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 1,
-                    path: Some(path),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
         ]);
@@ -1122,7 +1461,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 0,
-                    path: Some("HelloPath".into()),
+                    file: Some(FileId::new("HelloPath")),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -1130,7 +1470,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 1,
-                    path: Some("HelloPath".into()),
+                    file: Some(FileId::new("HelloPath")),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -1138,7 +1479,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 2,
-                    path: Some("HelloPath".into()),
+                    file: Some(FileId::new("HelloPath")),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -1146,7 +1488,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 3,
-                    path: Some("HelloPath".into()),
+                    file: Some(FileId::new("HelloPath")),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -1154,7 +1497,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 4,
-                    path: Some("HelloPath".into()),
+                    file: Some(FileId::new("HelloPath")),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -1162,15 +1506,17 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 7,
-                    path: Some("HelloPath".into()),
+                    file: Some(FileId::new("HelloPath")),
+                    expanded_from: None,
                 },
             },
             Token {
-                kind: TokenKind::Identifier("fff".to_string()),
+                kind: TokenKind::Identifier("fff".into()),
                 location: Location {
                     line: 1,
                     column: 8,
-                    path: Some("HelloPath".into()),
+                    file: Some(FileId::new("HelloPath")),
+                    expanded_from: None,
                 },
             },
         ]);
@@ -1188,7 +1534,8 @@ mod tests {
             location: Location {
                 line: 1,
                 column: 0,
-                path: Some(path),
+                file: Some(FileId::new(&path)),
+                expanded_from: None,
             },
         }]);
         assert_eq!(expected, actual);
@@ -1206,7 +1553,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 0,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -1214,7 +1562,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 10,
-                    path: Some(path),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
         ]);
@@ -1233,7 +1582,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 0,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -1241,7 +1591,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 4,
-                    path: Some(path),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
         ]);
@@ -1260,7 +1611,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 0,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -1268,7 +1620,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 3,
-                    path: Some(path),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
         ]);
@@ -1288,7 +1641,8 @@ mod tests {
             location: Location {
                 line: 1,
                 column: 0,
-                path: Some(path),
+                file: Some(FileId::new(&path)),
+                expanded_from: None,
             },
         });
         assert_eq!(expected, actual);
@@ -1305,12 +1659,42 @@ mod tests {
             location: Location {
                 line: 1,
                 column: 0,
-                path: Some(path),
+                file: Some(FileId::new(&path)),
+                expanded_from: None,
             },
         }]);
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn tokenize_multibyte_characters() {
+        let contents = "\"héllo\" wörld";
+        let path = PathBuf::from("HelloPath");
+
+        let actual = Tokenizer::tokenize(contents, Some(path.clone()));
+        let expected = Ok(vec![
+            Token {
+                kind: TokenKind::String("héllo".into()),
+                location: Location {
+                    line: 1,
+                    column: 0,
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
+                },
+            },
+            Token {
+                kind: TokenKind::Identifier("wörld".into()),
+                location: Location {
+                    line: 1,
+                    column: 8,
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
+                },
+            },
+        ]);
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn tokenize_single_string() {
         let contents = "\"hello world!\"";
@@ -1322,7 +1706,8 @@ mod tests {
             location: Location {
                 line: 1,
                 column: 0,
-                path: Some(path),
+                file: Some(FileId::new(&path)),
+                expanded_from: None,
             },
         }]);
         assert_eq!(expected, actual);
@@ -1340,7 +1725,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 0,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -1348,7 +1734,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 6,
-                    path: Some(path),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
         ]);
@@ -1367,7 +1754,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 0,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
             Token {
@@ -1375,7 +1763,8 @@ mod tests {
                 location: Location {
                     line: 1,
                     column: 10,
-                    path: Some(path),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
             },
         ]);
@@ -1393,14 +1782,16 @@ mod tests {
                 start: Location {
                     line: 1,
                     column: 0,
-                    path: Some(path.clone()),
+                    file: Some(FileId::new(&path)),
+                    expanded_from: None,
                 },
                 contents: "hello \n world!".into(),
             })),
             location: Location {
                 line: 2,
                 column: 7,
-                path: Some(path.clone()),
+                file: Some(FileId::new(&path)),
+                expanded_from: None,
             },
         });
         assert_eq!(expected, actual);
@@ -1579,4 +1970,111 @@ mod tests {
 
         assert_eq!(expected, tokenizer.pop_state());
     }
+
+    fn stream(contents: &[u8], capacity: usize) -> Vec<Result<Token, Err>> {
+        let reader = std::io::BufReader::with_capacity(capacity, contents);
+        TokenStream::new(reader, Some(PathBuf::from("stream"))).collect()
+    }
+
+    #[test]
+    fn token_stream_matches_tokenize_across_buffer_boundaries() {
+        let contents = "(déf (wörld \"héllo\r\nthere\") ; cömment\r\n  3.14 true)\r(x)";
+        let expected = Tokenizer::tokenize(contents, Some(PathBuf::from("stream"))).unwrap();
+
+        for capacity in [1, 2, 3, 7, 1024] {
+            let actual = stream(contents.as_bytes(), capacity)
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(expected, actual, "capacity {capacity}");
+        }
+    }
+
+    #[test]
+    fn token_stream_returns_err_on_unclosed_string() {
+        let actual = stream(b"(a \"b", 2);
+
+        assert_eq!(3, actual.len());
+        assert_eq!(
+            TokenErr::String(StringErr::Unclosed(StringState {
+                start: Location {
+                    line: 1,
+                    column: 3,
+                    file: Some(FileId::new("stream")),
+                    expanded_from: None,
+                },
+                contents: "b".into(),
+            })),
+            actual[2].clone().unwrap_err().kind
+        );
+    }
+
+    #[test]
+    fn token_stream_returns_err_on_invalid_utf8() {
+        let actual = stream(b"(a \xFF)", 4);
+
+        let err = actual.last().unwrap().clone().unwrap_err();
+        assert_eq!(TokenErr::Read("Invalid UTF-8".into()), err.kind);
+        assert_eq!(3, err.location.column);
+    }
+
+    #[test]
+    fn token_stream_stops_at_first_limit() {
+        let limited = |contents: &str, options: ParseOptions| {
+            let reader = std::io::BufReader::with_capacity(1, contents.as_bytes());
+            TokenStream::with_options(reader, None, &options).collect::<Vec<_>>()
+        };
+        let message = |results: &[Result<Token, Err>]| {
+            let err = results.last().unwrap().clone().unwrap_err();
+            (err.kind.to_string(), err.location.line, err.location.column)
+        };
+
+        let tokens = ParseOptions {
+            max_tokens: Some(2),
+            ..Default::default()
+        };
+        let actual = limited("(a b \"never closed", tokens);
+        assert_eq!(3, actual.len());
+        assert_eq!(
+            ("Input exceeds the maximum of 2 tokens".into(), 1, 3),
+            message(&actual)
+        );
+
+        let depth = ParseOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            ("List nesting exceeds the maximum depth of 1".into(), 1, 3),
+            message(&limited("(a (b))", depth))
+        );
+
+        let string = ParseOptions {
+            max_string_length: Some(3),
+            ..Default::default()
+        };
+        assert!(limited("(\"a\\\"c\")", string.clone())
+            .iter()
+            .all(|r| r.is_ok()));
+        let escapes = ParseOptions {
+            backslash_escapes: true,
+            ..string.clone()
+        };
+        assert!(limited("(\"a\\\\c\")", escapes).iter().all(|r| r.is_ok()));
+        let actual = limited("(\"abcd and on", string);
+        assert_eq!(2, actual.len());
+        assert_eq!(
+            ("String exceeds the maximum length of 3 bytes".into(), 1, 1),
+            message(&actual)
+        );
+
+        let bytes = ParseOptions {
+            max_input_bytes: Some(8),
+            ..Default::default()
+        };
+        assert_eq!(
+            ("Input exceeds the maximum size of 8 bytes".into(), 2, 2),
+            message(&limited("(a b)\n(c d)", bytes))
+        );
+    }
 }
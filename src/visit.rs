@@ -0,0 +1,442 @@
+//! Traversals over node trees, so analyses and rewriters don't each recurse over `Ast::List`.
+//!
+//! [`walk`] and [`walk_mut`] call a [`Visit`] or [`VisitMut`] for every node, parents before
+//! children, with [`transform`] rebuilding a tree bottom up through a [`Transform`]. Each has a
+//! default method per [`Ast`] variant, so implementations only handle the variants they need.
+//! The walks track their position in a heap-allocated stack of lists, so trees nested as deep
+//! as memory allows can be visited.
+use crate::{Ast, Node, Symbol};
+
+/// Reads a tree of nodes.
+pub trait Visit {
+    /// Called before the node and its children. Returning false skips them, though `leave` is
+    /// still called.
+    fn enter(&mut self, _node: &Node) -> bool {
+        true
+    }
+
+    /// Called after the node and its children.
+    fn leave(&mut self, _node: &Node) {}
+
+    fn visit_bool(&mut self, _value: bool, _node: &Node) {}
+
+    fn visit_comment(&mut self, _comment: &str, _node: &Node) {}
+
+    fn visit_identifier(&mut self, _id: Symbol, _node: &Node) {}
+
+    /// Called before the children are visited.
+    fn visit_list(&mut self, _children: &[Node], _node: &Node) {}
+
+    fn visit_number(&mut self, _value: f64, _node: &Node) {}
+
+    fn visit_string(&mut self, _value: &str, _node: &Node) {}
+}
+
+/// Edits a tree of nodes in place.
+pub trait VisitMut {
+    /// Called before the node and its children. Returning false skips them, though `leave` is
+    /// still called.
+    fn enter(&mut self, _node: &mut Node) -> bool {
+        true
+    }
+
+    /// Called after the node and its children.
+    fn leave(&mut self, _node: &mut Node) {}
+
+    fn visit_bool(&mut self, _value: &mut bool) {}
+
+    fn visit_comment(&mut self, _comment: &mut String) {}
+
+    fn visit_identifier(&mut self, _id: &mut Symbol) {}
+
+    /// Called before the children are visited, so children added or removed here are walked
+    /// as they are afterwards.
+    fn visit_list(&mut self, _children: &mut Vec<Node>) {}
+
+    fn visit_number(&mut self, _value: &mut f64) {}
+
+    fn visit_string(&mut self, _value: &mut String) {}
+}
+
+/// Rebuilds a tree of nodes, replacing children before their parents.
+pub trait Transform {
+    /// Called with the node before its children are transformed.
+    fn enter(&mut self, node: Node) -> Node {
+        node
+    }
+
+    fn fold_bool(&mut self, node: Node) -> Node {
+        node
+    }
+
+    fn fold_comment(&mut self, node: Node) -> Node {
+        node
+    }
+
+    fn fold_identifier(&mut self, node: Node) -> Node {
+        node
+    }
+
+    /// Called with the list once its children have been transformed.
+    fn fold_list(&mut self, node: Node) -> Node {
+        node
+    }
+
+    fn fold_number(&mut self, node: Node) -> Node {
+        node
+    }
+
+    fn fold_string(&mut self, node: Node) -> Node {
+        node
+    }
+}
+
+/// Visits the node and every node within it.
+pub fn walk<V: Visit + ?Sized>(visitor: &mut V, node: &Node) {
+    enum Item<'a> {
+        Enter(&'a Node),
+        Leave(&'a Node),
+    }
+
+    let mut stack = vec![Item::Enter(node)];
+    while let Some(item) = stack.pop() {
+        let node = match item {
+            Item::Enter(node) => node,
+            Item::Leave(node) => {
+                visitor.leave(node);
+                continue;
+            }
+        };
+
+        stack.push(Item::Leave(node));
+        if !visitor.enter(node) {
+            continue;
+        }
+        match &node.ast {
+            Ast::Bool(value) => visitor.visit_bool(*value, node),
+            Ast::Comment(comment) => visitor.visit_comment(comment, node),
+            Ast::Identifier(id) => visitor.visit_identifier(*id, node),
+            Ast::List(children) => {
+                visitor.visit_list(children, node);
+                stack.extend(children.iter().rev().map(Item::Enter));
+            }
+            Ast::Number(value) => visitor.visit_number(*value, node),
+            Ast::String(value) => visitor.visit_string(value, node),
+        }
+    }
+}
+
+/// Visits the node and every node within it, allowing each to be edited.
+pub fn walk_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut Node) {
+    /// A list's children, taken out of the list while they're walked.
+    struct Frame {
+        children: Vec<Node>,
+        /// The index of the next child to walk.
+        next: usize,
+    }
+
+    // Taking the children out lets each be edited without holding a reference into the tree,
+    // and only moves the list's Vec rather than the nodes in it
+    let mut stack: Vec<Frame> = vec![];
+    if let Some(children) = enter_mut(visitor, node) {
+        stack.push(Frame { children, next: 0 });
+    }
+    while let Some(frame) = stack.last_mut() {
+        if let Some(child) = frame.children.get_mut(frame.next) {
+            frame.next += 1;
+            if let Some(children) = enter_mut(visitor, child) {
+                stack.push(Frame { children, next: 0 });
+            }
+            continue;
+        }
+
+        let frame = stack.pop().unwrap();
+        let list = match stack.last_mut() {
+            Some(parent) => &mut parent.children[parent.next - 1],
+            None => &mut *node,
+        };
+        list.ast = Ast::List(frame.children);
+        visitor.leave(list);
+    }
+}
+
+/// Enters and visits the node, returning its children if they should be walked. Otherwise the
+/// node is left straight away.
+fn enter_mut<V: VisitMut + ?Sized>(visitor: &mut V, node: &mut Node) -> Option<Vec<Node>> {
+    if visitor.enter(node) {
+        match &mut node.ast {
+            Ast::Bool(value) => visitor.visit_bool(value),
+            Ast::Comment(comment) => visitor.visit_comment(comment),
+            Ast::Identifier(id) => visitor.visit_identifier(id),
+            Ast::List(children) => {
+                visitor.visit_list(children);
+                return Some(std::mem::take(children));
+            }
+            Ast::Number(value) => visitor.visit_number(value),
+            Ast::String(value) => visitor.visit_string(value),
+        }
+    }
+    visitor.leave(node);
+    None
+}
+
+/// Transforms the node and every node within it, returning the rebuilt tree.
+pub fn transform<T: Transform + ?Sized>(transformer: &mut T, node: Node) -> Node {
+    struct Transformer<'a, T: ?Sized>(&'a mut T);
+    impl<T: Transform + ?Sized> Rebuild for Transformer<'_, T> {
+        fn enter(&mut self, node: &mut Node) -> bool {
+            let taken = std::mem::replace(node, empty());
+            *node = self.0.enter(taken);
+            true
+        }
+
+        fn leave(&mut self, node: Node) -> Node {
+            match node.ast {
+                Ast::Bool(_) => self.0.fold_bool(node),
+                Ast::Comment(_) => self.0.fold_comment(node),
+                Ast::Identifier(_) => self.0.fold_identifier(node),
+                Ast::List(_) => self.0.fold_list(node),
+                Ast::Number(_) => self.0.fold_number(node),
+                Ast::String(_) => self.0.fold_string(node),
+            }
+        }
+    }
+
+    rebuild(&mut Transformer(transformer), node)
+}
+
+/// Hooks for [`rebuild`].
+trait Rebuild {
+    /// Called before the node's children, returning whether to walk them.
+    fn enter(&mut self, node: &mut Node) -> bool;
+
+    /// Called once the node's children have been rebuilt.
+    fn leave(&mut self, node: Node) -> Node;
+}
+
+/// Takes each list's children out while they're rebuilt, then puts them back, so the tree can be
+/// edited without holding references into it.
+fn rebuild<R: Rebuild>(hooks: &mut R, node: Node) -> Node {
+    struct Frame {
+        node: Node,
+        children: std::vec::IntoIter<Node>,
+        rebuilt: Vec<Node>,
+    }
+
+    let mut stack: Vec<Frame> = vec![];
+    let mut next = Some(node);
+    loop {
+        let finished = match next.take() {
+            Some(mut node) => {
+                let walk_children = hooks.enter(&mut node);
+                match &mut node.ast {
+                    Ast::List(children) if walk_children => {
+                        let children = std::mem::take(children);
+                        stack.push(Frame {
+                            rebuilt: Vec::with_capacity(children.len()),
+                            children: children.into_iter(),
+                            node,
+                        });
+                        continue;
+                    }
+                    _ => hooks.leave(node),
+                }
+            }
+            None => {
+                let frame = stack.last_mut().expect("a list is being rebuilt");
+                if let Some(child) = frame.children.next() {
+                    next = Some(child);
+                    continue;
+                }
+
+                let mut frame = stack.pop().unwrap();
+                if let Ast::List(children) = &mut frame.node.ast {
+                    *children = frame.rebuilt;
+                }
+                hooks.leave(frame.node)
+            }
+        };
+
+        match stack.last_mut() {
+            Some(frame) => frame.rebuilt.push(finished),
+            None => return finished,
+        }
+    }
+}
+
+/// A node to leave in place of one being rebuilt.
+fn empty() -> Node {
+    Node {
+        ast: Ast::List(vec![]),
+        tokens: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::TokenKind;
+
+    fn node(contents: &str) -> Node {
+        crate::parse_str(contents).unwrap().remove(0).into_node()
+    }
+
+    #[derive(Default)]
+    struct Trace(Vec<String>);
+    impl Visit for Trace {
+        fn enter(&mut self, node: &Node) -> bool {
+            self.0.push(format!("enter {node}"));
+            !matches!(&node.ast, Ast::List(c) if c.first().is_some_and(|n| n.to_string() == "skip"))
+        }
+
+        fn leave(&mut self, node: &Node) {
+            self.0.push(format!("leave {node}"));
+        }
+
+        fn visit_identifier(&mut self, id: Symbol, _node: &Node) {
+            self.0.push(format!("id {id}"));
+        }
+
+        fn visit_number(&mut self, value: f64, _node: &Node) {
+            self.0.push(format!("number {value}"));
+        }
+    }
+
+    #[test]
+    fn walk_visits_parents_before_children() {
+        let mut trace = Trace::default();
+
+        walk(&mut trace, &node("(a (skip b) 1)"));
+
+        assert_eq!(
+            vec![
+                "enter (a (skip b) 1)",
+                "enter a",
+                "id a",
+                "leave a",
+                "enter (skip b)",
+                "leave (skip b)",
+                "enter 1",
+                "number 1",
+                "leave 1",
+                "leave (a (skip b) 1)",
+            ],
+            trace.0
+        );
+    }
+
+    #[test]
+    fn walk_mut_edits_nodes_in_place() {
+        struct Rename;
+        impl VisitMut for Rename {
+            fn visit_identifier(&mut self, id: &mut Symbol) {
+                if *id == "old" {
+                    *id = Symbol::new("new");
+                }
+            }
+
+            fn visit_list(&mut self, children: &mut Vec<Node>) {
+                children.retain(|n| !matches!(n.ast, Ast::Number(_)));
+            }
+        }
+        let mut node = node("(old 1 (old \"old\" 2))");
+
+        walk_mut(&mut Rename, &mut node);
+
+        assert_eq!("(new (new \"old\"))", node.to_string());
+    }
+
+    #[test]
+    fn walk_mut_leaves_lists_with_their_children() {
+        #[derive(Default)]
+        struct Trace(Vec<String>);
+        impl VisitMut for Trace {
+            fn enter(&mut self, node: &mut Node) -> bool {
+                self.0.push(format!("enter {node}"));
+                !matches!(&node.ast, Ast::List(c) if c.first().is_some_and(|n| n.to_string() == "skip"))
+            }
+
+            fn leave(&mut self, node: &mut Node) {
+                self.0.push(format!("leave {node}"));
+            }
+        }
+        let mut trace = Trace::default();
+
+        walk_mut(&mut trace, &mut node("((a) (skip b))"));
+
+        assert_eq!(
+            vec![
+                "enter ((a) (skip b))",
+                "enter (a)",
+                "enter a",
+                "leave a",
+                "leave (a)",
+                "enter (skip b)",
+                "leave (skip b)",
+                "leave ((a) (skip b))",
+            ],
+            trace.0
+        );
+    }
+
+    #[test]
+    fn transform_rebuilds_children_before_parents() {
+        struct Sum;
+        impl Transform for Sum {
+            fn fold_list(&mut self, node: Node) -> Node {
+                let children = node.as_list_ref().unwrap();
+                if children.first().and_then(|n| n.as_symbol().ok()) != Some(Symbol::new("+")) {
+                    return node;
+                }
+                let sum = children[1..].iter().map(|n| n.as_number().unwrap()).sum();
+                Node {
+                    ast: Ast::Number(sum),
+                    tokens: vec![crate::tokenizer::Token {
+                        kind: TokenKind::Number(sum),
+                        location: node.first_location(),
+                    }],
+                }
+            }
+        }
+
+        let node = transform(&mut Sum, node("(x (+ 1 (+ 2 3)) (y))"));
+
+        assert_eq!("(x 6 (y))", node.to_string());
+    }
+
+    #[test]
+    fn deeply_nested_lists_do_not_overflow_the_stack() {
+        let depth = 100_000;
+        let mut node = Node {
+            ast: Ast::List(vec![]),
+            tokens: vec![],
+        };
+        for _ in 0..depth {
+            node = Node {
+                ast: Ast::List(vec![node]),
+                tokens: vec![],
+            };
+        }
+
+        #[derive(Default)]
+        struct Count(usize);
+        impl Visit for Count {
+            fn visit_list(&mut self, _children: &[Node], _node: &Node) {
+                self.0 += 1;
+            }
+        }
+        impl VisitMut for Count {
+            fn visit_list(&mut self, _children: &mut Vec<Node>) {
+                self.0 += 1;
+            }
+        }
+        impl Transform for Count {}
+
+        let mut count = Count::default();
+        walk(&mut count, &node);
+        walk_mut(&mut count, &mut node);
+        let node = transform(&mut count, node);
+        assert_eq!(2 * (depth + 1), count.0);
+        assert!(matches!(node.ast, Ast::List(_)));
+    }
+}
@@ -0,0 +1,270 @@
+use crate::{load::load_file, Error, List, Loader, Location, Symlinks};
+use notify::{
+    event::{AccessKind, AccessMode},
+    EventKind, RecursiveMode, Watcher as _,
+};
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    time::Duration,
+};
+
+/// A file that changed and the result of parsing it again.
+/// Removed files are delivered as having no lists.
+pub type WatchEvent = (PathBuf, Result<Vec<List>, Error>);
+
+/// Watches directories, sending an event whenever a file is added, changed or removed.
+/// Events stop once this is dropped.
+pub struct Watcher {
+    _watcher: notify::RecommendedWatcher,
+    events: Receiver<WatchEvent>,
+}
+
+impl Watcher {
+    /// Returns the channel events are delivered through.
+    pub fn events(&self) -> &Receiver<WatchEvent> {
+        &self.events
+    }
+}
+
+impl Loader {
+    /// Watches the directories, parsing files that pass this loader's filters again when they change.
+    /// Changes are batched until no more arrive for the debounce duration, so a burst of saves
+    /// to the same file only parses it once. Event paths are absolute, since that's how they're
+    /// reported by the file system.
+    pub fn watch(&self, dirs: &[PathBuf], debounce: Duration) -> Result<Watcher, Error> {
+        let dirs = dirs
+            .iter()
+            .map(|dir| {
+                dir.canonicalize().map_err(|e| {
+                    Error::new(
+                        format!("Error reading directory '{:?}': {e}", dir),
+                        Location::new(Some(dir.clone())),
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut known = BTreeSet::new();
+        for dir in &dirs {
+            known.extend(self.files(dir)?);
+        }
+
+        let (sender, changes) = channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+        for dir in &dirs {
+            watcher
+                .watch(dir, RecursiveMode::Recursive)
+                .map_err(watch_error)?;
+        }
+
+        let (events, receiver) = channel();
+        let worker = Worker {
+            loader: self.clone(),
+            dirs,
+            known,
+            events,
+        };
+        std::thread::spawn(move || worker.run(changes, debounce));
+
+        Ok(Watcher {
+            _watcher: watcher,
+            events: receiver,
+        })
+    }
+}
+
+/// Watches the directory for changes to files with the extension, matching the files `load_directory` loads.
+pub fn watch_directory(
+    extension: &str,
+    location: PathBuf,
+    debounce: Duration,
+) -> Result<Watcher, Error> {
+    let mut loader = Loader::new();
+    loader
+        .extension(extension)
+        .ignore_files(false)
+        .hidden(true)
+        .symlinks(Symlinks::Follow);
+    loader.watch(&[location], debounce)
+}
+
+/// Turns batches of file system changes into events.
+struct Worker {
+    loader: Loader,
+    dirs: Vec<PathBuf>,
+    /// The files loaded as of the last batch.
+    known: BTreeSet<PathBuf>,
+    events: Sender<WatchEvent>,
+}
+
+impl Worker {
+    /// Runs until the watcher or the event receiver is dropped.
+    fn run(mut self, changes: Receiver<notify::Result<notify::Event>>, debounce: Duration) {
+        while let Ok(change) = changes.recv() {
+            let mut changed = BTreeSet::new();
+            let mut errors = vec![];
+            let mut next = Ok(change);
+            loop {
+                match next {
+                    Ok(Ok(event)) if is_change(&event.kind) => changed.extend(event.paths),
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => errors.push(e),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+                next = changes.recv_timeout(debounce);
+            }
+
+            for e in errors {
+                let path = e.paths.first().cloned().unwrap_or_default();
+                if self.events.send((path, Err(watch_error(e)))).is_err() {
+                    return;
+                }
+            }
+            if changed.is_empty() {
+                continue;
+            }
+            if !self.send_batch(&changed) {
+                return;
+            }
+        }
+    }
+
+    /// Sends an event for each changed, added or removed file, returning false if nothing is listening.
+    fn send_batch(&mut self, changed: &BTreeSet<PathBuf>) -> bool {
+        // Walk again rather than matching each path so ignore files and new directories are handled
+        let mut files = BTreeSet::new();
+        for dir in &self.dirs {
+            match self.loader.files(dir) {
+                Ok(found) => files.extend(found),
+                Err(e) => return self.events.send((dir.clone(), Err(e))).is_ok(),
+            }
+        }
+
+        let cache = &self.loader.options.cache;
+        let file_system = self.loader.file_system();
+        for file in &files {
+            if changed.contains(file) || !self.known.contains(file) {
+                let result = load_file(&file_system, file.clone(), cache).map(|f| f.lists);
+                if self.events.send((file.clone(), result)).is_err() {
+                    return false;
+                }
+            }
+        }
+        for file in self.known.difference(&files) {
+            if self.events.send((file.clone(), Ok(vec![]))).is_err() {
+                return false;
+            }
+        }
+
+        self.known = files;
+        true
+    }
+}
+
+/// Returns whether the event may have changed a file's contents, ignoring reads.
+fn is_change(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+        EventKind::Access(_) => false,
+        _ => true,
+    }
+}
+
+fn watch_error(e: notify::Error) -> Error {
+    let path = e.paths.first().cloned();
+    Error::new(format!("Error watching files: {e}"), Location::new(path))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    const DEBOUNCE: Duration = Duration::from_millis(100);
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn next(watcher: &Watcher) -> (PathBuf, Result<Vec<String>, String>) {
+        let (path, result) = watcher.events().recv_timeout(TIMEOUT).unwrap();
+        let result = result
+            .map(|lists| lists.iter().map(|l| l.to_string()).collect())
+            .map_err(|e| e.message);
+        (path, result)
+    }
+
+    fn assert_quiet(watcher: &Watcher) {
+        let event = watcher.events().recv_timeout(DEBOUNCE * 3);
+        assert!(event.is_err(), "unexpected event {event:?}");
+    }
+
+    #[test]
+    fn watch_directory_sends_changed_files() {
+        let dir = TempDir::new("watch-changes");
+        let file = dir.join("a.lisp");
+        std::fs::write(&file, "(a 1)").unwrap();
+        let watcher = watch_directory("lisp", dir.to_path_buf(), DEBOUNCE).unwrap();
+
+        std::fs::write(&file, "(a 2)").unwrap();
+        assert_eq!((file.clone(), Ok(vec!["(a 2)".into()])), next(&watcher));
+
+        std::fs::write(&file, "(a").unwrap();
+        assert_eq!((file.clone(), Err("Unclosed list".into())), next(&watcher));
+
+        std::fs::write(dir.join("notes.txt"), "(ignored)").unwrap();
+        assert_quiet(&watcher);
+
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!((file, Ok(vec![])), next(&watcher));
+    }
+
+    #[test]
+    fn watch_directory_debounces_rapid_saves() {
+        let dir = TempDir::new("watch-debounce");
+        let file = dir.join("a.lisp");
+        std::fs::write(&file, "(a 0)").unwrap();
+        let watcher = watch_directory("lisp", dir.to_path_buf(), DEBOUNCE).unwrap();
+
+        for i in 1..=5 {
+            std::fs::write(&file, format!("(a {i})")).unwrap();
+        }
+
+        assert_eq!((file, Ok(vec!["(a 5)".into()])), next(&watcher));
+        assert_quiet(&watcher);
+    }
+
+    #[test]
+    fn watch_sends_files_in_new_directories() {
+        let dir = TempDir::new("watch-new-dirs");
+        let watcher = Loader::new()
+            .extension("lisp")
+            .watch(&[dir.to_path_buf()], DEBOUNCE)
+            .unwrap();
+
+        let file = dir.join("mods/b.lisp");
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, "(b)").unwrap();
+
+        assert_eq!((file, Ok(vec!["(b)".into()])), next(&watcher));
+    }
+
+    #[test]
+    fn watch_sends_changes_in_relative_directories() {
+        let dir = TempDir::new("watch-relative");
+        let cwd = std::env::current_dir().unwrap();
+        let relative: PathBuf = cwd
+            .components()
+            .skip(1)
+            .map(|_| "..".as_ref())
+            .chain(dir.strip_prefix("/"))
+            .collect();
+        let file = dir.join("a.lisp");
+        std::fs::write(&file, "(a 1)").unwrap();
+        let watcher = watch_directory("lisp", relative, DEBOUNCE).unwrap();
+
+        std::fs::write(&file, "(a 2)").unwrap();
+
+        assert_eq!((file, Ok(vec!["(a 2)".into()])), next(&watcher));
+        assert_quiet(&watcher);
+    }
+}